@@ -0,0 +1,308 @@
+// Path tracing integrator shared by the ray tracing pipeline and the compute fallback.
+//
+// The including shader must define `trace_scene`, which finds the closest intersection
+// of a ray with the scene, and call `render_pixel` from its entry point.
+
+struct Camera {
+    mat4 view_proj;     // Camera view * projection
+    mat4 inverse_view;  // Camera inverse view matrix
+    mat4 inverse_proj;  // Camera inverse projection matrix
+};
+
+struct World {
+    vec3 top_color;
+    vec3 bottom_color;
+    uint is_transparent;  // The world emits no light and camera rays that miss get zero alpha
+};
+
+struct Material {
+    vec3 albedo;
+    float roughness;
+    float metallic;
+    vec3 emission_color;
+    float emission_strength;
+    float transmission;
+    float ior;
+    // HACK: This is done to fix an issue with the shader compiler and the data layout it generates
+    vec2 _padding;
+};
+
+struct RendererProperties {
+    uint max_bounces;  // Limit on the total number of rays traced along a path
+    uint seed;
+    uint russian_roulette;  // Randomly terminate paths that carry little light
+    uint russian_roulette_min_depth;
+    uint max_diffuse_bounces;
+    uint max_glossy_bounces;
+    uint max_transmission_bounces;
+    float clamp_direct;    // Maximum brightness of direct light contributions, 0 if unclamped
+    float clamp_indirect;  // Maximum brightness of indirect light contributions, 0 if unclamped
+    uint reconstruction_filter;  // Pixel reconstruction filter, one of the `FILTER_*` constants
+};
+
+// The samples traced by one dispatch, they are added to the samples already in the image.
+struct SampleBatch {
+    uint first_sample;
+    uint sample_count;
+};
+
+// Counters of the traced rays and paths, read back by the host for profiling.
+// Must match the `STATISTIC_*` constants in `vulkan.rs`.
+const uint STATISTIC_PATHS = 0;
+const uint STATISTIC_PRIMARY_RAYS = 1;
+const uint STATISTIC_SECONDARY_RAYS = 2;
+const uint STATISTIC_INTERSECTION_TESTS = 3;
+const uint STATISTIC_BVH_NODES_VISITED = 4;
+// The first bin of the path length histogram, the last bin counts all longer paths
+const uint STATISTIC_PATH_LENGTHS = 5;
+const uint PATH_LENGTH_BINS = 16;  // Must match `PATH_LENGTH_BINS` in `timing.rs`
+const uint STATISTIC_COUNT = STATISTIC_PATH_LENGTHS + PATH_LENGTH_BINS;
+
+// Every counter is split into a low and a high word, because 64-bit atomics are not available everywhere.
+struct PathStatistics {
+    uint counters[2 * STATISTIC_COUNT];
+};
+
+layout(set = 0, binding = 1) uniform _Camera { Camera camera; };
+layout(set = 0, binding = 2) uniform _World { World world; };
+layout(set = 0, binding = 5) buffer _Material { Material[] materials; };
+layout(set = 0, binding = 7) uniform _RendererProperties { RendererProperties renderer_properties; };
+layout(set = 0, binding = 11) buffer _PathStatistics { PathStatistics path_statistics; };
+layout(set = 1, binding = 0, rgba32f) uniform image2D image;
+layout(push_constant) uniform _SampleBatch { SampleBatch sample_batch; };
+
+vec3 sample_world(vec3 origin, vec3 direction) {
+    vec3 up = vec3(0.0, 1.0, 0.0);
+    float cosine_similarity = dot(direction, up) / (length(direction) * length(up));
+    vec3 sky_color = mix(world.bottom_color, world.top_color, (cosine_similarity + 1.0) * 0.5);
+    return sky_color;
+}
+
+// The distance bounced rays are moved away from the surface to avoid self-intersections.
+// Must match `RAY_OFFSET` in the CPU renderer.
+const float RAY_OFFSET = 0.001;
+
+uint rng_state;
+
+uint pcg_hash() {
+    uint state = rng_state;
+	rng_state = rng_state * uint(747796405) + uint(2891336453);
+	uint word = ((state >> ((state >> uint(28)) + uint(4))) ^ state) * uint(277803737);
+	return (word >> uint(22)) ^ word;
+}
+
+float rand() {
+    uint seed = pcg_hash();
+    const float UINT_MAX = 4294967295.0;
+    return float(seed) / UINT_MAX;
+}
+
+float rand_range(float min, float max) {
+    return min + (max - min) * rand();
+}
+
+#include "sampling.glsl"
+
+bool can_refract(vec3 ray_direction, vec3 normal, float ior_ratio) {
+    float cos_theta = min(dot(ray_direction, -normal), 1.0);
+    float sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    return ior_ratio * sin_theta <= 1.0;
+}
+
+// Clamps the light a path picked up after `bounces` bounces, keeping its hue.
+// Light that reaches the camera after at most one bounce is direct, the rest is indirect.
+vec3 clamp_contribution(vec3 contribution, uint bounces) {
+    float limit = bounces <= 1 ? renderer_properties.clamp_direct : renderer_properties.clamp_indirect;
+    float brightness = max(contribution.r, max(contribution.g, contribution.b));
+    if (limit > 0.0 && brightness > limit) {
+        return contribution * (limit / brightness);
+    }
+    return contribution;
+}
+
+// The kinds of scattering a bounce performs, each kind has its own bounce limit
+const uint LOBE_DIFFUSE = 0;
+const uint LOBE_GLOSSY = 1;
+const uint LOBE_TRANSMISSION = 2;
+
+// Counted by `trace_scene` if it traverses the scene itself, hardware ray tracing does not expose them
+uint intersection_tests;
+uint bvh_nodes_visited;
+
+HitRecord trace_scene(vec3 origin, vec3 direction, float t_min, float t_max);
+
+// Adds to a counter of the path statistics, carrying into the high word when the low word overflows
+void add_statistic(uint statistic, uint value) {
+    if (value == 0) {
+        return;
+    }
+    uint previous = atomicAdd(path_statistics.counters[2 * statistic], value);
+    if (previous + value < previous) {
+        atomicAdd(path_statistics.counters[2 * statistic + 1], 1);
+    }
+}
+
+void render_pixel(uvec2 pixel, uvec2 size) {
+    // Hash the pixel index together with the seed, so that neighbouring pixels get uncorrelated sequences
+    rng_state = pixel.x + pixel.y * size.x;
+    rng_state = pcg_hash() ^ renderer_properties.seed;
+    // Every batch continues with a different sequence
+    rng_state = pcg_hash() ^ sample_batch.first_sample;
+    rng_state = pcg_hash();
+
+    vec4 accumulated_light = vec4(0.0);
+    uint primary_rays = 0;
+    uint secondary_rays = 0;
+    uint path_lengths[PATH_LENGTH_BINS];
+    for (uint bin = 0; bin < PATH_LENGTH_BINS; ++bin) {
+        path_lengths[bin] = 0;
+    }
+    intersection_tests = 0;
+    bvh_nodes_visited = 0;
+
+    for (uint samples = 0; samples < sample_batch.sample_count; ++samples) {
+        const vec2 pixel_center = vec2(pixel) + vec2(0.5);
+        const vec2 in_uv = (pixel_center + pixel_filter(renderer_properties.reconstruction_filter)) / vec2(size);
+        vec2 d = in_uv * 2.0 - 1.0;
+
+        vec4 ray_origin = camera.inverse_view * vec4(0, 0, 0, 1);
+        vec4 ray_target = camera.inverse_proj * vec4(d.x, d.y, 1, 1);
+        vec4 ray_direction = camera.inverse_view * vec4(normalize(ray_target.xyz), 0);
+
+        vec3 light = vec3(0.0);
+        vec3 attenuation = vec3(1.0);
+        float alpha = 1.0;
+        uint diffuse_bounces = 0;
+        uint glossy_bounces = 0;
+        uint transmission_bounces = 0;
+        uint path_length = 0;
+
+        for (uint bounces = 0; bounces < renderer_properties.max_bounces; ++bounces) {
+            ++path_length;
+            HitRecord hit_record = trace_scene(ray_origin.xyz, ray_direction.xyz, RAY_OFFSET, 10000.0);
+
+            if (hit_record.is_hit) {
+                Material material = materials[hit_record.material_index];
+                
+                // The roughness is squared to achieve perceptual linearity.
+                // (based on https://www.pbr-book.org/3ed-2018/Reflection_Models/Microfacet_Models.html
+                //           https://www.pbr-book.org/4ed/Reflection_Models/Roughness_Using_Microfacet_Theory
+                //           and research by Disney)
+                float roughness = material.roughness * material.roughness;
+                float metallic = material.metallic;
+                float transmission = material.transmission;
+
+                vec3 normal = hit_record.world_normal;
+                vec3 incident_direction = normalize(ray_direction.xyz);
+
+                vec3 diffuse_direction = cosine_hemisphere(normal);
+
+                // The specular and transmission lobes scatter off a microfacet normal sampled
+                // from the visible normals of a GGX distribution. Without roughness, the
+                // distribution collapses to the surface normal.
+                vec3 microfacet_normal = roughness > 0.0 ? ggx_vndf(-incident_direction, normal, roughness) : normal;
+
+                vec3 specular_direction = reflect(incident_direction, microfacet_normal);
+                // Reflections off steep microfacets can point into the surface, mirror them
+                // back out instead of losing their energy.
+                if (dot(specular_direction, normal) <= 0.0) {
+                    specular_direction = reflect(specular_direction, normal);
+                }
+
+                bool transmission_ray = rand() < transmission;
+                vec3 direction = specular_direction;
+                uint lobe = LOBE_GLOSSY;
+                if (transmission_ray) {
+                    float ior = material.ior;
+                    if (hit_record.is_front_face) {
+                        ior = 1.0 / ior;
+                    }
+
+                    // Apply Schlick's approximation for the Fresnel effect.
+                    float cos_theta = min(dot(incident_direction, -microfacet_normal), 1.0);
+                    float r0 = pow((ior - 1.0) / (ior + 1.0), 2.0);
+                    float reflection_coefficient = r0 + (1.0 - r0) * pow(1.0 - cos_theta, 5.0);
+
+                    if (reflection_coefficient < rand() && can_refract(incident_direction, microfacet_normal, ior)) {
+                        vec3 refracted = refract(incident_direction, microfacet_normal, ior);
+                        // Refractions off steep microfacets can point out of the surface
+                        if (dot(refracted, normal) < 0.0) {
+                            direction = refracted;
+                            lobe = LOBE_TRANSMISSION;
+                        }
+                    }
+                } else if (rand() < metallic) {
+                    // Specular reflection
+                } else if (rand() < roughness) {
+                    direction = diffuse_direction;
+                    lobe = LOBE_DIFFUSE;
+                }
+
+                // Move the ray origin slightly along the direction of travel to avoid self-intersections
+                vec3 offset = transmission_ray ? direction : normal;
+                
+                ray_origin = vec4(hit_record.world_position + offset * RAY_OFFSET, 1.0);
+                ray_direction = vec4(direction, 0.0);
+                float length2 = dot(ray_direction.xyz, ray_direction.xyz);
+                if (length2 < 1e-10) {
+                    ray_direction.xyz = normal;
+                }
+
+                // Emitted light is attenuated by the surfaces it bounced off on its way to the camera
+                light += clamp_contribution(material.emission_color * material.emission_strength * attenuation, bounces);
+
+                attenuation *= material.albedo;
+
+                // End the path once it exceeds the limit of the kind of bounce it is making
+                if (lobe == LOBE_DIFFUSE && ++diffuse_bounces > renderer_properties.max_diffuse_bounces) {
+                    break;
+                }
+                if (lobe == LOBE_GLOSSY && ++glossy_bounces > renderer_properties.max_glossy_bounces) {
+                    break;
+                }
+                if (lobe == LOBE_TRANSMISSION && ++transmission_bounces > renderer_properties.max_transmission_bounces) {
+                    break;
+                }
+
+                // Russian roulette: terminate paths that carry little light with a probability
+                // that grows as their throughput falls, and boost the surviving ones by the
+                // inverse of their survival probability to keep the estimate unbiased.
+                if (renderer_properties.russian_roulette != 0 && bounces + 1 >= renderer_properties.russian_roulette_min_depth) {
+                    float survival_probability = min(max(attenuation.r, max(attenuation.g, attenuation.b)), 1.0);
+                    if (rand() >= survival_probability) {
+                        break;
+                    }
+                    attenuation /= survival_probability;
+                }
+            } else {
+                light += clamp_contribution(sample_world(ray_origin.xyz, ray_direction.xyz) * attenuation, bounces);
+                if (bounces == 0 && world.is_transparent != 0) {
+                    alpha = 0.0;
+                }
+                break;
+            }
+        }
+
+        accumulated_light += vec4(light, alpha);
+        if (path_length > 0) {
+            ++primary_rays;
+            secondary_rays += path_length - 1;
+        }
+        ++path_lengths[min(path_length, PATH_LENGTH_BINS - 1)];
+    }
+
+    // The accumulated sum is averaged on the host, in the same way as the CPU renderer's frame buffer.
+    if (sample_batch.first_sample > 0) {
+        accumulated_light += imageLoad(image, ivec2(pixel));
+    }
+    imageStore(image, ivec2(pixel), accumulated_light);
+
+    add_statistic(STATISTIC_PATHS, sample_batch.sample_count);
+    add_statistic(STATISTIC_PRIMARY_RAYS, primary_rays);
+    add_statistic(STATISTIC_SECONDARY_RAYS, secondary_rays);
+    add_statistic(STATISTIC_INTERSECTION_TESTS, intersection_tests);
+    add_statistic(STATISTIC_BVH_NODES_VISITED, bvh_nodes_visited);
+    for (uint bin = 0; bin < PATH_LENGTH_BINS; ++bin) {
+        add_statistic(STATISTIC_PATH_LENGTHS + bin, path_lengths[bin]);
+    }
+}
@@ -0,0 +1,112 @@
+// Sampling routines used by the path tracing integrator.
+//
+// Mirrors `utils::sampling` of the CPU renderer, see there for the densities of the samples.
+// The including shader must define `rand`, which returns a uniform random number in [0, 1].
+
+const float PI = 3.14159265358979323846;
+
+// Pixel reconstruction filters, must match the order of `Filter` in the render settings
+const uint FILTER_BOX = 0;
+const uint FILTER_TENT = 1;
+const uint FILTER_GAUSSIAN = 2;
+
+// Standard deviation of the Gaussian reconstruction filter in pixels
+const float GAUSSIAN_FILTER_SIGMA = 0.5;
+
+// Builds an orthonormal basis around the unit vector `normal`.
+// (based on "Building an Orthonormal Basis, Revisited" by Duff et al.)
+void orthonormal_basis(vec3 normal, out vec3 tangent, out vec3 bitangent) {
+    float sign_z = normal.z >= 0.0 ? 1.0 : -1.0;
+    float a = -1.0 / (sign_z + normal.z);
+    float b = normal.x * normal.y * a;
+    tangent = vec3(1.0 + sign_z * normal.x * normal.x * a, sign_z * b, -sign_z * normal.x);
+    bitangent = vec3(b, sign_z + normal.y * normal.y * a, -normal.y);
+}
+
+// Transforms a direction from the local frame where `normal` is the Z axis to world space.
+vec3 to_world(vec3 local_direction, vec3 normal) {
+    vec3 tangent, bitangent;
+    orthonormal_basis(normal, tangent, bitangent);
+    return tangent * local_direction.x + bitangent * local_direction.y + normal * local_direction.z;
+}
+
+// Transforms a world space direction to the local frame where `normal` is the Z axis.
+vec3 to_local(vec3 direction, vec3 normal) {
+    vec3 tangent, bitangent;
+    orthonormal_basis(normal, tangent, bitangent);
+    return vec3(dot(direction, tangent), dot(direction, bitangent), dot(direction, normal));
+}
+
+// Samples a point uniformly from the unit disk, using Shirley and Chiu's concentric mapping.
+vec2 concentric_disk() {
+    vec2 offset = vec2(rand(), rand()) * 2.0 - 1.0;
+    if (offset.x == 0.0 && offset.y == 0.0) {
+        return offset;
+    }
+
+    float radius;
+    float theta;
+    if (abs(offset.x) > abs(offset.y)) {
+        radius = offset.x;
+        theta = PI / 4.0 * (offset.y / offset.x);
+    } else {
+        radius = offset.y;
+        theta = PI / 2.0 - PI / 4.0 * (offset.x / offset.y);
+    }
+    return vec2(cos(theta), sin(theta)) * radius;
+}
+
+// Samples a direction from the hemisphere around `normal` proportionally to the cosine of
+// its angle with the normal.
+vec3 cosine_hemisphere(vec3 normal) {
+    vec2 disk = concentric_disk();
+    float z = sqrt(max(0.0, 1.0 - dot(disk, disk)));
+    return to_world(vec3(disk, z), normal);
+}
+
+// Samples a microfacet normal of the GGX distribution around `normal` that is visible from
+// the direction `view`, which points away from the surface. `alpha` must be greater than zero.
+// (based on "Sampling the GGX Distribution of Visible Normals" by Heitz)
+vec3 ggx_vndf(vec3 view, vec3 normal, float alpha) {
+    vec3 view_local = to_local(view, normal);
+
+    // Transform the view direction to the hemisphere configuration
+    vec3 view_hemisphere = normalize(vec3(alpha * view_local.xy, view_local.z));
+
+    // Orthonormal basis around the view direction
+    float length2 = dot(view_hemisphere.xy, view_hemisphere.xy);
+    vec3 t1 = length2 > 0.0 ? vec3(-view_hemisphere.y, view_hemisphere.x, 0.0) / sqrt(length2) : vec3(1.0, 0.0, 0.0);
+    vec3 t2 = cross(view_hemisphere, t1);
+
+    // Sample the projected area of the visible hemisphere
+    float radius = sqrt(rand());
+    float phi = 2.0 * PI * rand();
+    float p1 = radius * cos(phi);
+    float s = 0.5 * (1.0 + view_hemisphere.z);
+    float p2 = (1.0 - s) * sqrt(max(0.0, 1.0 - p1 * p1)) + s * radius * sin(phi);
+    vec3 normal_hemisphere = t1 * p1 + t2 * p2 + view_hemisphere * sqrt(max(0.0, 1.0 - p1 * p1 - p2 * p2));
+
+    // Transform the normal back to the ellipsoid configuration
+    vec3 microfacet_normal = normalize(vec3(alpha * normal_hemisphere.xy, max(0.0, normal_hemisphere.z)));
+
+    return to_world(microfacet_normal, normal);
+}
+
+// Maps a uniform random number to the tent distribution on [-1, 1] by inverting its CDF.
+float sample_tent(float u) {
+    return u < 0.5 ? sqrt(2.0 * u) - 1.0 : 1.0 - sqrt(2.0 - 2.0 * u);
+}
+
+// Samples the offset of a camera ray from the center of its pixel, in pixels, proportionally
+// to the reconstruction filter.
+vec2 pixel_filter(uint reconstruction_filter) {
+    if (reconstruction_filter == FILTER_TENT) {
+        return vec2(sample_tent(rand()), sample_tent(rand()));
+    } else if (reconstruction_filter == FILTER_GAUSSIAN) {
+        // Box-Muller transform, `rand` may return zero
+        float radius = GAUSSIAN_FILTER_SIGMA * sqrt(-2.0 * log(max(1.0 - rand(), 1e-7)));
+        float phi = 2.0 * PI * rand();
+        return vec2(cos(phi), sin(phi)) * radius;
+    }
+    return vec2(rand(), rand()) - 0.5;
+}
@@ -1,4 +1,3 @@
-use cgmath::Vector2;
 use clap::Parser;
 use color_eyre::eyre::{self, Result};
 use raydar::{
@@ -13,7 +12,6 @@ struct EditorApp {
     scene: Scene,
     renderer: Box<dyn Renderer>,
 
-    original_resolution: Vector2<u32>,
     original_scene_file: Option<PathBuf>,
 
     needs_rerender: bool,
@@ -31,7 +29,6 @@ impl eframe::App for EditorApp {
 
         Inspector::new(
             &mut self.scene,
-            &mut self.original_resolution,
             self.renderer.as_mut(),
             &mut self.needs_rerender,
             &mut self.should_constantly_rerender,
@@ -57,14 +54,10 @@ impl EditorApp {
         renderer: Box<dyn Renderer>,
         original_scene_file: Option<PathBuf>,
     ) -> Self {
-        let original_resolution_x = scene.camera.resolution_x();
-        let original_resolution_y = scene.camera.resolution_y();
-
         Self {
             scene,
             renderer,
 
-            original_resolution: Vector2::new(original_resolution_x, original_resolution_y),
             original_scene_file,
 
             needs_rerender: true,
@@ -74,13 +67,12 @@ impl EditorApp {
     }
 
     fn save_scene(&self) -> Result<(), std::io::Error> {
-        // Clone the scene to restore the original resolution
+        // The camera follows the size of the viewport, save it with the resolution of the
+        // render settings instead
         let mut scene = self.scene.clone();
-        let camera = &mut scene.camera;
-        camera.set_resolution_x(self.original_resolution.x);
-        camera.set_resolution_y(self.original_resolution.y);
+        scene.apply_resolution();
 
-        let json = serde_json::to_string_pretty(&scene)?;
+        let json = scene.to_json()?;
         let file_name = if let Some(original_scene_file) = &self.original_scene_file {
             original_scene_file.to_path_buf()
         } else {
@@ -121,6 +113,7 @@ fn main() -> eyre::Result<()> {
     let native_options = eframe::NativeOptions::default();
     let args = RaydarEditorArgs::parse();
 
+    let _trace_guard = args.common.settings.start_tracing()?;
     let (scene, renderer) = args.common.initialize()?;
 
     eframe::run_native(
@@ -0,0 +1,416 @@
+//! Renders of many scenes from a job manifest, for `raydar batch`.
+//!
+//! Every job loads its scene, applies the values of the manifest and the flags of the command
+//! line and renders it. Jobs are spread over a fixed number of threads, each of which creates
+//! a single renderer and configures it for every job with
+//! [`Renderer::apply_render_settings`], so devices are only set up once per thread.
+
+use std::{
+    fs,
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use color_eyre::eyre::{Context, Report};
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+use super::{load_scene, SettingsArgs};
+use crate::{
+    renderer::{
+        describe_incomplete_render,
+        output::{self, ImageMetadata},
+        Renderer,
+    },
+    scene::{
+        overrides::{self, Override},
+        Scene,
+    },
+};
+
+/// A list of render jobs, read from a TOML file such as
+///
+/// ```toml
+/// [[jobs]]
+/// name = "rough"
+/// scene = "scenes/benchmark.rscn"
+/// output = "renders/rough.png"
+/// overrides = ["objects[2].material.roughness=0.4"]
+///
+/// [jobs.render_settings]
+/// samples = 256
+/// resolution = { width = 1920, height = 1080 }
+/// ```
+///
+/// Scene and output paths are relative to the directory of the manifest.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub jobs: Vec<BatchJob>,
+}
+
+impl Manifest {
+    /// Reads a manifest file.
+    pub fn read(path: &Path) -> Result<Self, Report> {
+        let contents = fs::read_to_string(path).wrap_err("Cannot read job manifest")?;
+        let base_dir = path.parent().unwrap_or(Path::new(""));
+        Self::from_toml(&contents, base_dir)
+            .wrap_err_with(|| format!("Cannot parse job manifest {}", path.display()))
+    }
+
+    /// Parses a manifest, resolving its paths relative to `base_dir`.
+    pub fn from_toml(toml: &str, base_dir: &Path) -> Result<Self, toml::de::Error> {
+        let mut manifest: Self = toml::from_str(toml)?;
+        for job in &mut manifest.jobs {
+            job.scene = base_dir.join(&job.scene);
+            if let Some(output) = &mut job.output {
+                *output = base_dir.join(&*output);
+            }
+        }
+        Ok(manifest)
+    }
+}
+
+/// A scene to render and the values to change before rendering it.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct BatchJob {
+    /// Name of the job in the report, the path of the scene by default
+    pub name: Option<String>,
+    /// Path to the scene file (.rscn)
+    pub scene: PathBuf,
+    /// Path the image is saved to, the output of the scene's render settings by default
+    pub output: Option<PathBuf>,
+    /// Values of the scene to change, such as `objects[2].material.roughness=0.4`
+    #[serde(default, deserialize_with = "deserialize_overrides")]
+    pub overrides: Vec<Override>,
+    /// Render settings of the scene to change, such as `samples` or `seed`
+    #[serde(default)]
+    pub render_settings: toml::Table,
+}
+
+fn deserialize_overrides<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Override>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|s| s.parse().map_err(de::Error::custom))
+        .collect()
+}
+
+impl BatchJob {
+    pub fn name(&self) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| self.scene.display().to_string())
+    }
+
+    /// Loads the scene of the job and applies the values of the job, then the flags of the
+    /// command line.
+    pub fn prepare_scene(&self, settings: &SettingsArgs) -> Result<Scene, Report> {
+        let mut scene = load_scene(&self.scene)
+            .wrap_err_with(|| format!("Cannot load {}", self.scene.display()))?;
+        overrides::apply(&mut scene, &self.overrides).wrap_err("Cannot override scene value")?;
+
+        let render_settings: Vec<_> = self
+            .render_settings
+            .iter()
+            .map(|(key, value)| Override {
+                path: format!("render_settings.{key}"),
+                value: serde_json::to_string(value).expect("TOML values can always be serialized"),
+            })
+            .collect();
+        overrides::apply(&mut scene, &render_settings).wrap_err("Cannot apply render settings")?;
+
+        let mut scene = settings.apply_overrides(scene)?;
+        if let Some(output) = &self.output {
+            scene.render_settings.output = output.clone();
+        }
+        Ok(scene)
+    }
+}
+
+/// The outcome of a job.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct JobReport {
+    pub name: String,
+    pub scene: PathBuf,
+    /// Path the image was saved to, if it was saved
+    pub output: Option<PathBuf>,
+    pub renderer: Option<String>,
+    /// Samples the image was rendered with
+    pub samples: u32,
+    /// Samples of the render settings of the job
+    pub total_samples: u32,
+    /// Time it took to load and check the scene, in milliseconds
+    pub load_ms: f64,
+    /// Time it took the renderer to prepare the scene, in milliseconds
+    pub prepare_ms: Option<f64>,
+    /// Time it took to render and save the image, in milliseconds
+    pub render_ms: f64,
+    pub error: Option<String>,
+}
+
+impl JobReport {
+    fn new(job: &BatchJob) -> Self {
+        Self {
+            name: job.name(),
+            scene: job.scene.clone(),
+            output: None,
+            renderer: None,
+            samples: 0,
+            total_samples: 0,
+            load_ms: 0.0,
+            prepare_ms: None,
+            render_ms: 0.0,
+            error: None,
+        }
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// The outcomes of all jobs of a manifest, in the order of the manifest.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchReport {
+    pub version: String,
+    /// Number of jobs that were rendered at the same time
+    pub parallel: u32,
+    /// Time it took to run all jobs, in milliseconds
+    pub total_ms: f64,
+    pub jobs: Vec<JobReport>,
+}
+
+impl BatchReport {
+    pub fn failures(&self) -> impl Iterator<Item = &JobReport> {
+        self.jobs.iter().filter(|job| !job.succeeded())
+    }
+}
+
+/// Renders the jobs of a manifest, `parallel` at a time.
+///
+/// Failing jobs do not stop the batch, their errors are recorded in their reports.
+/// `on_finished` is called with the report of every job as soon as it is finished.
+pub fn run(
+    manifest: &Manifest,
+    settings: &SettingsArgs,
+    parallel: u32,
+    on_finished: &(dyn Fn(&JobReport) + Sync),
+) -> BatchReport {
+    let _span = tracing::info_span!("batch", jobs = manifest.jobs.len()).entered();
+    let start = Instant::now();
+    let next_job = AtomicUsize::new(0);
+    let reports = Mutex::new(vec![None; manifest.jobs.len()]);
+
+    let threads = (parallel as usize).clamp(1, manifest.jobs.len().max(1));
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                let mut renderer = settings
+                    .create_renderer(&Scene::default())
+                    .map_err(|err| format!("Cannot create renderer: {err:#}"));
+
+                loop {
+                    let index = next_job.fetch_add(1, Ordering::SeqCst);
+                    let Some(job) = manifest.jobs.get(index) else {
+                        break;
+                    };
+
+                    let report = match &mut renderer {
+                        Ok(renderer) => run_job(job, settings, renderer.as_mut()),
+                        Err(err) => JobReport {
+                            error: Some(err.clone()),
+                            ..JobReport::new(job)
+                        },
+                    };
+                    on_finished(&report);
+                    reports.lock().unwrap()[index] = Some(report);
+                }
+            });
+        }
+    });
+
+    BatchReport {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        parallel,
+        total_ms: milliseconds(start.elapsed()),
+        jobs: reports
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|report| report.expect("every job has been run"))
+            .collect(),
+    }
+}
+
+fn run_job(job: &BatchJob, settings: &SettingsArgs, renderer: &mut dyn Renderer) -> JobReport {
+    let _span = tracing::info_span!("job", name = %job.name()).entered();
+    let mut report = JobReport {
+        renderer: Some(renderer.name().to_string()),
+        ..JobReport::new(job)
+    };
+
+    let start = Instant::now();
+    let scene = match job.prepare_scene(settings) {
+        Ok(scene) => scene,
+        Err(err) => {
+            report.error = Some(format!("{err:#}"));
+            return report;
+        }
+    };
+    report.load_ms = milliseconds(start.elapsed());
+    report.total_samples = scene.render_settings.samples;
+
+    let start = Instant::now();
+    renderer.apply_render_settings(&scene.render_settings);
+    let time_limit = scene.render_settings.time_limit;
+    let mut out_of_time = false;
+    let image = renderer.render_frame_with_progress(&scene, &mut |progress| {
+        out_of_time = time_limit.is_some_and(|limit| progress.elapsed.as_secs_f32() >= limit);
+        if out_of_time {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    });
+    report.samples = renderer.sample_count();
+    report.prepare_ms = renderer
+        .profiler()
+        .prepare_timer()
+        .duration()
+        .map(milliseconds);
+
+    let output = &scene.render_settings.output;
+    if report.samples < report.total_samples && !out_of_time {
+        report.error = Some(describe_incomplete_render(renderer));
+    } else if let Err(err) = output::save_image(&image, output, &ImageMetadata::new(report.samples))
+    {
+        report.error = Some(format!("Cannot save image {}: {err}", output.display()));
+    } else {
+        report.output = Some(output.clone());
+    }
+    report.render_ms = milliseconds(start.elapsed());
+
+    report
+}
+
+fn milliseconds(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::Path};
+
+    use clap::Parser;
+
+    use super::{run, Manifest};
+    use crate::{
+        cli::CommonArgs,
+        scene::{overrides::Override, render_settings::Resolution, Scene},
+    };
+
+    #[test]
+    fn manifests_resolve_paths_relative_to_their_directory() {
+        let manifest = Manifest::from_toml(
+            r#"
+            [[jobs]]
+            scene = "a.rscn"
+            output = "out/a.png"
+            overrides = ["render_settings.seed=4"]
+            render_settings = { samples = 8 }
+
+            [[jobs]]
+            name = "absolute"
+            scene = "/scenes/b.rscn"
+            "#,
+            Path::new("batches"),
+        )
+        .unwrap();
+
+        let [first, second] = manifest.jobs.as_slice() else {
+            panic!("expected two jobs, found {:?}", manifest.jobs);
+        };
+        assert_eq!(first.scene, Path::new("batches/a.rscn"));
+        assert_eq!(
+            first.output.as_deref(),
+            Some(Path::new("batches/out/a.png"))
+        );
+        assert_eq!(first.name(), "batches/a.rscn");
+        assert_eq!(
+            first.overrides,
+            ["render_settings.seed=4".parse::<Override>().unwrap()]
+        );
+        assert_eq!(first.render_settings["samples"].as_integer(), Some(8));
+        assert_eq!(second.scene, Path::new("/scenes/b.rscn"));
+        assert_eq!(second.name(), "absolute");
+
+        assert!(
+            Manifest::from_toml("[[jobs]]\nscene = 'a.rscn'\nsamples = 8", Path::new("")).is_err()
+        );
+        assert!(Manifest::from_toml(
+            "[[jobs]]\nscene = 'a.rscn'\noverrides = ['seed']",
+            Path::new("")
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn failing_jobs_do_not_stop_the_batch() {
+        let dir = env::temp_dir().join(format!("raydar-batch-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut scene = Scene::default();
+        scene.render_settings.resolution = Resolution::new(16, 9);
+        fs::write(dir.join("small.rscn"), scene.to_json().unwrap()).unwrap();
+
+        let manifest = Manifest::from_toml(
+            r#"
+            [[jobs]]
+            scene = "small.rscn"
+            output = "small.png"
+            render_settings = { samples = 2, seed = 3 }
+
+            [[jobs]]
+            scene = "missing.rscn"
+
+            [[jobs]]
+            scene = "small.rscn"
+            output = "invalid.png"
+            render_settings = { samples = -1 }
+            "#,
+            &dir,
+        )
+        .unwrap();
+        let settings = CommonArgs::parse_from(["raydar", "--cpu"]).settings;
+        let report = run(&manifest, &settings, 2, &|_| {});
+
+        let [rendered, missing, invalid] = report.jobs.as_slice() else {
+            panic!("expected three reports, found {:?}", report.jobs);
+        };
+        assert!(rendered.succeeded(), "{:?}", rendered.error);
+        assert_eq!((rendered.samples, rendered.total_samples), (2, 2));
+        assert_eq!(
+            rendered.output.as_deref(),
+            Some(dir.join("small.png").as_path())
+        );
+        assert_eq!(image::open(dir.join("small.png")).unwrap().width(), 16);
+
+        assert!(missing.error.as_ref().unwrap().contains("Cannot load"));
+        assert!(invalid
+            .error
+            .as_ref()
+            .unwrap()
+            .contains("Cannot apply render settings"));
+        assert!(!dir.join("invalid.png").exists());
+        assert_eq!(report.failures().count(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
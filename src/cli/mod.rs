@@ -1,71 +1,542 @@
-use clap::Parser;
-use color_eyre::eyre::{Context, Report};
-use std::{fs::File, io::Read, path::PathBuf};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use color_eyre::eyre::{eyre, Context, Report};
+use std::{
+    fs::File,
+    io::{self, Read},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tracing_chrome::{ChromeLayerBuilder, FlushGuard};
+use tracing_subscriber::{
+    filter::LevelFilter,
+    layer::{Layer, SubscriberExt},
+    util::SubscriberInitExt,
+};
+
+pub mod batch;
+pub mod render;
 
 use crate::{
-    renderer::{cpu::CpuRenderer, vulkan::VulkanRenderer, Renderer, RendererConfig},
-    scene::Scene,
+    renderer::{
+        checkpoint::{Checkpoint, CheckpointSettings},
+        cpu::CpuRenderer,
+        vulkan::{DeviceSelector, VulkanRenderer},
+        Renderer, RendererConfig,
+    },
+    scene::{
+        animation,
+        format::blender,
+        overrides::{self, Override},
+        render_settings::{self, Filter, RenderSettings, Resolution, Tonemapping},
+        Scene,
+    },
 };
 
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
 pub struct CommonArgs {
+    #[command(flatten)]
+    pub settings: SettingsArgs,
+
+    /// Path to the scene file (.rscn)
+    pub scene_file: Option<PathBuf>,
+}
+
+impl CommonArgs {
+    /// Initialize scene and renderer from command line arguments
+    pub fn initialize(&self) -> Result<(Scene, Box<dyn Renderer>), Report> {
+        let scene = self.settings.prepare_scene(self.scene_file.as_deref())?;
+        let renderer = self.settings.create_renderer(&scene)?;
+        Ok((scene, renderer))
+    }
+}
+
+/// Flags that configure the renderer and override values of the scene.
+#[derive(Args, Debug)]
+pub struct SettingsArgs {
     /// Use CPU renderer instead of Vulkan
     #[arg(long)]
     pub cpu: bool,
 
+    /// Vulkan device to render on, by index or name (see `raydar --list-devices`)
+    #[arg(long, conflicts_with = "cpu")]
+    pub device: Option<DeviceSelector>,
+
+    /// Resolution of the rendered image, such as 1920x1080
+    #[arg(long)]
+    pub resolution: Option<Resolution>,
+
     /// Maximum number of samples per pixel
     #[arg(long)]
     pub max_sample_count: Option<u32>,
 
+    /// Seed for the random number generators
+    #[arg(long)]
+    pub seed: Option<u32>,
+
+    /// Pixel reconstruction filter
+    #[arg(long, value_enum)]
+    pub filter: Option<Filter>,
+
+    /// Mapping of the rendered radiance to displayable colors
+    #[arg(long, value_enum)]
+    pub tonemapping: Option<Tonemapping>,
+
     /// Maximum number of light bounces
     #[arg(long)]
     pub max_bounces: Option<u32>,
 
-    /// Path to the scene file (.rscn)
-    pub scene_file: Option<PathBuf>,
+    /// Maximum number of diffuse reflections
+    #[arg(long)]
+    pub max_diffuse_bounces: Option<u32>,
+
+    /// Maximum number of specular reflections
+    #[arg(long)]
+    pub max_glossy_bounces: Option<u32>,
+
+    /// Maximum number of refractions through transmissive surfaces
+    #[arg(long)]
+    pub max_transmission_bounces: Option<u32>,
+
+    /// Clamp the brightness of direct light contributions to suppress fireflies
+    #[arg(long)]
+    pub clamp_direct: Option<f32>,
+
+    /// Clamp the brightness of indirect light contributions to suppress fireflies
+    #[arg(long)]
+    pub clamp_indirect: Option<f32>,
+
+    /// Trace every path up to the maximum number of bounces instead of terminating paths
+    /// that carry little light with Russian roulette
+    #[arg(long)]
+    pub no_russian_roulette: bool,
+
+    /// Number of bounces a path makes before Russian roulette may terminate it
+    #[arg(long, conflicts_with = "no_russian_roulette")]
+    pub russian_roulette_min_depth: Option<u32>,
+
+    /// Set a value of the scene, such as `objects[2].material.roughness=0.1` or
+    /// `camera.projection.Perspective.fov=45`. Can be given multiple times
+    #[arg(long = "set", value_name = "PATH=VALUE")]
+    pub overrides: Vec<Override>,
+
+    /// Write a Chrome trace of the scene preparation and rendering to this file, which can
+    /// be opened in Perfetto or chrome://tracing
+    #[arg(long, value_name = "FILE")]
+    pub trace_output: Option<PathBuf>,
 }
 
-impl CommonArgs {
-    /// Initialize scene and renderer from command line arguments
-    pub fn initialize(&self) -> Result<(Scene, Box<dyn Renderer>), Report> {
-        let scene = if let Some(path) = &self.scene_file {
-            let mut file = File::open(path).wrap_err("Cannot open scene file")?;
-            let mut contents = String::new();
-            file.read_to_string(&mut contents)
-                .wrap_err("Cannot read scene file")?;
-            serde_json::from_str(&contents).wrap_err("Cannot parse scene file")?
+impl SettingsArgs {
+    /// Prints warnings and errors to stderr and starts recording spans to the trace file, if
+    /// one was given.
+    ///
+    /// The trace is written when the returned guard is dropped.
+    pub fn start_tracing(&self) -> Result<Option<FlushGuard>, Report> {
+        let (chrome_layer, guard) = match &self.trace_output {
+            Some(path) => {
+                let file = File::create(path)
+                    .wrap_err_with(|| format!("Cannot create trace file {}", path.display()))?;
+                let (layer, guard) = ChromeLayerBuilder::new()
+                    .writer(file)
+                    .include_args(true)
+                    .build();
+                (Some(layer), Some(guard))
+            }
+            None => (None, None),
+        };
+        let stderr_layer = tracing_subscriber::fmt::layer()
+            .with_writer(io::stderr)
+            .with_filter(LevelFilter::WARN);
+        tracing_subscriber::registry()
+            .with(chrome_layer)
+            .with(stderr_layer)
+            .try_init()
+            .wrap_err("Cannot start tracing")?;
+
+        Ok(guard)
+    }
+
+    /// Loads a scene, or the default one if no path is given, and applies the overrides given
+    /// on the command line.
+    pub fn prepare_scene(&self, path: Option<&Path>) -> Result<Scene, Report> {
+        let scene = if let Some(path) = path {
+            load_scene(path)?
         } else {
             Scene::default()
         };
+        self.apply_overrides(scene)
+    }
 
-        let mut config = RendererConfig::default();
-        if let Some(max_sample_count) = self.max_sample_count {
-            config.max_sample_count = max_sample_count;
-        }
-        if let Some(max_bounces) = self.max_bounces {
-            config.max_bounces = max_bounces;
-        }
+    /// Applies the overrides given on the command line to a scene and checks it for errors.
+    pub fn apply_overrides(&self, mut scene: Scene) -> Result<Scene, Report> {
+        overrides::apply(&mut scene, &self.overrides).wrap_err("Cannot override scene value")?;
+        self.override_render_settings(&mut scene.render_settings);
+        scene.apply_resolution();
+        check_scene(&scene)?;
+
+        Ok(scene)
+    }
 
+    /// Creates the selected renderer, configured with the render settings of a scene.
+    pub fn create_renderer(&self, scene: &Scene) -> Result<Box<dyn Renderer>, Report> {
+        let config = RendererConfig::from(&scene.render_settings);
         let renderer: Box<dyn Renderer> = if self.cpu {
             Box::new(CpuRenderer::new(config))
+        } else if let Some(device) = &self.device {
+            // An explicitly selected device should not silently fall back to the CPU
+            Box::new(
+                VulkanRenderer::with_device(config, device)
+                    .wrap_err("Cannot initialize the selected Vulkan device")?,
+            )
         } else {
-            Box::new(VulkanRenderer::new(config))
+            match VulkanRenderer::new(config.clone()) {
+                Ok(renderer) => Box::new(renderer),
+                Err(err) => {
+                    eprintln!("Warning: Vulkan renderer unavailable ({err}), falling back to CPU");
+                    Box::new(CpuRenderer::new(config))
+                }
+            }
         };
 
-        Ok((scene, renderer))
+        Ok(renderer)
+    }
+
+    /// Applies the render settings given on the command line on top of the scene's own.
+    fn override_render_settings(&self, settings: &mut RenderSettings) {
+        if let Some(resolution) = self.resolution {
+            settings.resolution = resolution;
+        }
+        if let Some(max_sample_count) = self.max_sample_count {
+            settings.samples = max_sample_count;
+        }
+        if let Some(seed) = self.seed {
+            settings.seed = seed;
+        }
+        if let Some(filter) = self.filter {
+            settings.filter = filter;
+        }
+        if let Some(tonemapping) = self.tonemapping {
+            settings.tonemapping = tonemapping;
+        }
+
+        let bounces = &mut settings.bounces;
+        if let Some(max_bounces) = self.max_bounces {
+            bounces.total = max_bounces;
+        }
+        if let Some(max_diffuse_bounces) = self.max_diffuse_bounces {
+            bounces.diffuse = max_diffuse_bounces;
+        }
+        if let Some(max_glossy_bounces) = self.max_glossy_bounces {
+            bounces.glossy = max_glossy_bounces;
+        }
+        if let Some(max_transmission_bounces) = self.max_transmission_bounces {
+            bounces.transmission = max_transmission_bounces;
+        }
+
+        if let Some(clamp_direct) = self.clamp_direct {
+            settings.clamping.direct = Some(clamp_direct);
+        }
+        if let Some(clamp_indirect) = self.clamp_indirect {
+            settings.clamping.indirect = Some(clamp_indirect);
+        }
+
+        if self.no_russian_roulette {
+            settings.russian_roulette = false;
+        }
+        if let Some(min_depth) = self.russian_roulette_min_depth {
+            settings.russian_roulette_min_depth = min_depth;
+        }
+    }
+}
+
+/// Reads a scene file of any supported version.
+pub fn load_scene(path: &Path) -> Result<Scene, Report> {
+    let mut file = File::open(path).wrap_err("Cannot open scene file")?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)
+        .wrap_err("Cannot read scene file")?;
+    Scene::from_json(&contents)
+        .wrap_err_with(|| format!("Cannot parse scene file {}", path.display()))
+}
+
+/// Validates a scene before rendering it, printing its warnings and failing on errors.
+pub fn check_scene(scene: &Scene) -> Result<(), Report> {
+    let (errors, warnings): (Vec<_>, Vec<_>) = scene
+        .validate()
+        .into_iter()
+        .partition(|diagnostic| diagnostic.is_error());
+    for warning in warnings {
+        eprintln!("{warning}");
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        let errors: Vec<_> = errors.iter().map(ToString::to_string).collect();
+        Err(eyre!("Invalid scene:\n{}", errors.join("\n")))
     }
 }
 
 #[derive(Parser, Debug)]
-#[command(author, version, about)]
+#[command(author, version, about, args_conflicts_with_subcommands = true)]
 pub struct RaydarArgs {
+    #[command(subcommand)]
+    pub command: Option<RaydarCommand>,
+
+    /// Arguments of `raydar scene.rscn`, a shorthand for `raydar render scene.rscn`
+    #[command(flatten)]
+    pub render: RenderArgs,
+
+    /// List the available Vulkan devices and exit
+    #[arg(long)]
+    pub list_devices: bool,
+}
+
+impl RaydarArgs {
+    /// Returns the command to run, rendering if none was given.
+    pub fn into_command(self) -> RaydarCommand {
+        self.command.unwrap_or(RaydarCommand::Render(self.render))
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum RaydarCommand {
+    /// Render a scene to an image file
+    Render(RenderArgs),
+    /// Print statistics of a scene, such as its objects, bounds and light sources
+    Info {
+        /// Path to the scene file (.rscn)
+        scene_file: PathBuf,
+    },
+    /// Check a scene file for invalid values without rendering it
+    Validate {
+        /// Path to the scene file (.rscn)
+        scene_file: PathBuf,
+    },
+    /// Convert a scene file to another format, or upgrade it to the current version
+    Convert {
+        /// Path to the scene file (.rscn)
+        input: PathBuf,
+        /// Path to the converted file
+        output: PathBuf,
+        /// Format of the converted file, guessed from its extension by default
+        #[arg(long, value_enum)]
+        format: Option<SceneFormat>,
+    },
+    /// Render scenes repeatedly and report statistics of their timings
+    Bench(BenchArgs),
+    /// Combine checkpoints of renders with different seeds into one image with more samples
+    Merge(MergeArgs),
+    /// Render the jobs of a manifest and report their timings and failures
+    Batch(BatchArgs),
+    /// Serve an HTTP API that renders submitted scenes one after another
+    Server(ServerArgs),
+    /// Render tiles for coordinators started with `raydar render --worker`
+    ServeWorker {
+        /// Address to listen on, use `0.0.0.0:7878` to accept coordinators on other machines
+        #[arg(long, default_value = "127.0.0.1:7878")]
+        listen: String,
+    },
+}
+
+#[derive(Args, Debug)]
+pub struct RenderArgs {
     #[command(flatten)]
     pub common: CommonArgs,
 
-    /// Output image path
-    #[arg(short, long, default_value = "output.png")]
-    pub output: PathBuf,
+    /// Output image path, overrides the one in the scene's render settings
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Stop starting new samples after this much wall time, such as `90s`, `5m` or `1h`
+    #[arg(long, value_name = "DURATION", value_parser = render_settings::parse_seconds)]
+    pub time_limit: Option<f32>,
+
+    /// Render these frames of the scene's animation, such as `1-120`, to the output path with
+    /// its `#`s replaced by the frame number, such as `out_####.png`
+    #[arg(
+        long,
+        value_name = "FRAMES",
+        value_parser = animation::parse_frames,
+        conflicts_with_all = ["checkpoint", "resume", "workers"]
+    )]
+    pub frames: Option<RangeInclusive<u32>>,
+
+    /// Periodically save the render to this checkpoint file, from which it can be resumed
+    /// with `--resume`
+    #[arg(long, value_name = "FILE", requires = "cpu")]
+    pub checkpoint: Option<PathBuf>,
+
+    /// Time between two checkpoints, such as `30s` or `10m`
+    #[arg(
+        long,
+        value_name = "DURATION",
+        default_value = "5m",
+        value_parser = render_settings::parse_duration
+    )]
+    pub checkpoint_interval: Duration,
+
+    /// Continue the render saved in this checkpoint file, which keeps being updated unless
+    /// `--checkpoint` is given
+    #[arg(long, value_name = "FILE", requires = "cpu")]
+    pub resume: Option<PathBuf>,
+
+    /// Render on the `raydar serve-worker` process at this address instead of locally. Can be
+    /// given multiple times
+    #[arg(
+        long = "worker",
+        value_name = "HOST:PORT",
+        conflicts_with_all = ["checkpoint", "resume"]
+    )]
+    pub workers: Vec<String>,
+
+    /// Time to wait for a worker to connect or to answer before its tiles are given to the
+    /// other workers. Workers report every second while they render a tile
+    #[arg(
+        long,
+        value_name = "DURATION",
+        default_value = "10m",
+        value_parser = render_settings::parse_duration
+    )]
+    pub worker_timeout: Duration,
+}
+
+impl RenderArgs {
+    /// Creates the selected renderer, resuming and saving checkpoints if requested.
+    pub fn create_renderer(&self, scene: &Scene) -> Result<Box<dyn Renderer>, Report> {
+        let Some(path) = self.checkpoint.as_ref().or(self.resume.as_ref()) else {
+            return self.common.settings.create_renderer(scene);
+        };
+
+        // Only the CPU renderer keeps its accumulation buffer in host memory between samples,
+        // which is why the checkpoint flags require `--cpu`
+        let mut renderer = CpuRenderer::new(RendererConfig::from(&scene.render_settings));
+        if let Some(resume) = &self.resume {
+            let checkpoint = Checkpoint::read(resume)
+                .wrap_err_with(|| format!("Cannot read checkpoint {}", resume.display()))?;
+            if checkpoint.sample_count >= renderer.config().max_sample_count {
+                return Err(eyre!(
+                    "Checkpoint {} already has {} samples, raise --max-sample-count to continue it",
+                    resume.display(),
+                    checkpoint.sample_count
+                ));
+            }
+            renderer
+                .resume_from(checkpoint, scene)
+                .wrap_err_with(|| format!("Cannot resume from {}", resume.display()))?;
+        }
+        renderer.set_checkpointing(Some(CheckpointSettings {
+            path: path.clone(),
+            interval: self.checkpoint_interval,
+        }));
+
+        Ok(Box::new(renderer))
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct BenchArgs {
+    #[command(flatten)]
+    pub settings: SettingsArgs,
+
+    /// Number of renders of each scene before the measured ones
+    #[arg(long, default_value_t = 1)]
+    pub warmup: u32,
+
+    /// Number of measured renders of each scene
+    #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u32).range(1..))]
+    pub runs: u32,
+
+    /// Write the results as JSON to this file
+    #[arg(long, value_name = "FILE")]
+    pub json: Option<PathBuf>,
+
+    /// Compare the results against a report saved with `--json`, failing on regressions
+    #[arg(long, value_name = "FILE")]
+    pub baseline: Option<PathBuf>,
+
+    /// Percentage by which a metric may get worse than in the baseline
+    #[arg(long, default_value_t = 5.0, requires = "baseline")]
+    pub threshold: f64,
+
+    /// Paths to the scene files (.rscn) to benchmark
+    #[arg(default_value = "scenes/benchmark.rscn")]
+    pub scene_files: Vec<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct MergeArgs {
+    #[command(flatten)]
+    pub settings: SettingsArgs,
+
+    /// Output image path, overrides the one in the scene's render settings
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Path to the scene file (.rscn) the checkpoints were rendered from
+    pub scene_file: PathBuf,
+
+    /// Checkpoint files written with `--checkpoint`, each rendered with a different seed
+    #[arg(required = true)]
+    pub checkpoints: Vec<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct BatchArgs {
+    /// Flags that apply to every job, after the values of the manifest
+    #[command(flatten)]
+    pub settings: SettingsArgs,
+
+    /// Number of jobs rendered at the same time, each thread creates one renderer
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub parallel: u32,
+
+    /// Write the report of the jobs as JSON to this file
+    #[arg(long, value_name = "FILE")]
+    pub json: Option<PathBuf>,
+
+    /// Path to the job manifest (.toml), see the README for its format
+    pub manifest: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct ServerArgs {
+    /// Flags that apply to every submitted scene
+    #[command(flatten)]
+    pub settings: SettingsArgs,
+
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    pub listen: String,
+}
+
+/// A format scenes can be converted to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SceneFormat {
+    /// Raydar scene file of the current version
+    Rscn,
+    /// Python script that recreates the scene in Blender
+    Blender,
+}
+
+impl SceneFormat {
+    /// Guesses the format of a file from its extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "rscn" | "json" => Some(Self::Rscn),
+            "py" => Some(Self::Blender),
+            _ => None,
+        }
+    }
+
+    /// Writes a scene in this format.
+    pub fn write(self, scene: &Scene) -> Result<String, Report> {
+        match self {
+            Self::Rscn => scene.to_json().wrap_err("Cannot serialize scene"),
+            Self::Blender => Ok(blender::to_script(scene)),
+        }
+    }
 }
 
 #[derive(Parser, Debug)]
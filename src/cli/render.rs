@@ -0,0 +1,201 @@
+//! Rendering a single frame from the command line, until it is done, runs out of time or is
+//! interrupted.
+
+use std::{
+    ops::ControlFlow,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use color_eyre::eyre::{eyre, Report};
+use image::RgbaImage;
+
+use crate::{
+    renderer::{describe_incomplete_render, progress::Progress, Renderer},
+    scene::Scene,
+};
+
+/// Why a render stopped before all of its samples were rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// `interrupted` was set, usually by Ctrl+C
+    Interrupted,
+    /// The time limit of the scene ran out
+    OutOfTime,
+}
+
+/// Renders a frame of a scene, calling `on_progress` after every sample, until all samples are
+/// rendered, the time limit in the scene's render settings runs out or `interrupted` is set.
+///
+/// Returns the image accumulated so far along with the reason the render stopped early, or an
+/// error if the renderer stopped on its own before all samples were rendered.
+pub fn render_until_stopped(
+    renderer: &mut dyn Renderer,
+    scene: &Scene,
+    interrupted: &AtomicBool,
+    on_progress: &mut dyn FnMut(Progress),
+) -> Result<(RgbaImage, Option<StopReason>), Report> {
+    let time_limit = scene.render_settings.time_limit;
+    let mut stop_reason = None;
+    let image = renderer.render_frame_with_progress(scene, &mut |progress| {
+        on_progress(progress);
+        if interrupted.load(Ordering::SeqCst) {
+            stop_reason = Some(StopReason::Interrupted);
+        } else if time_limit.is_some_and(|limit| progress.elapsed.as_secs_f32() >= limit) {
+            stop_reason = Some(StopReason::OutOfTime);
+        }
+        match stop_reason {
+            Some(_) => ControlFlow::Break(()),
+            None => ControlFlow::Continue(()),
+        }
+    });
+
+    if stop_reason.is_none() && renderer.sample_count() < renderer.config().max_sample_count {
+        return Err(eyre!(describe_incomplete_render(renderer)));
+    }
+    Ok((image, stop_reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use image::{Rgba32FImage, RgbaImage};
+
+    use super::{render_until_stopped, StopReason};
+    use crate::{
+        renderer::{timing::Profiler, Renderer, RendererConfig},
+        scene::Scene,
+    };
+
+    /// Renders blank samples one by one, and fails after `failing_sample` samples.
+    struct StepRenderer {
+        config: RendererConfig,
+        profiler: Profiler,
+        sample_count: u32,
+        failing_sample: Option<u32>,
+    }
+
+    impl StepRenderer {
+        fn new(max_sample_count: u32) -> Self {
+            Self {
+                config: RendererConfig {
+                    max_sample_count,
+                    ..Default::default()
+                },
+                profiler: Profiler::default(),
+                sample_count: 0,
+                failing_sample: None,
+            }
+        }
+    }
+
+    impl Renderer for StepRenderer {
+        fn render_frame(&mut self, scene: &Scene) -> RgbaImage {
+            self.render_frame_with_progress(scene, &mut |_| std::ops::ControlFlow::Continue(()))
+        }
+
+        fn render_sample(&mut self, _scene: &Scene) -> Option<RgbaImage> {
+            if self.sample_count >= self.config.max_sample_count
+                || Some(self.sample_count) == self.failing_sample
+            {
+                return None;
+            }
+            self.sample_count += 1;
+            Some(RgbaImage::new(1, 1))
+        }
+
+        fn new_frame(&mut self, _scene: &Scene) {
+            self.sample_count = 0;
+        }
+
+        fn frame_buffer(&self) -> Option<&Rgba32FImage> {
+            None
+        }
+
+        fn profiler(&self) -> &Profiler {
+            &self.profiler
+        }
+
+        fn sample_count(&self) -> u32 {
+            self.sample_count
+        }
+
+        fn config(&self) -> &RendererConfig {
+            &self.config
+        }
+
+        fn config_mut(&mut self) -> &mut RendererConfig {
+            &mut self.config
+        }
+
+        fn name(&self) -> &'static str {
+            "Step"
+        }
+    }
+
+    #[test]
+    fn renders_all_samples_without_interrupt() {
+        let mut renderer = StepRenderer::new(8);
+        let mut reported = Vec::new();
+        let (_, stop_reason) = render_until_stopped(
+            &mut renderer,
+            &Scene::default(),
+            &AtomicBool::new(false),
+            &mut |progress| reported.push(progress.samples),
+        )
+        .unwrap();
+
+        assert_eq!(stop_reason, None);
+        assert_eq!(reported, (1..=8).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn interrupt_stops_after_the_current_sample() {
+        let mut renderer = StepRenderer::new(8);
+        let interrupted = AtomicBool::new(false);
+        let (_, stop_reason) = render_until_stopped(
+            &mut renderer,
+            &Scene::default(),
+            &interrupted,
+            &mut |progress| {
+                if progress.samples == 3 {
+                    interrupted.store(true, Ordering::SeqCst);
+                }
+            },
+        )
+        .unwrap();
+
+        assert_eq!(stop_reason, Some(StopReason::Interrupted));
+        assert_eq!(renderer.sample_count(), 3);
+    }
+
+    #[test]
+    fn time_limit_stops_after_the_first_sample() {
+        let mut scene = Scene::default();
+        scene.render_settings.time_limit = Some(0.0);
+        let mut renderer = StepRenderer::new(8);
+        let (_, stop_reason) =
+            render_until_stopped(&mut renderer, &scene, &AtomicBool::new(false), &mut |_| {})
+                .unwrap();
+
+        assert_eq!(stop_reason, Some(StopReason::OutOfTime));
+        assert_eq!(renderer.sample_count(), 1);
+    }
+
+    #[test]
+    fn renders_that_stop_on_their_own_fail() {
+        let mut renderer = StepRenderer {
+            failing_sample: Some(5),
+            ..StepRenderer::new(8)
+        };
+        let result = render_until_stopped(
+            &mut renderer,
+            &Scene::default(),
+            &AtomicBool::new(false),
+            &mut |_| {},
+        );
+
+        assert!(result.is_err());
+        assert_eq!(renderer.sample_count(), 5);
+    }
+}
@@ -1,6 +1,7 @@
 pub mod cli;
 pub mod renderer;
 pub mod scene;
+pub mod server;
 pub mod widgets;
 
 pub(crate) mod utils;
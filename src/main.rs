@@ -1,53 +1,595 @@
+use std::{
+    fs,
+    io::{self, IsTerminal},
+    net::TcpListener,
+    ops::{ControlFlow, RangeInclusive},
+    path::Path,
+    process::{self, ExitCode},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Instant,
+};
+
 use clap::{crate_version, Parser};
 use color_eyre::eyre::{self, Context, OptionExt};
 use owo_colors::OwoColorize;
 use raydar::{
-    cli::RaydarArgs,
-    renderer::{timing::Profiler, Renderer},
-    scene::Scene,
+    cli::{
+        self,
+        batch::{self, BatchReport, JobReport, Manifest},
+        render::{render_until_stopped, StopReason},
+        BatchArgs, BenchArgs, MergeArgs, RaydarArgs, RaydarCommand, RenderArgs, SceneFormat,
+        ServerArgs,
+    },
+    renderer::{
+        benchmark::{self, BenchmarkReport, Statistics},
+        checkpoint::{self, Checkpoint},
+        distributed::{self, Coordinator, DistributedError},
+        output::{self, ImageMetadata},
+        timing::{PathStatistics, Profiler},
+        vulkan, RendererConfig,
+    },
+    scene::{animation, objects::Geometry, validation::Severity, world::World, Scene},
+    server::{self, JobQueue},
 };
 
-fn main() -> eyre::Result<()> {
+fn main() -> eyre::Result<ExitCode> {
     color_eyre::install()?;
 
     let args = RaydarArgs::parse();
-    let (scene, mut renderer) = args.common.initialize()?;
+    if args.list_devices {
+        return print_devices();
+    }
+
+    match args.into_command() {
+        RaydarCommand::Render(args) => render(args),
+        RaydarCommand::Info { scene_file } => info(&scene_file),
+        RaydarCommand::Validate { scene_file } => validate(&scene_file),
+        RaydarCommand::Convert {
+            input,
+            output,
+            format,
+        } => convert(&input, &output, format),
+        RaydarCommand::Bench(args) => bench(args),
+        RaydarCommand::Merge(args) => merge(args),
+        RaydarCommand::ServeWorker { listen } => serve_worker(&listen),
+        RaydarCommand::Batch(args) => batch(args),
+        RaydarCommand::Server(args) => server(args),
+    }
+}
+
+fn render(args: RenderArgs) -> eyre::Result<ExitCode> {
+    // The trace is written when the guard is dropped at the end of the command
+    let _trace_guard = args.common.settings.start_tracing()?;
+    let mut scene = args
+        .common
+        .settings
+        .prepare_scene(args.common.scene_file.as_deref())?;
+    if let Some(output) = &args.output {
+        scene.render_settings.output = output.clone();
+    }
+    if let Some(time_limit) = args.time_limit {
+        scene.render_settings.time_limit = Some(time_limit);
+    }
+    if let Some(frames) = args.frames.clone() {
+        return render_frames(&args, &scene, frames);
+    }
+    if !args.workers.is_empty() {
+        return render_distributed(&args, &scene);
+    }
+    let mut renderer = args.create_renderer(&scene)?;
+
+    print_info(
+        &scene,
+        renderer.name(),
+        renderer.config().max_sample_count,
+        renderer.config().bounces.total,
+    );
+    let interrupted = install_interrupt_handler()?;
+
+    let show_progress = io::stderr().is_terminal();
+    let result = render_until_stopped(renderer.as_mut(), &scene, &interrupted, &mut |progress| {
+        if show_progress {
+            eprint!("\r\x1b[2K{} {progress}", "Rendering:".bold());
+        }
+    });
+    if show_progress {
+        eprintln!();
+    }
+    let (image, stop_reason) = result?;
 
-    print_info(&args, &scene, renderer.as_ref());
+    let metadata = ImageMetadata::new(renderer.sample_count());
+    output::save_image(&image, &scene.render_settings.output, &metadata)
+        .wrap_err("Cannot save image")?;
 
-    let image = renderer.render_frame(&scene);
-    image.save(&args.output).wrap_err("Cannot save image")?;
+    if stop_reason == Some(StopReason::Interrupted) {
+        println!(
+            "{} saved {} after {} of {} samples",
+            "Interrupted:".yellow().bold(),
+            scene.render_settings.output.display(),
+            renderer.sample_count(),
+            renderer.config().max_sample_count
+        );
+        return Ok(ExitCode::from(130));
+    }
+
+    if stop_reason == Some(StopReason::OutOfTime) {
+        println!(
+            "{} stopped after {} of {} samples",
+            "Time Limit Reached:".yellow().bold(),
+            renderer.sample_count(),
+            renderer.config().max_sample_count
+        );
+    }
 
     print_profiling_metrics(renderer.profiler())?;
 
-    Ok(())
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Installs a Ctrl+C handler: the first interrupt sets the returned flag so that the render
+/// stops after the current sample, the second one exits.
+fn install_interrupt_handler() -> eyre::Result<Arc<AtomicBool>> {
+    let interrupted = Arc::new(AtomicBool::new(false));
+    {
+        let interrupted = interrupted.clone();
+        ctrlc::set_handler(move || {
+            if interrupted.swap(true, Ordering::SeqCst) {
+                process::exit(130);
+            }
+        })
+        .wrap_err("Cannot install interrupt handler")?;
+    }
+    Ok(interrupted)
 }
 
-fn print_info(args: &RaydarArgs, scene: &Scene, renderer: &dyn Renderer) {
+/// Renders frames of the animation of a scene one after another with the same renderer.
+fn render_frames(
+    args: &RenderArgs,
+    scene: &Scene,
+    frames: RangeInclusive<u32>,
+) -> eyre::Result<ExitCode> {
+    let pattern = &scene.render_settings.output;
+    if animation::frame_path(pattern, *frames.start()).is_none() {
+        eyre::bail!(
+            "The output path {} has no `#` to replace with the frame number, such as out_####.png",
+            pattern.display()
+        );
+    }
+    if scene.animation.is_empty() {
+        eprintln!("Warning: the scene has no animation, all frames are the same");
+    }
+    // Animated values are only known per frame, so check them all before rendering any
+    for frame in frames.clone() {
+        let errors: Vec<_> = scene
+            .at_frame(frame)
+            .validate()
+            .into_iter()
+            .filter(|diagnostic| diagnostic.is_error())
+            .map(|diagnostic| diagnostic.to_string())
+            .collect();
+        if !errors.is_empty() {
+            eyre::bail!("Invalid scene at frame {frame}:\n{}", errors.join("\n"));
+        }
+    }
+
+    let mut renderer = args.create_renderer(scene)?;
+    print_info(
+        scene,
+        renderer.name(),
+        renderer.config().max_sample_count,
+        renderer.config().bounces.total,
+    );
+    let interrupted = install_interrupt_handler()?;
+
+    let show_progress = io::stderr().is_terminal();
+    let frame_count = frames.clone().count();
+    let start = Instant::now();
+    for (index, frame) in frames.enumerate() {
+        let _span = tracing::info_span!("frame", frame).entered();
+        let frame_scene = scene.at_frame(frame);
+        let path = animation::frame_path(pattern, frame).expect("the pattern has been checked");
+
+        let result = render_until_stopped(
+            renderer.as_mut(),
+            &frame_scene,
+            &interrupted,
+            &mut |progress| {
+                if show_progress {
+                    eprint!(
+                        "\r\x1b[2K{} frame {frame} ({}/{frame_count}) {progress}",
+                        "Rendering:".bold(),
+                        index + 1
+                    );
+                }
+            },
+        );
+        if show_progress {
+            eprintln!();
+        }
+        let (image, stop_reason) =
+            result.wrap_err_with(|| format!("Cannot render frame {frame}"))?;
+
+        let metadata = ImageMetadata::new(renderer.sample_count());
+        output::save_image(&image, &path, &metadata)
+            .wrap_err_with(|| format!("Cannot save frame {frame}"))?;
+
+        if stop_reason == Some(StopReason::Interrupted) {
+            println!(
+                "{} saved {} after {} of {} samples",
+                "Interrupted:".yellow().bold(),
+                path.display(),
+                renderer.sample_count(),
+                renderer.config().max_sample_count
+            );
+            return Ok(ExitCode::from(130));
+        }
+        println!(
+            "Saved frame {frame} to {} ({} samples)",
+            path.display(),
+            renderer.sample_count()
+        );
+    }
+
     println!(
-        "{}",
-        format!("{}", format!("=== Raydar v{} ===", crate_version!()).bold())
+        "Rendered {frame_count} frames in {:.1}s",
+        start.elapsed().as_secs_f64()
     );
+    Ok(ExitCode::SUCCESS)
+}
+
+fn render_distributed(args: &RenderArgs, scene: &Scene) -> eyre::Result<ExitCode> {
+    let config = RendererConfig::from(&scene.render_settings);
+    let coordinator = Coordinator::new(args.workers.clone(), args.worker_timeout);
+
+    print_info(
+        scene,
+        &format!("Distributed ({} workers)", args.workers.len()),
+        config.max_sample_count,
+        config.bounces.total,
+    );
+    if scene.render_settings.time_limit.is_some() {
+        eprintln!("Warning: the time limit does not apply to distributed renders");
+    }
+    let interrupted = install_interrupt_handler()?;
+
+    let show_progress = io::stderr().is_terminal();
+    let start = Instant::now();
+    let frame_buffer = coordinator.render(scene, &config, &mut |progress| {
+        if show_progress {
+            eprint!("\r\x1b[2K{} {progress}", "Rendering:".bold());
+        }
+        if interrupted.load(Ordering::SeqCst) {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    });
+    if show_progress {
+        eprintln!();
+    }
+
+    let frame_buffer = match frame_buffer {
+        Err(DistributedError::Cancelled) => {
+            println!(
+                "{} distributed renders are only saved once every tile is done",
+                "Interrupted:".yellow().bold()
+            );
+            return Ok(ExitCode::from(130));
+        }
+        result => result.wrap_err("Distributed render failed")?,
+    };
+
+    let image =
+        output::display_transform(&frame_buffer, config.max_sample_count, config.tonemapping);
+    let metadata = ImageMetadata::new(config.max_sample_count);
+    output::save_image(&image, &scene.render_settings.output, &metadata)
+        .wrap_err("Cannot save image")?;
 
     println!(
-        "{} {}",
-        "Renderer:".red().bold(),
-        // TODO: Add renderer name to renderer
-        if args.common.cpu { "CPU" } else { "Vulkan" }
+        "Saved {} after {:.1}s",
+        scene.render_settings.output.display(),
+        start.elapsed().as_secs_f64()
     );
+    Ok(ExitCode::SUCCESS)
+}
 
+fn serve_worker(address: &str) -> eyre::Result<ExitCode> {
+    let listener =
+        TcpListener::bind(address).wrap_err_with(|| format!("Cannot listen on {address}"))?;
+    println!("{} listening on {address}", "Worker:".bold());
+    distributed::serve_worker(&listener).wrap_err("Cannot accept coordinator")?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn server(args: ServerArgs) -> eyre::Result<ExitCode> {
+    let mut renderer = args.settings.create_renderer(&Scene::default())?;
+    let http = tiny_http::Server::http(&args.listen)
+        .map_err(|err| eyre::eyre!("Cannot listen on {}: {err}", args.listen))?;
     println!(
-        "{} {}",
-        "Max Samples:".yellow().bold(),
-        renderer.max_sample_count()
+        "{} rendering with {} on http://{}",
+        "Server:".bold(),
+        renderer.name(),
+        args.listen
     );
 
+    let queue = Arc::new(JobQueue::default());
+    let settings = args.settings;
+    thread::spawn({
+        let queue = Arc::clone(&queue);
+        move || server::serve(&http, &queue, &settings)
+    });
+
+    loop {
+        queue.render_next(renderer.as_mut());
+    }
+}
+
+fn merge(args: MergeArgs) -> eyre::Result<ExitCode> {
+    let mut scene = args.settings.prepare_scene(Some(&args.scene_file))?;
+    if let Some(output) = args.output {
+        scene.render_settings.output = output;
+    }
+
+    let mut checkpoints = Vec::new();
+    for path in &args.checkpoints {
+        let checkpoint = Checkpoint::read(path)
+            .wrap_err_with(|| format!("Cannot read checkpoint {}", path.display()))?;
+        checkpoint
+            .check_scene(&scene)
+            .wrap_err_with(|| format!("Cannot merge {}", path.display()))?;
+        checkpoints.push(checkpoint);
+    }
+
+    let (frame_buffer, sample_count) =
+        checkpoint::merge(&checkpoints).wrap_err("Cannot merge checkpoints")?;
+    let image = output::display_transform(
+        &frame_buffer,
+        sample_count,
+        scene.render_settings.tonemapping,
+    );
+    let metadata = ImageMetadata::new(sample_count);
+    output::save_image(&image, &scene.render_settings.output, &metadata)
+        .wrap_err("Cannot save image")?;
+
     println!(
-        "{} {}",
-        "Max Bounces:".green().bold(),
-        renderer.max_bounces()
+        "Merged {} checkpoints with {sample_count} samples into {}",
+        checkpoints.len(),
+        scene.render_settings.output.display()
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+fn bench(args: BenchArgs) -> eyre::Result<ExitCode> {
+    let _trace_guard = args.settings.start_tracing()?;
+    let mut scenes = Vec::new();
+    for scene_file in &args.scene_files {
+        let scene = args.settings.prepare_scene(Some(scene_file))?;
+        let mut renderer = args.settings.create_renderer(&scene)?;
+
+        let name = scene_file.display().to_string();
+        println!(
+            "{} {name} ({}, {}x{}, {} samples)",
+            "Benchmarking".bold(),
+            renderer.name(),
+            scene.camera.resolution_x(),
+            scene.camera.resolution_y(),
+            renderer.config().max_sample_count
+        );
+        scenes.push(benchmark::benchmark_scene(
+            renderer.as_mut(),
+            &scene,
+            name,
+            args.warmup,
+            args.runs,
+        ));
+    }
+
+    let report = BenchmarkReport {
+        version: crate_version!().to_string(),
+        warmup_runs: args.warmup,
+        runs: args.runs,
+        scenes,
+    };
+    print_benchmark_report(&report);
+
+    if let Some(path) = &args.json {
+        let json = serde_json::to_string_pretty(&report).wrap_err("Cannot serialize report")?;
+        fs::write(path, json)
+            .wrap_err_with(|| format!("Cannot write report to {}", path.display()))?;
+    }
+
+    if let Some(path) = &args.baseline {
+        let baseline = fs::read_to_string(path)
+            .wrap_err_with(|| format!("Cannot read baseline {}", path.display()))?;
+        let baseline: BenchmarkReport = serde_json::from_str(&baseline)
+            .wrap_err_with(|| format!("Cannot parse baseline {}", path.display()))?;
+
+        for scene in &report.scenes {
+            if !baseline
+                .scenes
+                .iter()
+                .any(|baseline_scene| baseline_scene.is_comparable_to(scene))
+            {
+                println!(
+                    "{} {} is not in the baseline with the same renderer, resolution and samples",
+                    "warning:".yellow().bold(),
+                    scene.scene
+                );
+            }
+        }
+
+        let regressions = report.regressions(&baseline, args.threshold / 100.0);
+        for regression in &regressions {
+            println!("{} {regression}", "regression:".red().bold());
+        }
+        if regressions.is_empty() {
+            println!("No regressions compared to {}", path.display());
+        } else {
+            return Ok(ExitCode::FAILURE);
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn print_benchmark_report(report: &BenchmarkReport) {
+    println!(
+        "\n{}",
+        format!(
+            "=== Benchmark ({} runs, {} warm-up) ===",
+            report.runs, report.warmup_runs
+        )
+        .bold()
     );
 
+    let milliseconds = |statistics: &Statistics| {
+        format!(
+            "{:.2}ms (median {:.2}ms, σ {:.2}ms)",
+            statistics.mean, statistics.median, statistics.stddev
+        )
+    };
+    for scene in &report.scenes {
+        println!("{}", scene.scene.bold());
+        println!(
+            "    {} {}",
+            "Scene Preparation:".red().bold(),
+            milliseconds(&scene.prepare_ms)
+        );
+        println!(
+            "    {} {}",
+            "Sample Time:".yellow().bold(),
+            milliseconds(&scene.sample_ms)
+        );
+        println!(
+            "    {} {}",
+            "Frame Time:".green().bold(),
+            milliseconds(&scene.frame_ms)
+        );
+        println!(
+            "    {} {:.2}M (median {:.2}M, σ {:.2}M)",
+            "Rays per Second:".cyan().bold(),
+            scene.rays_per_second.mean / 1e6,
+            scene.rays_per_second.median / 1e6,
+            scene.rays_per_second.stddev / 1e6
+        );
+    }
+}
+
+fn batch(args: BatchArgs) -> eyre::Result<ExitCode> {
+    let _trace_guard = args.settings.start_tracing()?;
+    let manifest = Manifest::read(&args.manifest)?;
+    println!(
+        "{} {} jobs, {} at a time",
+        "Batch:".bold(),
+        manifest.jobs.len(),
+        args.parallel
+    );
+
+    let report = batch::run(
+        &manifest,
+        &args.settings,
+        args.parallel,
+        &|job: &JobReport| match &job.error {
+            None => println!("{} {}", "Finished".green().bold(), job.name),
+            Some(err) => println!("{} {}: {err}", "Failed".red().bold(), job.name),
+        },
+    );
+    print_batch_report(&report);
+
+    if let Some(path) = &args.json {
+        let json = serde_json::to_string_pretty(&report).wrap_err("Cannot serialize report")?;
+        fs::write(path, json)
+            .wrap_err_with(|| format!("Cannot write report to {}", path.display()))?;
+    }
+
+    if report.failures().next().is_some() {
+        return Ok(ExitCode::FAILURE);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn print_batch_report(report: &BatchReport) {
+    println!(
+        "\n{}",
+        format!(
+            "=== Batch ({} jobs in {:.1}s) ===",
+            report.jobs.len(),
+            report.total_ms / 1000.0
+        )
+        .bold()
+    );
+
+    let column_width = |header: &str, values: Vec<&str>| {
+        values
+            .into_iter()
+            .chain([header])
+            .map(|value| value.chars().count())
+            .max()
+            .unwrap_or_default()
+    };
+    let renderer = |job: &JobReport| job.renderer.clone().unwrap_or_else(|| "-".to_string());
+    let name_width = column_width(
+        "Job",
+        report.jobs.iter().map(|job| job.name.as_str()).collect(),
+    );
+    let renderers: Vec<_> = report.jobs.iter().map(renderer).collect();
+    let renderer_width = column_width("Renderer", renderers.iter().map(String::as_str).collect());
+    println!(
+        "{:<name_width$}  {:<renderer_width$}  {:>9}  {:>9}  {:>9}  {}",
+        "Job".bold(),
+        "Renderer".bold(),
+        "Samples".bold(),
+        "Load".bold(),
+        "Render".bold(),
+        "Result".bold()
+    );
+    for job in &report.jobs {
+        let result = match (&job.output, &job.error) {
+            (_, Some(_)) => "failed".red().to_string(),
+            (Some(output), None) => output.display().green().to_string(),
+            (None, None) => String::new(),
+        };
+        println!(
+            "{:<name_width$}  {:<renderer_width$}  {:>9}  {:>7.0}ms  {:>7.0}ms  {result}",
+            job.name,
+            renderer(job),
+            format!("{}/{}", job.samples, job.total_samples),
+            job.load_ms,
+            job.render_ms
+        );
+    }
+
+    let failures = report.failures().count();
+    if failures > 0 {
+        println!(
+            "{} {failures} of {} jobs failed",
+            "error:".red().bold(),
+            report.jobs.len()
+        );
+    }
+}
+
+fn print_info(scene: &Scene, renderer_name: &str, max_samples: u32, max_bounces: u32) {
+    println!(
+        "{}",
+        format!("{}", format!("=== Raydar v{} ===", crate_version!()).bold())
+    );
+
+    println!("{} {renderer_name}", "Renderer:".red().bold());
+
+    println!("{} {max_samples}", "Max Samples:".yellow().bold());
+
+    if let Some(time_limit) = scene.render_settings.time_limit {
+        println!("{} {time_limit}s", "Time Limit:".yellow().bold());
+    }
+
+    println!("{} {max_bounces}", "Max Bounces:".green().bold());
+
     println!(
         "{} {}x{}",
         "Resolution:".blue().bold(),
@@ -58,6 +600,224 @@ fn print_info(args: &RaydarArgs, scene: &Scene, renderer: &dyn Renderer) {
     println!("{} {}", "Objects:".magenta().bold(), scene.objects.len());
 }
 
+fn info(scene_file: &Path) -> eyre::Result<ExitCode> {
+    let scene = cli::load_scene(scene_file)?;
+
+    println!("{}", format!("=== {} ===", scene_file.display()).bold());
+
+    let sphere_count = scene
+        .objects
+        .iter()
+        .filter(|object| matches!(object.geometry, Geometry::Sphere(_)))
+        .count();
+    let cube_count = scene.objects.len() - sphere_count;
+    println!(
+        "{} {} ({sphere_count} spheres, {cube_count} cubes)",
+        "Objects:".red().bold(),
+        scene.objects.len()
+    );
+
+    let bounds = scene.bounds();
+    if bounds.is_empty() {
+        println!("{} none", "Bounds:".yellow().bold());
+    } else {
+        println!(
+            "{} ({}, {}, {}) to ({}, {}, {})",
+            "Bounds:".yellow().bold(),
+            bounds.min.x,
+            bounds.min.y,
+            bounds.min.z,
+            bounds.max.x,
+            bounds.max.y,
+            bounds.max.z
+        );
+    }
+
+    let emissive_objects: Vec<_> = scene
+        .objects
+        .iter()
+        .enumerate()
+        .filter(|(_, object)| object.material.is_emissive())
+        .collect();
+    println!(
+        "{} {}",
+        "Emissive Objects:".green().bold(),
+        emissive_objects.len()
+    );
+    for (index, object) in emissive_objects {
+        let material = &object.material;
+        println!(
+            "    {} color ({}, {}, {}), strength {}",
+            format!("objects[{index}]").bold(),
+            material.emission_color.x,
+            material.emission_color.y,
+            material.emission_color.z,
+            material.emission_strength
+        );
+    }
+
+    let world = match scene.world {
+        World::SkyColor { .. } => "sky color",
+        World::SolidColor(_) => "solid color",
+        World::Transparent => "transparent",
+    };
+    println!("{} {world}", "World:".blue().bold());
+
+    let settings = &scene.render_settings;
+    print!(
+        "{} {}, {} samples",
+        "Render Settings:".magenta().bold(),
+        settings.resolution,
+        settings.samples
+    );
+    if let Some(time_limit) = settings.time_limit {
+        print!(", {time_limit}s time limit");
+    }
+    println!();
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn validate(scene_file: &Path) -> eyre::Result<ExitCode> {
+    let scene = cli::load_scene(scene_file)?;
+    let diagnostics = scene.validate();
+
+    for diagnostic in &diagnostics {
+        let severity = match diagnostic.severity {
+            Severity::Error => "error:".red().bold().to_string(),
+            Severity::Warning => "warning:".yellow().bold().to_string(),
+        };
+        if diagnostic.path.is_empty() {
+            println!("{severity} {}", diagnostic.message);
+        } else {
+            println!(
+                "{severity} {}: {}",
+                diagnostic.path.bold(),
+                diagnostic.message
+            );
+        }
+    }
+
+    let error_count = diagnostics.iter().filter(|d| d.is_error()).count();
+    let warning_count = diagnostics.len() - error_count;
+    if diagnostics.is_empty() {
+        println!("{} is valid", scene_file.display());
+    } else {
+        println!(
+            "{}: {error_count} error(s), {warning_count} warning(s)",
+            scene_file.display()
+        );
+    }
+
+    if error_count > 0 {
+        return Ok(ExitCode::FAILURE);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn convert(input: &Path, output: &Path, format: Option<SceneFormat>) -> eyre::Result<ExitCode> {
+    let format = format
+        .or_else(|| SceneFormat::from_path(output))
+        .ok_or_else(|| {
+            eyre::eyre!(
+                "Cannot guess the format of {} from its extension, use --format",
+                output.display()
+            )
+        })?;
+
+    let scene = cli::load_scene(input)?;
+    let contents = format.write(&scene)?;
+    fs::write(output, contents).wrap_err_with(|| format!("Cannot write {}", output.display()))?;
+
+    println!("Converted {} to {}", input.display(), output.display());
+    Ok(ExitCode::SUCCESS)
+}
+
+fn print_devices() -> eyre::Result<ExitCode> {
+    let devices = vulkan::list_devices().wrap_err("Cannot enumerate Vulkan devices")?;
+
+    println!("{}", "=== Vulkan Devices ===".bold());
+    if devices.is_empty() {
+        println!("No Vulkan devices found");
+    }
+
+    for device in devices {
+        println!(
+            "{} {} ({:?})",
+            format!("[{}]", device.index).bold(),
+            device.name,
+            device.device_type
+        );
+        println!(
+            "    {} {}",
+            "Driver:".red().bold(),
+            device.driver.as_deref().unwrap_or("unknown")
+        );
+        println!("    {} {}", "Vulkan:".yellow().bold(), device.api_version);
+        println!(
+            "    {} {} MiB",
+            "Memory:".green().bold(),
+            device.memory / (1024 * 1024)
+        );
+        println!(
+            "    {} {}",
+            "Ray Tracing:".blue().bold(),
+            if device.supports_ray_tracing {
+                "supported"
+            } else {
+                "unsupported"
+            }
+        );
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn print_path_statistics(statistics: &PathStatistics) {
+    println!(
+        "{} {} primary, {} secondary, {} shadow",
+        "Rays:".cyan().bold(),
+        statistics.primary_rays(),
+        statistics.secondary_rays(),
+        statistics.shadow_rays()
+    );
+
+    if let Some(intersection_tests) = statistics.intersection_tests() {
+        println!(
+            "{} {intersection_tests}",
+            "Intersection Tests:".cyan().bold()
+        );
+    }
+    if let Some(bvh_nodes_visited) = statistics.bvh_nodes_visited() {
+        println!("{} {bvh_nodes_visited}", "BVH Nodes Visited:".cyan().bold());
+    }
+
+    if let Some(average_path_length) = statistics.average_length() {
+        println!(
+            "{} {:.2} rays",
+            "Average Path Length:".cyan().bold(),
+            average_path_length
+        );
+
+        let histogram = statistics.length_histogram();
+        let bins: Vec<_> = histogram
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count > 0)
+            .map(|(length, &count)| {
+                let plus = if length == histogram.len() - 1 {
+                    "+"
+                } else {
+                    ""
+                };
+                let percentage = count as f64 / statistics.path_count() as f64 * 100.0;
+                format!("{length}{plus}: {percentage:.1}%")
+            })
+            .collect();
+        println!("{} {}", "Path Lengths:".cyan().bold(), bins.join(", "));
+    }
+}
+
 fn print_profiling_metrics(profiler: &Profiler) -> eyre::Result<()> {
     println!("\n{}", "=== Render Profiling Metrics ===".bold());
 
@@ -91,6 +851,15 @@ fn print_profiling_metrics(profiler: &Profiler) -> eyre::Result<()> {
             .as_millis()
     );
 
+    if let Some(samples_per_second) = profiler.samples_per_second() {
+        println!(
+            "{} {samples_per_second:.1}",
+            "Samples per Second:".green().bold()
+        );
+    }
+
+    print_path_statistics(profiler.path_statistics());
+
     println!("{}", "─".repeat(40).blue().bold());
 
     println!(
@@ -0,0 +1,352 @@
+//! Repeated, measured renders of scenes, for tracking the performance of the renderers.
+//!
+//! A benchmark renders every scene a few times to warm up caches and drivers, then measures
+//! a number of runs with the [`Profiler`](super::timing::Profiler). The results are summarized
+//! in a [`BenchmarkReport`], which can be saved as JSON and compared against an earlier one.
+
+use std::{fmt, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use super::Renderer;
+use crate::scene::{render_settings::Resolution, Scene};
+
+/// Durations shorter than this, in milliseconds, are dominated by the noise of the timers and
+/// are not compared against baselines.
+pub const MIN_COMPARED_DURATION_MS: f64 = 1.0;
+
+/// Summary statistics of a measured quantity.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct Statistics {
+    pub mean: f64,
+    pub median: f64,
+    /// The sample standard deviation, zero for a single measurement
+    pub stddev: f64,
+}
+
+impl Statistics {
+    /// Summarizes a list of measurements, if there are any.
+    pub fn from_samples(samples: &[f64]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+
+        let count = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / count;
+
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let middle = sorted.len() / 2;
+        let median = if sorted.len() % 2 == 1 {
+            sorted[middle]
+        } else {
+            (sorted[middle - 1] + sorted[middle]) / 2.0
+        };
+
+        let stddev = if samples.len() > 1 {
+            let variance = samples
+                .iter()
+                .map(|sample| (sample - mean).powi(2))
+                .sum::<f64>()
+                / (count - 1.0);
+            variance.sqrt()
+        } else {
+            0.0
+        };
+
+        Some(Self {
+            mean,
+            median,
+            stddev,
+        })
+    }
+}
+
+/// The results of benchmarking a single scene.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SceneReport {
+    /// The name of the scene, usually the path of its file
+    pub scene: String,
+    pub renderer: String,
+    pub resolution: Resolution,
+    pub samples: u32,
+    /// Time it takes to prepare the scene for rendering, in milliseconds
+    pub prepare_ms: Statistics,
+    /// Time it takes to render a sample, in milliseconds
+    pub sample_ms: Statistics,
+    /// Time it takes to render the whole frame, in milliseconds
+    pub frame_ms: Statistics,
+    /// Rays traced per second over the whole frame, zero if no frame took a measurable time
+    pub rays_per_second: Statistics,
+}
+
+impl SceneReport {
+    /// Whether the reports are of the same scene, rendered with the same configuration.
+    pub fn is_comparable_to(&self, other: &SceneReport) -> bool {
+        self.scene == other.scene
+            && self.renderer == other.renderer
+            && self.resolution == other.resolution
+            && self.samples == other.samples
+    }
+
+    fn metrics(&self) -> [(Metric, Statistics); 4] {
+        [
+            (Metric::Prepare, self.prepare_ms),
+            (Metric::Sample, self.sample_ms),
+            (Metric::Frame, self.frame_ms),
+            (Metric::RaysPerSecond, self.rays_per_second),
+        ]
+    }
+}
+
+/// The results of a benchmark over a set of scenes.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BenchmarkReport {
+    /// The version of Raydar that ran the benchmark
+    pub version: String,
+    pub warmup_runs: u32,
+    pub runs: u32,
+    pub scenes: Vec<SceneReport>,
+}
+
+impl BenchmarkReport {
+    /// Compares the report against a baseline, returning the metrics of scenes that got worse
+    /// by more than `threshold`, a fraction of the baseline's mean.
+    ///
+    /// Scenes are matched by name and only compared if they were rendered by the same renderer
+    /// with the same resolution and number of samples. Durations shorter than
+    /// [`MIN_COMPARED_DURATION_MS`] are not compared either.
+    pub fn regressions(&self, baseline: &BenchmarkReport, threshold: f64) -> Vec<Regression> {
+        let mut regressions = Vec::new();
+        for report in &self.scenes {
+            let Some(baseline_report) = baseline
+                .scenes
+                .iter()
+                .find(|baseline_report| baseline_report.is_comparable_to(report))
+            else {
+                continue;
+            };
+
+            for ((metric, current), (_, baseline)) in
+                report.metrics().into_iter().zip(baseline_report.metrics())
+            {
+                let comparable = if metric.higher_is_better() {
+                    baseline.mean > 0.0
+                } else {
+                    current.mean.max(baseline.mean) >= MIN_COMPARED_DURATION_MS
+                };
+                if !comparable {
+                    continue;
+                }
+
+                let change = (current.mean - baseline.mean) / baseline.mean;
+                let regressed = if metric.higher_is_better() {
+                    change < -threshold
+                } else {
+                    change > threshold
+                };
+                if regressed {
+                    regressions.push(Regression {
+                        scene: report.scene.clone(),
+                        metric,
+                        baseline: baseline.mean,
+                        current: current.mean,
+                    });
+                }
+            }
+        }
+        regressions
+    }
+}
+
+/// A measured quantity of a [`SceneReport`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    Prepare,
+    Sample,
+    Frame,
+    RaysPerSecond,
+}
+
+impl Metric {
+    fn higher_is_better(self) -> bool {
+        self == Metric::RaysPerSecond
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Metric::Prepare => write!(f, "prepare time"),
+            Metric::Sample => write!(f, "sample time"),
+            Metric::Frame => write!(f, "frame time"),
+            Metric::RaysPerSecond => write!(f, "rays per second"),
+        }
+    }
+}
+
+/// A metric of a scene that got worse than in the baseline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Regression {
+    pub scene: String,
+    pub metric: Metric,
+    pub baseline: f64,
+    pub current: f64,
+}
+
+impl fmt::Display for Regression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let change = (self.current - self.baseline) / self.baseline * 100.0;
+        write!(
+            f,
+            "{}: {} changed by {change:+.1}% ({:.3} -> {:.3})",
+            self.scene, self.metric, self.baseline, self.current
+        )
+    }
+}
+
+/// Renders a scene `warmup_runs` times without measuring it, then measures `runs` renders.
+///
+/// `runs` must be at least one.
+pub fn benchmark_scene(
+    renderer: &mut dyn Renderer,
+    scene: &Scene,
+    name: String,
+    warmup_runs: u32,
+    runs: u32,
+) -> SceneReport {
+    assert!(runs > 0, "a benchmark needs at least one measured run");
+
+    for _ in 0..warmup_runs {
+        renderer.render_frame(scene);
+    }
+
+    let milliseconds =
+        |duration: Option<Duration>| duration.unwrap_or_default().as_secs_f64() * 1000.0;
+    let mut prepare_ms = Vec::new();
+    let mut sample_ms = Vec::new();
+    let mut frame_ms = Vec::new();
+    let mut rays_per_second = Vec::new();
+    for _ in 0..runs {
+        renderer.render_frame(scene);
+
+        let profiler = renderer.profiler();
+        let frame_time = profiler.frame_timer().duration().unwrap_or_default();
+        prepare_ms.push(milliseconds(profiler.prepare_timer().duration()));
+        sample_ms.push(milliseconds(profiler.sample_timer().duration()));
+        frame_ms.push(milliseconds(Some(frame_time)));
+        rays_per_second.extend(throughput(
+            profiler.path_statistics().ray_count(),
+            frame_time,
+        ));
+    }
+
+    let statistics = |samples: &[f64]| {
+        Statistics::from_samples(samples).expect("there is at least one measured run")
+    };
+    SceneReport {
+        scene: name,
+        renderer: renderer.name().to_string(),
+        resolution: scene.render_settings.resolution,
+        samples: renderer.config().max_sample_count,
+        prepare_ms: statistics(&prepare_ms),
+        sample_ms: statistics(&sample_ms),
+        frame_ms: statistics(&frame_ms),
+        rays_per_second: Statistics::from_samples(&rays_per_second).unwrap_or_default(),
+    }
+}
+
+/// The number of rays traced per second, if the frame took a measurable time.
+///
+/// Without this guard, frames the timers could not measure would give an infinite or NaN
+/// throughput, which JSON reports cannot represent.
+fn throughput(ray_count: u64, frame_time: Duration) -> Option<f64> {
+    let seconds = frame_time.as_secs_f64();
+    (seconds > 0.0).then(|| ray_count as f64 / seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{throughput, BenchmarkReport, Metric, SceneReport, Statistics};
+    use crate::scene::render_settings::Resolution;
+
+    fn report(frame_ms: f64, rays_per_second: f64) -> BenchmarkReport {
+        let statistics = |mean| Statistics {
+            mean,
+            median: mean,
+            stddev: 0.0,
+        };
+        BenchmarkReport {
+            version: String::new(),
+            warmup_runs: 1,
+            runs: 1,
+            scenes: vec![SceneReport {
+                scene: "scene.rscn".to_string(),
+                renderer: "CPU".to_string(),
+                resolution: Resolution::default(),
+                samples: 16,
+                prepare_ms: statistics(0.1),
+                sample_ms: statistics(frame_ms / 16.0),
+                frame_ms: statistics(frame_ms),
+                rays_per_second: statistics(rays_per_second),
+            }],
+        }
+    }
+
+    #[test]
+    fn statistics_summarize_samples() {
+        let statistics = Statistics::from_samples(&[4.0, 1.0, 3.0, 2.0]).unwrap();
+        assert_eq!(statistics.mean, 2.5);
+        assert_eq!(statistics.median, 2.5);
+        assert!((statistics.stddev - (5.0f64 / 3.0).sqrt()).abs() < 1e-12);
+
+        let statistics = Statistics::from_samples(&[3.0, 1.0, 2.0]).unwrap();
+        assert_eq!(statistics.median, 2.0);
+
+        let statistics = Statistics::from_samples(&[7.0]).unwrap();
+        assert_eq!(statistics.stddev, 0.0);
+
+        assert!(Statistics::from_samples(&[]).is_none());
+    }
+
+    #[test]
+    fn throughput_needs_a_measurable_frame_time() {
+        assert_eq!(throughput(1000, Duration::from_millis(500)), Some(2000.0));
+        assert_eq!(throughput(1000, Duration::ZERO), None);
+        assert_eq!(throughput(0, Duration::ZERO), None);
+    }
+
+    #[test]
+    fn regressions_exceed_the_threshold() {
+        let baseline = report(100.0, 1e6);
+
+        assert!(report(104.0, 0.97e6)
+            .regressions(&baseline, 0.05)
+            .is_empty());
+
+        let regressions = report(110.0, 0.9e6).regressions(&baseline, 0.05);
+        let metrics: Vec<_> = regressions
+            .iter()
+            .map(|regression| regression.metric)
+            .collect();
+        assert_eq!(
+            metrics,
+            [Metric::Sample, Metric::Frame, Metric::RaysPerSecond]
+        );
+
+        // Prepare times this short are dominated by noise
+        let mut noisy = report(100.0, 1e6);
+        noisy.scenes[0].prepare_ms.mean = 0.5;
+        assert!(noisy.regressions(&baseline, 0.05).is_empty());
+
+        // Renders of a different resolution are not compared
+        let mut resized = report(400.0, 1e6);
+        resized.scenes[0].resolution = Resolution::new(1920, 1080);
+        assert!(resized.regressions(&baseline, 0.05).is_empty());
+
+        // Improvements are never regressions
+        assert!(report(50.0, 2e6).regressions(&baseline, 0.05).is_empty());
+    }
+}
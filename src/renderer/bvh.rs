@@ -0,0 +1,234 @@
+//! Bounding volume hierarchy over the objects of a scene.
+//!
+//! The hierarchy is stored as a flat array of nodes in depth-first order, so that it can be
+//! uploaded to the GPU as-is and traversed without pointers: the left child of an interior
+//! node always directly follows it, and the node stores the index of its right child.
+
+use cgmath::{EuclideanSpace, Point3};
+
+use crate::scene::objects::Object;
+
+/// An axis-aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    /// Returns a box that contains nothing, the identity for [`Aabb::union`].
+    pub fn empty() -> Self {
+        Self {
+            min: Point3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: Point3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: Point3::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: Point3::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+
+    pub fn centroid(&self) -> Point3<f32> {
+        self.min.midpoint(self.max)
+    }
+
+    /// Returns the index of the axis along which the box is the largest.
+    pub fn largest_axis(&self) -> usize {
+        let extent = self.max - self.min;
+        if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        }
+    }
+}
+
+/// A node of a flattened [`Bvh`].
+#[derive(Debug, Clone, Copy)]
+pub struct BvhNode {
+    pub bounds: Aabb,
+    /// For leaves, the first entry in [`Bvh::object_indices`].
+    /// For interior nodes, the index of the right child.
+    pub left_first: u32,
+    /// The number of objects in a leaf, zero for interior nodes.
+    pub count: u32,
+}
+
+impl BvhNode {
+    pub fn is_leaf(&self) -> bool {
+        self.count > 0
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Bvh {
+    pub nodes: Vec<BvhNode>,
+    /// Indices into the scene's objects, referenced by the leaves.
+    pub object_indices: Vec<u32>,
+    /// The bounds of the objects the hierarchy was built for.
+    object_bounds: Vec<Aabb>,
+    /// The number of interior nodes on the longest path from the root to a leaf.
+    depth: usize,
+}
+
+impl Bvh {
+    /// The maximum number of objects stored in a single leaf.
+    const MAX_LEAF_SIZE: usize = 2;
+
+    /// The deepest hierarchy the traversals can handle. Traversing a hierarchy of this
+    /// [`depth`](Self::depth) keeps at most one more node on the stack, so this must be below
+    /// `MAX_STACK_SIZE` in `raytrace.comp`.
+    pub const MAX_DEPTH: usize = 31;
+
+    /// Builds a hierarchy by recursively splitting the objects at the median of their
+    /// centroids along the largest axis.
+    #[tracing::instrument(name = "bvh_build", skip_all, fields(objects = objects.len()))]
+    pub fn build(objects: &[Object]) -> Self {
+        let bounds: Vec<_> = objects
+            .iter()
+            .map(|object| object.geometry.bounds())
+            .collect();
+
+        let mut bvh = Self {
+            nodes: Vec::with_capacity(objects.len() * 2),
+            object_indices: (0..objects.len() as u32).collect(),
+            object_bounds: Vec::new(),
+            depth: 0,
+        };
+        if !objects.is_empty() {
+            bvh.build_node(&bounds, 0, objects.len(), 0);
+        }
+        bvh.object_bounds = bounds;
+
+        // Median splits halve the objects at every level, so this only fails for more
+        // objects than fit in memory
+        assert!(
+            bvh.depth <= Self::MAX_DEPTH,
+            "the BVH of {} objects is {} levels deep, more than the traversal stacks hold",
+            objects.len(),
+            bvh.depth
+        );
+        bvh
+    }
+
+    /// Returns the number of interior nodes on the longest path from the root to a leaf.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Whether the hierarchy still fits the objects, which is the case as long as the bounds
+    /// of every object are unchanged.
+    pub fn is_built_for(&self, objects: &[Object]) -> bool {
+        self.object_bounds.len() == objects.len()
+            && self
+                .object_bounds
+                .iter()
+                .zip(objects)
+                .all(|(bounds, object)| *bounds == object.geometry.bounds())
+    }
+
+    /// Returns the bounds of the whole hierarchy.
+    pub fn bounds(&self) -> Aabb {
+        self.nodes
+            .first()
+            .map(|root| root.bounds)
+            .unwrap_or_else(Aabb::empty)
+    }
+
+    fn build_node(&mut self, bounds: &[Aabb], first: usize, count: usize, depth: usize) -> usize {
+        let indices = &mut self.object_indices[first..first + count];
+        let node_bounds = indices
+            .iter()
+            .fold(Aabb::empty(), |acc, &i| acc.union(&bounds[i as usize]));
+
+        let node_index = self.nodes.len();
+        self.nodes.push(BvhNode {
+            bounds: node_bounds,
+            left_first: first as u32,
+            count: count as u32,
+        });
+
+        if count <= Self::MAX_LEAF_SIZE {
+            return node_index;
+        }
+        self.depth = self.depth.max(depth + 1);
+
+        let centroid_bounds = indices.iter().fold(Aabb::empty(), |acc, &i| {
+            let centroid = bounds[i as usize].centroid();
+            acc.union(&Aabb {
+                min: centroid,
+                max: centroid,
+            })
+        });
+        let axis = centroid_bounds.largest_axis();
+        let middle = count / 2;
+        indices.select_nth_unstable_by(middle, |&a, &b| {
+            let a = bounds[a as usize].centroid()[axis];
+            let b = bounds[b as usize].centroid()[axis];
+            a.total_cmp(&b)
+        });
+
+        self.build_node(bounds, first, middle, depth + 1);
+        let right_child = self.build_node(bounds, first + middle, count - middle, depth + 1);
+
+        let node = &mut self.nodes[node_index];
+        node.left_first = right_child as u32;
+        node.count = 0;
+
+        node_index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Point3;
+
+    use super::Bvh;
+    use crate::scene::{
+        material::Material,
+        objects::{Geometry, Object, Sphere},
+    };
+
+    fn sphere(center: Point3<f32>) -> Object {
+        Object {
+            geometry: Geometry::Sphere(Sphere {
+                center,
+                radius: 0.5,
+            }),
+            material: Material::default(),
+        }
+    }
+
+    #[test]
+    fn median_splits_keep_the_hierarchy_shallow() {
+        let spread: Vec<_> = (0..1000)
+            .map(|i| sphere(Point3::new(i as f32, 0.0, 0.0)))
+            .collect();
+        assert_eq!(Bvh::build(&spread).depth(), 9);
+
+        // Objects at the same position cannot be told apart, but are still split in halves
+        let stacked: Vec<_> = (0..1000)
+            .map(|_| sphere(Point3::new(0.0, 0.0, 0.0)))
+            .collect();
+        assert_eq!(Bvh::build(&stacked).depth(), 9);
+
+        assert_eq!(Bvh::build(&[sphere(Point3::new(0.0, 0.0, 0.0))]).depth(), 0);
+    }
+}
@@ -0,0 +1,333 @@
+//! Checkpoints of CPU renders, which let long renders survive crashes and be merged.
+//!
+//! A checkpoint holds the float accumulation buffer of a render together with its sample
+//! count and seed. The random numbers of a sample only depend on the seed, the pixel and the
+//! index of the sample, so these are the complete state of the render: resuming a checkpoint
+//! gives the same image as rendering without interruption.
+//!
+//! Checkpoint files start with a header of little-endian integers, followed by the red, green,
+//! blue and alpha sums of every pixel as little-endian `f32`s, row by row.
+
+use std::{
+    error::Error,
+    fmt, fs,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use image::Rgba32FImage;
+
+use crate::scene::Scene;
+
+/// The bytes every checkpoint file starts with.
+const MAGIC: &[u8; 8] = b"RAYDARCK";
+
+/// Version of the checkpoint files written by this version of Raydar.
+pub const VERSION: u32 = 1;
+
+/// Size of the header in bytes: magic, version, scene hash, seed, sample count, width and height.
+const HEADER_SIZE: usize = 8 + 4 + 8 + 4 + 4 + 4 + 4;
+
+/// Where and how often a renderer saves checkpoints.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckpointSettings {
+    pub path: PathBuf,
+    /// Minimum time between two checkpoints, the last one is saved when the render stops
+    pub interval: Duration,
+}
+
+/// Errors of reading, resuming and merging checkpoints.
+#[derive(Debug)]
+pub enum CheckpointError {
+    /// The checkpoint file could not be read or written.
+    Io(io::Error),
+    /// The file is not a checkpoint or it is truncated.
+    Invalid,
+    /// The file was written by a newer version of Raydar.
+    UnsupportedVersion(u32),
+    /// The checkpoint was rendered from a different scene.
+    SceneMismatch,
+    /// Two merged checkpoints were rendered with the same seed, so they contain the same samples.
+    DuplicateSeed(u32),
+    /// No checkpoints were given to merge.
+    NoCheckpoints,
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::Invalid => write!(f, "not a checkpoint file or truncated"),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "checkpoint version {version} is newer than the supported version {VERSION}"
+            ),
+            Self::SceneMismatch => write!(f, "checkpoint was rendered from a different scene"),
+            Self::DuplicateSeed(seed) => write!(
+                f,
+                "several checkpoints were rendered with seed {seed}, merge renders with different seeds"
+            ),
+            Self::NoCheckpoints => write!(f, "no checkpoints to merge"),
+        }
+    }
+}
+
+impl Error for CheckpointError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CheckpointError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// The state of an unfinished render.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    /// Hash of the rendered scene, see [`Scene::content_hash`]
+    pub scene_hash: u64,
+    /// Seed of the random number generators
+    pub seed: u32,
+    /// Samples accumulated in the frame buffer
+    pub sample_count: u32,
+    /// Sum of all samples
+    pub frame_buffer: Rgba32FImage,
+}
+
+impl Checkpoint {
+    /// Reads a checkpoint file.
+    pub fn read(path: &Path) -> Result<Self, CheckpointError> {
+        let bytes = fs::read(path)?;
+        let header = bytes.get(..HEADER_SIZE).ok_or(CheckpointError::Invalid)?;
+        if &header[..MAGIC.len()] != MAGIC {
+            return Err(CheckpointError::Invalid);
+        }
+
+        let mut fields = header[MAGIC.len()..].chunks_exact(4);
+        let mut next_u32 = || u32::from_le_bytes(fields.next().unwrap().try_into().unwrap());
+        let version = next_u32();
+        if version > VERSION {
+            return Err(CheckpointError::UnsupportedVersion(version));
+        }
+        let scene_hash = u64::from(next_u32()) | (u64::from(next_u32()) << 32);
+        let seed = next_u32();
+        let sample_count = next_u32();
+        let (width, height) = (next_u32(), next_u32());
+
+        let pixels = &bytes[HEADER_SIZE..];
+        if pixels.len() as u64 != u64::from(width) * u64::from(height) * 16 {
+            return Err(CheckpointError::Invalid);
+        }
+        let pixels = pixels
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect();
+
+        Ok(Self {
+            scene_hash,
+            seed,
+            sample_count,
+            frame_buffer: Rgba32FImage::from_raw(width, height, pixels)
+                .ok_or(CheckpointError::Invalid)?,
+        })
+    }
+
+    /// Writes the checkpoint to a file.
+    ///
+    /// The file is written next to `path` first and then moved over it, so that a crash while
+    /// saving does not destroy the previous checkpoint.
+    pub fn write(&self, path: &Path) -> Result<(), CheckpointError> {
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        let temporary = PathBuf::from(temporary);
+
+        let mut file = BufWriter::new(fs::File::create(&temporary)?);
+        file.write_all(MAGIC)?;
+        for field in [
+            VERSION,
+            self.scene_hash as u32,
+            (self.scene_hash >> 32) as u32,
+        ] {
+            file.write_all(&field.to_le_bytes())?;
+        }
+        for field in [
+            self.seed,
+            self.sample_count,
+            self.frame_buffer.width(),
+            self.frame_buffer.height(),
+        ] {
+            file.write_all(&field.to_le_bytes())?;
+        }
+        for value in self.frame_buffer.as_raw() {
+            file.write_all(&value.to_le_bytes())?;
+        }
+        file.into_inner()
+            .map_err(|err| err.into_error())?
+            .sync_all()?;
+
+        fs::rename(&temporary, path)?;
+        Ok(())
+    }
+
+    /// Checks that the checkpoint was rendered from `scene`.
+    pub fn check_scene(&self, scene: &Scene) -> Result<(), CheckpointError> {
+        if self.scene_hash == scene.content_hash() {
+            Ok(())
+        } else {
+            Err(CheckpointError::SceneMismatch)
+        }
+    }
+}
+
+/// Sums independent renders of the same scene into one with more samples.
+///
+/// The renders must have different seeds, renders with the same seed take the same samples
+/// and merging them would not reduce their noise. Returns the summed frame buffer and the
+/// total number of samples.
+pub fn merge(checkpoints: &[Checkpoint]) -> Result<(Rgba32FImage, u32), CheckpointError> {
+    let (first, rest) = checkpoints
+        .split_first()
+        .ok_or(CheckpointError::NoCheckpoints)?;
+
+    let mut frame_buffer = first.frame_buffer.clone();
+    let mut sample_count = first.sample_count;
+    for (index, checkpoint) in rest.iter().enumerate() {
+        if checkpoint.scene_hash != first.scene_hash
+            || checkpoint.frame_buffer.dimensions() != frame_buffer.dimensions()
+        {
+            return Err(CheckpointError::SceneMismatch);
+        }
+        if checkpoints[..=index]
+            .iter()
+            .any(|other| other.seed == checkpoint.seed)
+        {
+            return Err(CheckpointError::DuplicateSeed(checkpoint.seed));
+        }
+
+        for (sum, value) in frame_buffer.iter_mut().zip(checkpoint.frame_buffer.iter()) {
+            *sum += value;
+        }
+        sample_count += checkpoint.sample_count;
+    }
+
+    Ok((frame_buffer, sample_count))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use image::{Rgba, Rgba32FImage};
+
+    use super::{merge, Checkpoint, CheckpointError};
+    use crate::{
+        renderer::{cpu::CpuRenderer, Renderer, RendererConfig},
+        scene::{render_settings::Resolution, Scene},
+    };
+
+    fn checkpoint(seed: u32, sample_count: u32, value: f32) -> Checkpoint {
+        Checkpoint {
+            scene_hash: 0x0123_4567_89ab_cdef,
+            seed,
+            sample_count,
+            frame_buffer: Rgba32FImage::from_pixel(3, 2, Rgba([value, value * 2.0, 0.5, 1.0])),
+        }
+    }
+
+    fn small_scene() -> Scene {
+        let mut scene = Scene::default();
+        scene.render_settings.resolution = Resolution::new(16, 9);
+        scene.apply_resolution();
+        scene
+    }
+
+    #[test]
+    fn checkpoints_survive_a_round_trip_through_a_file() {
+        let path = env::temp_dir().join(format!("raydar-checkpoint-{}.ckpt", std::process::id()));
+        let checkpoint = checkpoint(7, 12, 0.25);
+        checkpoint.write(&path).unwrap();
+
+        assert_eq!(Checkpoint::read(&path).unwrap(), checkpoint);
+
+        std::fs::write(&path, b"RAYDARCK").unwrap();
+        assert!(matches!(
+            Checkpoint::read(&path),
+            Err(CheckpointError::Invalid)
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn merge_sums_renders_with_different_seeds() {
+        let (frame_buffer, sample_count) =
+            merge(&[checkpoint(1, 4, 1.0), checkpoint(2, 6, 3.0)]).unwrap();
+        assert_eq!(sample_count, 10);
+        assert_eq!(frame_buffer.get_pixel(2, 1), &Rgba([4.0, 8.0, 1.0, 2.0]));
+
+        assert!(matches!(
+            merge(&[checkpoint(1, 4, 1.0), checkpoint(1, 6, 3.0)]),
+            Err(CheckpointError::DuplicateSeed(1))
+        ));
+        let other_scene = Checkpoint {
+            scene_hash: 0,
+            ..checkpoint(2, 6, 3.0)
+        };
+        assert!(matches!(
+            merge(&[checkpoint(1, 4, 1.0), other_scene]),
+            Err(CheckpointError::SceneMismatch)
+        ));
+    }
+
+    #[test]
+    fn resumed_renders_match_uninterrupted_ones() {
+        let scene = small_scene();
+        let config = |max_sample_count| RendererConfig {
+            max_sample_count,
+            seed: 3,
+            ..Default::default()
+        };
+
+        let mut uninterrupted = CpuRenderer::new(config(4));
+        uninterrupted.render_frame(&scene);
+
+        let mut first_half = CpuRenderer::new(config(2));
+        first_half.render_frame(&scene);
+        let checkpoint = first_half.checkpoint(&scene).unwrap();
+
+        let mut resumed = CpuRenderer::new(RendererConfig {
+            seed: 0,
+            ..config(4)
+        });
+        resumed.resume_from(checkpoint, &scene).unwrap();
+        resumed.render_frame(&scene);
+
+        assert_eq!(resumed.sample_count(), 4);
+        assert_eq!(resumed.frame_buffer(), uninterrupted.frame_buffer());
+    }
+
+    #[test]
+    fn resuming_a_different_scene_fails() {
+        let scene = small_scene();
+        let mut renderer = CpuRenderer::new(RendererConfig {
+            max_sample_count: 1,
+            ..Default::default()
+        });
+        renderer.render_frame(&scene);
+        let checkpoint = renderer.checkpoint(&scene).unwrap();
+
+        let mut other_scene = scene.clone();
+        other_scene.objects.pop();
+        assert!(matches!(
+            checkpoint.check_scene(&other_scene),
+            Err(CheckpointError::SceneMismatch)
+        ));
+        assert!(checkpoint.check_scene(&scene).is_ok());
+    }
+}
@@ -1,17 +1,65 @@
+use std::{
+    ops::{ControlFlow, Range},
+    time::Instant,
+};
+
 use cgmath::{ElementWise, EuclideanSpace, InnerSpace, Point3, Vector2, Vector3, Vector4, Zero};
-use image::{ImageBuffer, Rgba, Rgba32FImage, RgbaImage};
+use image::{Rgba, Rgba32FImage, RgbaImage};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 
 use crate::{
     scene::{
         objects::{Cube, Geometry, Object, Sphere},
+        world::World,
         Scene,
     },
-    utils,
+    utils::{sampling, Reflect, Refract},
+};
+
+use super::{
+    bvh::{Aabb, Bvh},
+    checkpoint::{Checkpoint, CheckpointError, CheckpointSettings},
+    output,
+    progress::Progress,
+    timing::{PathStatistics, Profiler},
+    Renderer, RendererConfig,
 };
 
-use self::utils::{Reflect, Refract};
+/// The distance bounced rays are moved away from the surface to avoid self-intersections.
+/// Must match `RAY_OFFSET` in `pathtrace.glsl`.
+const RAY_OFFSET: f32 = 0.001;
+
+/// The size of the stack of BVH nodes left to visit, enough for the deepest hierarchy
+/// `Bvh::build` accepts.
+const BVH_STACK_SIZE: usize = Bvh::MAX_DEPTH + 1;
 
-use super::{timing::Profiler, Renderer, RendererConfig};
+/// The width and height of the square tiles a sample is rendered in, in pixels.
+const TILE_SIZE: u32 = 64;
+
+/// A rectangle of the frame, in pixels.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Tile {
+    /// Splits a frame into square tiles of `size` pixels, row by row. The tiles at the right
+    /// and bottom edges are cut off at the frame.
+    pub fn split_frame(width: u32, height: u32, size: u32) -> impl Iterator<Item = Tile> {
+        (0..height).step_by(size as usize).flat_map(move |y| {
+            (0..width).step_by(size as usize).map(move |x| Tile {
+                x,
+                y,
+                width: size.min(width - x),
+                height: size.min(height - y),
+            })
+        })
+    }
+}
 
 #[derive(Debug)]
 pub struct Ray {
@@ -27,6 +75,15 @@ impl Ray {
         }
     }
 
+    /// Whether the ray enters the box before `t_max`.
+    fn hit_aabb(&self, aabb: &Aabb, t_max: f32) -> bool {
+        let t1 = (aabb.min - self.origin).div_element_wise(self.direction);
+        let t2 = (aabb.max - self.origin).div_element_wise(self.direction);
+        let t_enter = t1.x.min(t2.x).max(t1.y.min(t2.y)).max(t1.z.min(t2.z));
+        let t_exit = t1.x.max(t2.x).min(t1.y.max(t2.y)).min(t1.z.max(t2.z));
+        t_enter <= t_exit && t_exit >= 0.0 && t_enter < t_max
+    }
+
     fn at(&self, t: f32) -> Point3<f32> {
         self.origin + self.direction * t
     }
@@ -98,6 +155,14 @@ impl Ray {
     }
 }
 
+/// The kind of scattering a bounce performs, each kind has its own bounce limit.
+#[derive(Clone, Copy)]
+enum Lobe {
+    Diffuse,
+    Glossy,
+    Transmission,
+}
+
 struct HitRecord<'a> {
     #[allow(unused)]
     hit_distance: f32,
@@ -110,23 +175,60 @@ struct HitRecord<'a> {
 #[derive(Default)]
 pub struct CpuRenderer {
     profiler: Profiler,
+    bvh: Bvh,
     frame_buffer: Option<Rgba32FImage>,
     sample_count: u32,
     config: RendererConfig,
+    checkpointing: Option<CheckpointSettings>,
+    /// Checkpoint the next frame continues from instead of starting blank
+    resume: Option<Checkpoint>,
 }
 
 impl Renderer for CpuRenderer {
     fn render_frame(&mut self, scene: &Scene) -> RgbaImage {
-        self.new_frame(scene);
-        let mut frame_buffer = self.frame_buffer(scene);
+        self.render_frame_with_progress(scene, &mut |_| ControlFlow::Continue(()))
+    }
 
-        let mut rendered_frame =
-            ImageBuffer::new(scene.camera.resolution_x(), scene.camera.resolution_y());
+    fn render_frame_with_progress(
+        &mut self,
+        scene: &Scene,
+        on_progress: &mut dyn FnMut(Progress) -> ControlFlow<()>,
+    ) -> RgbaImage {
+        let start = Instant::now();
+        self.new_frame(scene);
+        let mut frame_buffer = self.take_frame_buffer(scene);
+        let mut last_checkpoint = Instant::now();
 
+        // Unlike `render_sample`, tone map the frame only once it is finished
         while self.sample_count < self.config.max_sample_count {
             self.render_next_sample(scene, &mut frame_buffer);
+
+            if self
+                .checkpointing
+                .as_ref()
+                .is_some_and(|checkpointing| last_checkpoint.elapsed() >= checkpointing.interval)
+            {
+                self.save_checkpoint(scene, &frame_buffer);
+                last_checkpoint = Instant::now();
+            }
+
+            let progress = Progress {
+                samples: self.sample_count,
+                total_samples: self.config.max_sample_count,
+                elapsed: start.elapsed(),
+            };
+            if on_progress(progress).is_break() {
+                // The timers are otherwise ended by the last sample of the frame
+                if self.sample_count < self.config.max_sample_count {
+                    self.profiler.render_timer.end();
+                    self.profiler.frame_timer.end();
+                }
+                break;
+            }
         }
-        self.print_frame_buffer(&frame_buffer, &mut rendered_frame);
+        self.save_checkpoint(scene, &frame_buffer);
+        let rendered_frame =
+            output::display_transform(&frame_buffer, self.sample_count, self.config.tonemapping);
 
         self.frame_buffer = Some(frame_buffer);
         rendered_frame
@@ -135,8 +237,19 @@ impl Renderer for CpuRenderer {
     fn new_frame(&mut self, scene: &Scene) {
         self.profiler.frame_timer.start();
         self.profiler.prepare_timer.start();
-        self.frame_buffer = Some(self.blank_frame_buffer(scene));
-        self.sample_count = 0;
+        let _span = tracing::info_span!("prepare_scene").entered();
+        self.bvh = Bvh::build(&scene.objects);
+        match self.resume.take() {
+            Some(checkpoint) => {
+                self.frame_buffer = Some(checkpoint.frame_buffer);
+                self.sample_count = checkpoint.sample_count;
+            }
+            None => {
+                self.frame_buffer = Some(self.blank_frame_buffer(scene));
+                self.sample_count = 0;
+            }
+        }
+        self.profiler.path_statistics = Default::default();
     }
 
     fn render_sample(&mut self, scene: &Scene) -> Option<RgbaImage> {
@@ -144,25 +257,19 @@ impl Renderer for CpuRenderer {
             return None;
         }
 
-        let mut frame_buffer = self.frame_buffer(scene);
-
-        let mut rendered_frame =
-            ImageBuffer::new(scene.camera.resolution_x(), scene.camera.resolution_y());
+        let mut frame_buffer = self.take_frame_buffer(scene);
 
         self.render_next_sample(scene, &mut frame_buffer);
-        self.print_frame_buffer(&frame_buffer, &mut rendered_frame);
+        let rendered_frame =
+            output::display_transform(&frame_buffer, self.sample_count, self.config.tonemapping);
 
         self.frame_buffer = Some(frame_buffer);
 
         Some(rendered_frame)
     }
 
-    fn max_sample_count(&self) -> u32 {
-        self.config.max_sample_count
-    }
-
-    fn max_bounces(&self) -> u32 {
-        self.config.max_bounces
+    fn frame_buffer(&self) -> Option<&Rgba32FImage> {
+        self.frame_buffer.as_ref()
     }
 
     fn profiler(&self) -> &Profiler {
@@ -173,12 +280,16 @@ impl Renderer for CpuRenderer {
         self.sample_count
     }
 
-    fn set_max_sample_count(&mut self, count: u32) {
-        self.config.max_sample_count = count;
+    fn config(&self) -> &RendererConfig {
+        &self.config
+    }
+
+    fn config_mut(&mut self) -> &mut RendererConfig {
+        &mut self.config
     }
 
-    fn set_max_bounces(&mut self, bounces: u32) {
-        self.config.max_bounces = bounces;
+    fn name(&self) -> &'static str {
+        "CPU"
     }
 }
 
@@ -190,24 +301,87 @@ impl CpuRenderer {
         }
     }
 
+    /// Saves checkpoints of the frames rendered with
+    /// [`Renderer::render_frame_with_progress`], or stops saving them with `None`.
+    pub fn set_checkpointing(&mut self, checkpointing: Option<CheckpointSettings>) {
+        self.checkpointing = checkpointing;
+    }
+
+    /// Continues the render of a checkpoint in the next frame, using its seed instead of the
+    /// configured one.
+    pub fn resume_from(
+        &mut self,
+        checkpoint: Checkpoint,
+        scene: &Scene,
+    ) -> Result<(), CheckpointError> {
+        checkpoint.check_scene(scene)?;
+        self.config.seed = checkpoint.seed;
+        self.resume = Some(checkpoint);
+        Ok(())
+    }
+
+    /// Returns a checkpoint of the current frame, if one has been started.
+    pub fn checkpoint(&self, scene: &Scene) -> Option<Checkpoint> {
+        Some(Checkpoint {
+            scene_hash: scene.content_hash(),
+            seed: self.config.seed,
+            sample_count: self.sample_count,
+            frame_buffer: self.frame_buffer.clone()?,
+        })
+    }
+
+    fn save_checkpoint(&self, scene: &Scene, frame_buffer: &Rgba32FImage) {
+        let Some(checkpointing) = &self.checkpointing else {
+            return;
+        };
+
+        let _span = tracing::info_span!("save_checkpoint").entered();
+        let checkpoint = Checkpoint {
+            scene_hash: scene.content_hash(),
+            seed: self.config.seed,
+            sample_count: self.sample_count,
+            frame_buffer: frame_buffer.clone(),
+        };
+        if let Err(err) = checkpoint.write(&checkpointing.path) {
+            eprintln!(
+                "Failed to save checkpoint to {}: {err}",
+                checkpointing.path.display()
+            );
+        }
+    }
+
+    /// Rebuilds the BVH if the objects moved or changed since it was built, as samples and tiles
+    /// may be rendered without starting a new frame first.
+    fn update_bvh(&mut self, scene: &Scene) {
+        if !self.bvh.is_built_for(&scene.objects) {
+            self.bvh = Bvh::build(&scene.objects);
+        }
+    }
+
     fn render_next_sample(&mut self, scene: &Scene, frame_buffer: &mut Rgba32FImage) {
         self.profiler.prepare_timer.end_if_not_ended();
         self.profiler.render_timer.start_if_not_started();
         self.profiler.sample_timer.start();
+        let _span = tracing::info_span!("sample", index = self.sample_count).entered();
+        self.update_bvh(scene);
 
-        for (x, y, pixel) in frame_buffer.enumerate_pixels_mut() {
-            let uv_coord = Vector2::new(
-                x as f32 / scene.camera.resolution_x() as f32,
-                1.0 - y as f32 / scene.camera.resolution_y() as f32,
+        let mut statistics = PathStatistics {
+            counts_traversal: true,
+            ..Default::default()
+        };
+        let (width, height) = frame_buffer.dimensions();
+        for tile in Tile::split_frame(width, height, TILE_SIZE) {
+            let _span = tracing::trace_span!("tile", x = tile.x, y = tile.y).entered();
+            self.add_tile_sample(
+                scene,
+                tile,
+                self.sample_count,
+                frame_buffer,
+                (0, 0),
+                &mut statistics,
             );
-            let color = self.per_pixel(uv_coord, scene);
-            *pixel = Rgba([
-                (pixel[0] + color.x),
-                (pixel[1] + color.y),
-                (pixel[2] + color.z),
-                (pixel[3] + color.w),
-            ]);
         }
+        self.profiler.path_statistics.merge(&statistics);
 
         self.sample_count += 1;
         if self.sample_count == self.config.max_sample_count {
@@ -218,19 +392,86 @@ impl CpuRenderer {
         self.profiler.sample_timer.end();
     }
 
-    fn print_frame_buffer(&self, frame_buffer: &Rgba32FImage, image: &mut RgbaImage) {
-        for (fb_pixel, rendered_pixel) in frame_buffer.pixels().zip(image.pixels_mut()) {
-            *rendered_pixel = Rgba([
-                ((fb_pixel[0] / self.sample_count as f32).clamp(0.0, 1.0) * 255.0) as u8,
-                ((fb_pixel[1] / self.sample_count as f32).clamp(0.0, 1.0) * 255.0) as u8,
-                ((fb_pixel[2] / self.sample_count as f32).clamp(0.0, 1.0) * 255.0) as u8,
-                ((fb_pixel[3] / self.sample_count as f32).clamp(0.0, 1.0) * 255.0) as u8,
-            ]);
+    /// Renders the samples in `samples` of the pixels of `tile` and returns their sum.
+    ///
+    /// The pixels get the same samples as when the whole frame is rendered, so tiles rendered
+    /// separately, even on other machines, add up to the same frame buffer.
+    pub fn render_tile(&mut self, scene: &Scene, tile: Tile, samples: Range<u32>) -> Rgba32FImage {
+        let _span = tracing::info_span!("render_tile", x = tile.x, y = tile.y).entered();
+        self.update_bvh(scene);
+
+        let mut statistics = PathStatistics {
+            counts_traversal: true,
+            ..Default::default()
+        };
+        let mut buffer = Rgba32FImage::new(tile.width, tile.height);
+        for sample_index in samples {
+            self.add_tile_sample(
+                scene,
+                tile,
+                sample_index,
+                &mut buffer,
+                (tile.x, tile.y),
+                &mut statistics,
+            );
+        }
+        self.profiler.path_statistics.merge(&statistics);
+
+        buffer
+    }
+
+    /// Adds sample `sample_index` of every pixel of `tile` to `buffer`, whose top left corner
+    /// is at `origin` in the frame.
+    fn add_tile_sample(
+        &self,
+        scene: &Scene,
+        tile: Tile,
+        sample_index: u32,
+        buffer: &mut Rgba32FImage,
+        origin: (u32, u32),
+        statistics: &mut PathStatistics,
+    ) {
+        for y in tile.y..tile.y + tile.height {
+            for x in tile.x..tile.x + tile.width {
+                let mut rng = self.pixel_rng(x + y * scene.camera.resolution_x(), sample_index);
+                let offset = sampling::pixel_filter(&mut rng, self.config.filter).value;
+                let uv_coord = Vector2::new(
+                    (x as f32 + 0.5 + offset.x) / scene.camera.resolution_x() as f32,
+                    1.0 - (y as f32 + 0.5 + offset.y) / scene.camera.resolution_y() as f32,
+                );
+                let color = self.per_pixel(uv_coord, scene, &mut rng, statistics);
+                let pixel = buffer.get_pixel_mut(x - origin.0, y - origin.1);
+                *pixel = Rgba([
+                    (pixel[0] + color.x),
+                    (pixel[1] + color.y),
+                    (pixel[2] + color.z),
+                    (pixel[3] + color.w),
+                ]);
+            }
         }
     }
 
+    /// Creates the random number generator for a sample of a pixel.
+    ///
+    /// Every pixel and sample gets its own stream derived from the seed, so the result does not
+    /// depend on the order in which pixels are rendered.
+    fn pixel_rng(&self, pixel_index: u32, sample_index: u32) -> SmallRng {
+        let stream = (u64::from(pixel_index) << 32) | u64::from(sample_index);
+        SmallRng::seed_from_u64(
+            u64::from(self.config.seed).wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ stream,
+        )
+    }
+
     /// Performs Monte Carlo path tracing for a single pixel by solving the rendering equation.
-    fn per_pixel(&self, uv_coord: Vector2<f32>, scene: &Scene) -> Vector4<f32> {
+    ///
+    /// Returns the radiance of the path and records it in `statistics`.
+    fn per_pixel(
+        &self,
+        uv_coord: Vector2<f32>,
+        scene: &Scene,
+        rng: &mut impl Rng,
+        statistics: &mut PathStatistics,
+    ) -> Vector4<f32> {
         let clip_space_point = (uv_coord * 2.0 - Vector2::new(1.0, 1.0))
             .extend(-1.0)
             .extend(-1.0);
@@ -252,9 +493,13 @@ impl CpuRenderer {
 
         let mut light = Vector3::zero();
         let mut attenuation = Vector3::new(1.0, 1.0, 1.0);
+        let mut alpha = 1.0;
+        let mut path_length = 0;
+        let (mut diffuse_bounces, mut glossy_bounces, mut transmission_bounces) = (0, 0, 0);
 
-        for _ in 0..self.config.max_bounces {
-            if let Some(hit_record) = self.trace_ray(&ray, scene) {
+        for bounce in 0..self.config.bounces.total {
+            path_length += 1;
+            if let Some(hit_record) = self.trace_ray(&ray, scene, statistics) {
                 // The roughness is squared to achieve perceptual linearity.
                 // (based on https://www.pbr-book.org/3ed-2018/Reflection_Models/Microfacet_Models.html
                 //           https://www.pbr-book.org/4ed/Reflection_Models/Roughness_Using_Microfacet_Theory
@@ -264,90 +509,171 @@ impl CpuRenderer {
                 let metallic = hit_record.object.material.metallic;
                 let transmission = hit_record.object.material.transmission;
 
-                let mut diffuse_direction =
-                    hit_record.world_normal + utils::random_in_unit_sphere();
-                if diffuse_direction.dot(hit_record.world_normal) < 0.0 {
-                    diffuse_direction = -diffuse_direction;
-                }
+                let normal = hit_record.world_normal;
+                let ray_direction = ray.direction.normalize();
 
-                let perfect_reflection = ray.direction.reflect(hit_record.world_normal);
+                let diffuse_direction = sampling::cosine_hemisphere(rng, normal).value;
 
-                // We perturb the reflection direction to achieve a more realistic reflection.
-                // TODO: use a GGX (Trowbridge-Reitz) microfacet distribution.
-                let random_offset = utils::random_in_unit_sphere() * roughness;
-                let specular_direction = (perfect_reflection + random_offset).normalize();
+                // The specular and transmission lobes scatter off a microfacet normal sampled
+                // from the visible normals of a GGX distribution. Without roughness, the
+                // distribution collapses to the surface normal.
+                let microfacet_normal = if roughness > 0.0 {
+                    sampling::ggx_vndf(rng, -ray_direction, normal, roughness).value
+                } else {
+                    normal
+                };
+
+                let mut specular_direction = ray_direction.reflect(microfacet_normal);
+                // Reflections off steep microfacets can point into the surface, mirror them
+                // back out instead of losing their energy.
+                if specular_direction.dot(normal) <= 0.0 {
+                    specular_direction = specular_direction.reflect(normal);
+                }
 
-                let transmission_ray = rand::random::<f32>() < transmission;
-                let direction = if transmission_ray {
+                let transmission_ray = rng.gen::<f32>() < transmission;
+                let (direction, lobe) = if transmission_ray {
                     let mut ior = hit_record.object.material.ior;
                     if hit_record.is_front_face {
                         ior = 1.0 / ior;
                     }
 
-                    let ray_direction = ray.direction.normalize();
-
                     // Apply Schlick's approximation for the Fresnel effect.
-                    let cos_theta = ray_direction.dot(-hit_record.world_normal).min(1.0);
+                    let cos_theta = ray_direction.dot(-microfacet_normal).min(1.0);
                     let reflection_coefficient = {
                         let r0 = ((ior - 1.0) / (ior + 1.0)).powi(2);
                         r0 + (1.0 - r0) * (1.0 - cos_theta).powi(5)
                     };
 
-                    if reflection_coefficient < rand::random::<f32>()
-                        && ray_direction.can_refract(hit_record.world_normal, ior)
+                    if reflection_coefficient < rng.gen::<f32>()
+                        && ray_direction.can_refract(microfacet_normal, ior)
                     {
-                        let refracted = ray_direction.refract(hit_record.world_normal, ior);
-                        // Add roughness perturbation to refracted direction
-                        let random_offset = utils::random_in_unit_sphere() * roughness;
-                        (refracted + random_offset).normalize()
+                        let refracted = ray_direction.refract(microfacet_normal, ior);
+                        // Refractions off steep microfacets can point out of the surface
+                        if refracted.dot(normal) < 0.0 {
+                            (refracted, Lobe::Transmission)
+                        } else {
+                            (specular_direction, Lobe::Glossy)
+                        }
                     } else {
-                        specular_direction
+                        (specular_direction, Lobe::Glossy)
                     }
-                } else if rand::random::<f32>() < metallic {
-                    specular_direction
+                } else if rng.gen::<f32>() < metallic {
+                    (specular_direction, Lobe::Glossy)
                 } else {
-                    if rand::random::<f32>() < roughness {
-                        diffuse_direction
+                    if rng.gen::<f32>() < roughness {
+                        (diffuse_direction, Lobe::Diffuse)
                     } else {
-                        specular_direction
+                        (specular_direction, Lobe::Glossy)
                     }
                 };
 
                 // Move the ray origin slightly along the direction of travel to avoid self-intersections
-                let offset = if transmission_ray {
-                    direction
-                } else {
-                    hit_record.world_normal
-                };
+                let offset = if transmission_ray { direction } else { normal };
                 ray = Ray {
-                    origin: hit_record.world_position + offset * 0.0001,
+                    origin: hit_record.world_position + offset * RAY_OFFSET,
                     direction,
                 };
                 if ray.direction.magnitude2() < 1e-10 {
-                    ray.direction = hit_record.world_normal;
+                    ray.direction = normal;
                 }
 
+                // Emitted light is attenuated by the surfaces it bounced off on its way to the camera
+                light += self.config.clamping.clamp(
+                    (hit_record.object.material.emission_color
+                        * hit_record.object.material.emission_strength)
+                        .mul_element_wise(attenuation),
+                    bounce,
+                );
+
                 attenuation = attenuation.mul_element_wise(hit_record.object.material.albedo);
 
-                light += hit_record.object.material.emission_color
-                    * hit_record.object.material.emission_strength;
+                // End the path once it exceeds the limit of the kind of bounce it is making
+                let (lobe_bounces, lobe_limit) = match lobe {
+                    Lobe::Diffuse => (&mut diffuse_bounces, self.config.bounces.diffuse),
+                    Lobe::Glossy => (&mut glossy_bounces, self.config.bounces.glossy),
+                    Lobe::Transmission => {
+                        (&mut transmission_bounces, self.config.bounces.transmission)
+                    }
+                };
+                *lobe_bounces += 1;
+                if *lobe_bounces > lobe_limit {
+                    break;
+                }
+
+                // Russian roulette: terminate paths that carry little light with a probability
+                // that grows as their throughput falls, and boost the surviving ones by the
+                // inverse of their survival probability to keep the estimate unbiased.
+                if self.config.russian_roulette
+                    && bounce + 1 >= self.config.russian_roulette_min_depth
+                {
+                    let survival_probability =
+                        attenuation.x.max(attenuation.y).max(attenuation.z).min(1.0);
+                    if rng.gen::<f32>() >= survival_probability {
+                        break;
+                    }
+                    attenuation /= survival_probability;
+                }
             } else {
                 // Add environment light contribution
-                light += scene.world.sample(ray).mul_element_wise(attenuation);
+                light += self.config.clamping.clamp(
+                    scene.world.sample(ray).mul_element_wise(attenuation),
+                    bounce,
+                );
+                // Camera rays that escape into a transparent world leave the pixel transparent
+                if bounce == 0 && scene.world == World::Transparent {
+                    alpha = 0.0;
+                }
                 break;
             };
         }
 
-        light.extend(1.0)
+        statistics.record_path(path_length);
+        light.extend(alpha)
     }
 
-    fn trace_ray<'a>(&self, ray: &Ray, scene: &'a Scene) -> Option<HitRecord<'a>> {
-        scene
-            .objects
-            .iter()
-            .filter_map(|o| ray.hit(o).map(|t| (o, t)))
-            .min_by_key(|(_, t)| ordered_float::OrderedFloat(*t))
-            .and_then(|(o, t)| self.closest_hit(ray, t, o))
+    /// Finds the closest object a ray hits by traversing the BVH, visiting nodes in the same
+    /// order as `trace_scene` in `raytrace.comp`.
+    fn trace_ray<'a>(
+        &self,
+        ray: &Ray,
+        scene: &'a Scene,
+        statistics: &mut PathStatistics,
+    ) -> Option<HitRecord<'a>> {
+        let mut closest: Option<(&Object, f32)> = None;
+
+        let mut stack = [0; BVH_STACK_SIZE];
+        let mut stack_size = usize::from(!self.bvh.nodes.is_empty());
+        while stack_size > 0 {
+            stack_size -= 1;
+            let node_index = stack[stack_size];
+            let node = &self.bvh.nodes[node_index];
+            statistics.bvh_nodes_visited += 1;
+
+            let closest_distance = closest.map_or(f32::INFINITY, |(_, t)| t);
+            if !ray.hit_aabb(&node.bounds, closest_distance) {
+                continue;
+            }
+
+            if node.is_leaf() {
+                let first = node.left_first as usize;
+                for &object_index in &self.bvh.object_indices[first..first + node.count as usize] {
+                    let object = &scene.objects[object_index as usize];
+                    statistics.intersection_tests += 1;
+                    if let Some(t) = ray.hit(object) {
+                        if t < closest.map_or(f32::INFINITY, |(_, t)| t) {
+                            closest = Some((object, t));
+                        }
+                    }
+                }
+            } else {
+                stack[stack_size] = node.left_first as usize;
+                stack[stack_size + 1] = node_index + 1;
+                stack_size += 2;
+            }
+        }
+
+        closest
+            .and_then(|(object, t)| self.closest_hit(ray, t, object))
             .or_else(|| self.miss(ray, scene))
     }
 
@@ -397,7 +723,7 @@ impl CpuRenderer {
         None
     }
 
-    fn frame_buffer(&mut self, scene: &Scene) -> Rgba32FImage {
+    fn take_frame_buffer(&mut self, scene: &Scene) -> Rgba32FImage {
         self.frame_buffer
             .take()
             .map(|frame_buffer| {
@@ -417,8 +743,195 @@ impl CpuRenderer {
     }
 
     fn blank_frame_buffer(&mut self, scene: &Scene) -> Rgba32FImage {
-        let mut frame_buffer = self.frame_buffer(scene);
+        let mut frame_buffer = self.take_frame_buffer(scene);
         frame_buffer.fill(0.0);
         frame_buffer
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Point3, Vector3};
+
+    use crate::{
+        renderer::RendererConfig,
+        scene::{
+            material::Material,
+            objects::{Cube, Geometry, Object, Sphere},
+            Scene,
+        },
+    };
+
+    use super::{CpuRenderer, Ray, Tile};
+
+    const EPSILON: f32 = 1e-5;
+
+    fn ray(origin: [f32; 3], direction: [f32; 3]) -> Ray {
+        Ray {
+            origin: Point3::from(origin),
+            direction: Vector3::from(direction),
+        }
+    }
+
+    fn unit_sphere() -> Sphere {
+        Sphere {
+            center: Point3::new(0.0, 0.0, 0.0),
+            radius: 1.0,
+        }
+    }
+
+    fn unit_cube() -> Cube {
+        Cube {
+            center: Point3::new(0.0, 0.0, 0.0),
+            side_length: 2.0,
+        }
+    }
+
+    fn assert_hit(hit: Option<f32>, expected: f32) {
+        match hit {
+            Some(t) => assert!(
+                (t - expected).abs() < EPSILON,
+                "expected {expected}, got {t}"
+            ),
+            None => panic!("expected a hit at {expected}, got a miss"),
+        }
+    }
+
+    #[test]
+    fn hit_sphere_returns_the_nearest_intersection() {
+        assert_hit(
+            ray([0.0, 0.0, -5.0], [0.0, 0.0, 1.0]).hit_sphere(&unit_sphere()),
+            4.0,
+        );
+    }
+
+    #[test]
+    fn hit_sphere_is_measured_in_units_of_the_direction() {
+        assert_hit(
+            ray([0.0, 0.0, -5.0], [0.0, 0.0, 2.0]).hit_sphere(&unit_sphere()),
+            2.0,
+        );
+    }
+
+    #[test]
+    fn hit_sphere_from_inside_returns_the_exit_point() {
+        assert_hit(
+            ray([0.0, 0.0, 0.0], [1.0, 0.0, 0.0]).hit_sphere(&unit_sphere()),
+            1.0,
+        );
+    }
+
+    #[test]
+    fn hit_sphere_grazing_ray_touches_the_surface() {
+        assert_hit(
+            ray([1.0, 0.0, -5.0], [0.0, 0.0, 1.0]).hit_sphere(&unit_sphere()),
+            5.0,
+        );
+    }
+
+    #[test]
+    fn hit_sphere_misses() {
+        assert_eq!(
+            ray([0.0, 2.0, -5.0], [0.0, 0.0, 1.0]).hit_sphere(&unit_sphere()),
+            None
+        );
+    }
+
+    #[test]
+    fn hit_sphere_ignores_spheres_behind_the_ray() {
+        assert_eq!(
+            ray([0.0, 0.0, 5.0], [0.0, 0.0, 1.0]).hit_sphere(&unit_sphere()),
+            None
+        );
+    }
+
+    #[test]
+    fn hit_cube_returns_the_nearest_intersection() {
+        assert_hit(
+            ray([0.0, 0.0, -5.0], [0.0, 0.0, 1.0]).hit_cube(&unit_cube()),
+            4.0,
+        );
+    }
+
+    #[test]
+    fn hit_cube_handles_diagonal_rays() {
+        let hit = ray([-5.0, -5.0, 0.5], [1.0, 1.0, 0.0]).hit_cube(&unit_cube());
+        assert_hit(hit, 4.0);
+    }
+
+    #[test]
+    fn hit_cube_from_inside_returns_the_exit_point() {
+        assert_hit(
+            ray([0.5, 0.0, 0.0], [1.0, 0.0, 0.0]).hit_cube(&unit_cube()),
+            0.5,
+        );
+    }
+
+    #[test]
+    fn hit_cube_misses() {
+        assert_eq!(
+            ray([0.0, 2.0, -5.0], [0.0, 0.0, 1.0]).hit_cube(&unit_cube()),
+            None
+        );
+        assert_eq!(
+            ray([-5.0, 0.0, 0.0], [1.0, 0.0, 5.0]).hit_cube(&unit_cube()),
+            None
+        );
+    }
+
+    #[test]
+    fn hit_cube_ignores_cubes_behind_the_ray() {
+        assert_eq!(
+            ray([0.0, 0.0, 5.0], [0.0, 0.0, 1.0]).hit_cube(&unit_cube()),
+            None
+        );
+    }
+
+    #[test]
+    fn hit_dispatches_on_the_geometry() {
+        let ray = ray([0.0, 0.0, -5.0], [0.0, 0.0, 1.0]);
+        let sphere = Object {
+            geometry: Geometry::Sphere(Sphere {
+                center: Point3::new(0.0, 0.0, 0.0),
+                radius: 2.0,
+            }),
+            material: Material::default(),
+        };
+        let cube = Object {
+            geometry: Geometry::Cube(Cube {
+                center: Point3::new(0.0, 0.0, 0.0),
+                side_length: 1.0,
+            }),
+            material: Material::default(),
+        };
+
+        assert_hit(ray.hit(&sphere), 3.0);
+        assert_hit(ray.hit(&cube), 4.5);
+    }
+
+    #[test]
+    fn tiles_are_rendered_with_the_current_geometry() {
+        let mut scene = Scene::default();
+        let tile = Tile {
+            x: 420,
+            y: 236,
+            width: 8,
+            height: 8,
+        };
+        let mut renderer = CpuRenderer::new(RendererConfig::default());
+        let before = renderer.render_tile(&scene, tile, 0..1);
+
+        // Moving the sphere out of view keeps the number of objects the same
+        let Geometry::Sphere(sphere) = &mut scene.objects[0].geometry else {
+            panic!("the default scene starts with a sphere");
+        };
+        sphere.center.y += 100.0;
+        let after = renderer.render_tile(&scene, tile, 0..1);
+
+        assert_ne!(before, after);
+        assert_eq!(
+            after,
+            CpuRenderer::new(RendererConfig::default()).render_tile(&scene, tile, 0..1)
+        );
+    }
+}
@@ -0,0 +1,724 @@
+//! Rendering a frame on several machines.
+//!
+//! Workers started with `raydar serve-worker` listen for TCP connections from a
+//! [`Coordinator`]. The coordinator sends every worker the scene and the renderer configuration
+//! once, then assigns it tiles of the frame together with a range of their samples to render.
+//! The worker returns the sums of these samples, see [`CpuRenderer::render_tile`], and the
+//! coordinator adds up the sums of all ranges of a tile, so the assembled frame buffer is the
+//! same as if the frame was rendered on one machine, up to rounding. Assignments of workers
+//! that disconnect, fail or time out are handed to the remaining ones.
+//!
+//! Every message is a little-endian `u32` length followed by that many bytes. Requests are
+//! JSON, responses start with a status byte: `0` for success, followed by the red, green, blue
+//! and alpha sums of the pixels of the tile as little-endian `f32`s row by row, or `1` for
+//! failure, followed by an UTF-8 error message. While a worker renders an assignment, it sends
+//! a response with just the status byte `2` about every [`HEARTBEAT_INTERVAL`], so that slow
+//! assignments are not mistaken for lost workers.
+
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fmt,
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    ops::{ControlFlow, Range},
+    sync::{
+        mpsc::{self, Sender},
+        Condvar, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use image::Rgba32FImage;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    cpu::{CpuRenderer, Tile},
+    RendererConfig,
+};
+use crate::scene::Scene;
+
+/// The width and height of the tiles a frame is split into, in pixels. Larger than the tiles
+/// of the CPU renderer to keep the overhead of the messages low.
+pub const TILE_SIZE: u32 = 128;
+
+/// The most samples of a tile rendered by one assignment. Renders with more samples are split
+/// into several assignments per tile, so that they spread over all workers.
+pub const SAMPLES_PER_ASSIGNMENT: u32 = 64;
+
+/// Requests larger than this are rejected as corrupt. The largest request is the one with the
+/// scene.
+const MAX_REQUEST_SIZE: u32 = 16 << 20;
+/// Responses larger than this are rejected as corrupt. The largest valid response is the
+/// status byte followed by the sums of a whole tile.
+const MAX_RESPONSE_SIZE: u32 = 1 + TILE_SIZE * TILE_SIZE * 16;
+
+const STATUS_OK: u8 = 0;
+const STATUS_ERROR: u8 = 1;
+const STATUS_RENDERING: u8 = 2;
+
+/// How often a worker reports that it is still rendering an assignment. The timeout of the
+/// coordinator must be longer than this.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A request of the coordinator to a worker.
+#[derive(Serialize, Deserialize, Debug)]
+enum Request {
+    /// Render the following assignments of this scene
+    Scene {
+        /// The scene file
+        scene: String,
+        config: RendererConfig,
+    },
+    /// Render the samples of a tile
+    Render(Assignment),
+}
+
+/// A part of the frame assigned to a worker.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Assignment {
+    pub tile: Tile,
+    /// Indices of the samples to render
+    pub samples: Range<u32>,
+}
+
+/// Error of a distributed render.
+#[derive(Debug)]
+pub enum DistributedError {
+    /// Every worker failed before the frame was complete.
+    NoWorkersLeft {
+        /// The error of the last worker that failed, `None` if no workers were given
+        last_error: Option<io::Error>,
+    },
+    /// The progress callback cancelled the render.
+    Cancelled,
+}
+
+impl fmt::Display for DistributedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoWorkersLeft {
+                last_error: Some(err),
+            } => write!(f, "all workers failed, the last one with: {err}"),
+            Self::NoWorkersLeft { last_error: None } => write!(f, "no workers to render on"),
+            Self::Cancelled => write!(f, "the render was cancelled"),
+        }
+    }
+}
+
+impl Error for DistributedError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::NoWorkersLeft {
+                last_error: Some(err),
+            } => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// How far a distributed render has progressed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TileProgress {
+    /// Assignments rendered so far
+    pub assignments: usize,
+    /// Assignments the frame is split into
+    pub total_assignments: usize,
+    /// Workers that have not failed yet
+    pub workers: usize,
+    /// Time since the render was started
+    pub elapsed: Duration,
+}
+
+impl fmt::Display for TileProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} assignments ({:.0}%), {} workers, {:.1}s elapsed",
+            self.assignments,
+            self.total_assignments,
+            self.assignments as f64 / self.total_assignments.max(1) as f64 * 100.0,
+            self.workers,
+            self.elapsed.as_secs_f64()
+        )
+    }
+}
+
+/// Accepts connections of coordinators until accepting one fails.
+///
+/// Every connection is served on a thread of its own, with its own renderer.
+pub fn serve_worker(listener: &TcpListener) -> io::Result<()> {
+    for stream in listener.incoming() {
+        let stream = stream?;
+        thread::spawn(move || {
+            let peer = stream
+                .peer_addr()
+                .map_or_else(|_| "unknown".to_string(), |address| address.to_string());
+            if let Err(err) = serve_coordinator(stream) {
+                eprintln!("Connection to coordinator {peer} failed: {err}");
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Answers the requests of a coordinator until it disconnects.
+fn serve_coordinator(mut stream: TcpStream) -> io::Result<()> {
+    let mut session = None;
+    loop {
+        let request = match read_message(&mut stream, MAX_REQUEST_SIZE) {
+            Ok(request) => request,
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(err) => return Err(err),
+        };
+
+        // A failed heartbeat also fails the response after it
+        let mut heartbeat = || {
+            let _ = write_message(&mut stream, &[STATUS_RENDERING], MAX_RESPONSE_SIZE);
+        };
+        let response = serde_json::from_slice(&request)
+            .map_err(|err| format!("invalid request: {err}"))
+            .and_then(|request| handle_request(&mut session, request, &mut heartbeat));
+        let response = match response {
+            Ok(payload) => [vec![STATUS_OK], payload].concat(),
+            Err(message) => [vec![STATUS_ERROR], message.into_bytes()].concat(),
+        };
+        write_message(&mut stream, &response, MAX_RESPONSE_SIZE)?;
+    }
+}
+
+/// Answers a request, calling `heartbeat` every [`HEARTBEAT_INTERVAL`] while rendering.
+fn handle_request(
+    session: &mut Option<(Scene, CpuRenderer)>,
+    request: Request,
+    heartbeat: &mut dyn FnMut(),
+) -> Result<Vec<u8>, String> {
+    match request {
+        Request::Scene { scene, config } => {
+            let scene = Scene::from_json(&scene).map_err(|err| format!("invalid scene: {err}"))?;
+            *session = Some((scene, CpuRenderer::new(config)));
+            Ok(Vec::new())
+        }
+        Request::Render(Assignment { tile, samples }) => {
+            let (scene, renderer) = session.as_mut().ok_or("no scene to render")?;
+            if tile.x.saturating_add(tile.width) > scene.camera.resolution_x()
+                || tile.y.saturating_add(tile.height) > scene.camera.resolution_y()
+            {
+                return Err(format!("{tile:?} is outside of the frame"));
+            }
+
+            // Rendering the samples one by one adds them up in the same order as rendering
+            // them at once
+            let mut buffer = Rgba32FImage::new(tile.width, tile.height);
+            let mut last_heartbeat = Instant::now();
+            for sample in samples {
+                let sample_buffer = renderer.render_tile(scene, tile, sample..sample + 1);
+                for (sum, value) in buffer.iter_mut().zip(sample_buffer.iter()) {
+                    *sum += value;
+                }
+                if last_heartbeat.elapsed() >= HEARTBEAT_INTERVAL {
+                    heartbeat();
+                    last_heartbeat = Instant::now();
+                }
+            }
+            Ok(buffer
+                .as_raw()
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect())
+        }
+    }
+}
+
+/// Reads a message, rejecting messages longer than `max_size` bytes before allocating them.
+fn read_message(stream: &mut impl Read, max_size: u32) -> io::Result<Vec<u8>> {
+    let mut length = [0; 4];
+    stream.read_exact(&mut length)?;
+    let length = u32::from_le_bytes(length);
+    if length > max_size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("message of {length} bytes is too large"),
+        ));
+    }
+
+    let mut message = vec![0; length as usize];
+    stream.read_exact(&mut message)?;
+    Ok(message)
+}
+
+fn write_message(stream: &mut impl Write, message: &[u8], max_size: u32) -> io::Result<()> {
+    let length = u32::try_from(message.len())
+        .ok()
+        .filter(|&length| length <= max_size)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "message of {} bytes is larger than the limit of {max_size} bytes",
+                    message.len()
+                ),
+            )
+        })?;
+    stream.write_all(&length.to_le_bytes())?;
+    stream.write_all(message)?;
+    stream.flush()
+}
+
+/// A connection of the coordinator to a worker.
+struct WorkerConnection {
+    stream: TcpStream,
+}
+
+impl WorkerConnection {
+    /// Connects to a worker and sends it the scene.
+    fn open(
+        address: &str,
+        timeout: Duration,
+        scene: &str,
+        config: &RendererConfig,
+    ) -> io::Result<Self> {
+        let socket_address = address.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("cannot resolve {address}"))
+        })?;
+        let stream = TcpStream::connect_timeout(&socket_address, timeout)?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+
+        let mut connection = Self { stream };
+        connection.request(&Request::Scene {
+            scene: scene.to_string(),
+            config: config.clone(),
+        })?;
+        Ok(connection)
+    }
+
+    /// Sends a request and waits for its response. The read timeout of the stream applies to
+    /// every heartbeat, not to the whole request.
+    fn request(&mut self, request: &Request) -> io::Result<Vec<u8>> {
+        write_message(
+            &mut self.stream,
+            &serde_json::to_vec(request)?,
+            MAX_REQUEST_SIZE,
+        )?;
+        let response = loop {
+            let response = read_message(&mut self.stream, MAX_RESPONSE_SIZE)?;
+            if response != [STATUS_RENDERING] {
+                break response;
+            }
+        };
+        match response.split_first() {
+            Some((&STATUS_OK, payload)) => Ok(payload.to_vec()),
+            Some((&STATUS_ERROR, message)) => Err(io::Error::other(
+                String::from_utf8_lossy(message).into_owned(),
+            )),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid response status",
+            )),
+        }
+    }
+
+    fn render(&mut self, assignment: &Assignment) -> io::Result<Rgba32FImage> {
+        let payload = self.request(&Request::Render(assignment.clone()))?;
+
+        let Tile { width, height, .. } = assignment.tile;
+        if payload.len() as u64 != u64::from(width) * u64::from(height) * 16 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "tile data does not match the size of the tile",
+            ));
+        }
+        let values = payload
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect();
+        Ok(Rgba32FImage::from_raw(width, height, values).unwrap())
+    }
+}
+
+/// Something that happened on the thread of a worker.
+enum Event {
+    Rendered(Assignment, Rgba32FImage),
+    WorkerLost { address: String, error: io::Error },
+}
+
+/// Splits a frame into tiles of [`TILE_SIZE`] pixels and their samples into ranges of at most
+/// [`SAMPLES_PER_ASSIGNMENT`].
+fn split_assignments(width: u32, height: u32, sample_count: u32) -> VecDeque<Assignment> {
+    Tile::split_frame(width, height, TILE_SIZE)
+        .flat_map(|tile| {
+            (0..sample_count)
+                .step_by(SAMPLES_PER_ASSIGNMENT as usize)
+                .map(move |start| Assignment {
+                    tile,
+                    samples: start..sample_count.min(start + SAMPLES_PER_ASSIGNMENT),
+                })
+        })
+        .collect()
+}
+
+/// The assignments that are left to render, shared by the threads of the workers.
+struct AssignmentQueue {
+    /// `None` once the render is finished or cancelled
+    assignments: Mutex<Option<VecDeque<Assignment>>>,
+    changed: Condvar,
+}
+
+impl AssignmentQueue {
+    fn new(assignments: VecDeque<Assignment>) -> Self {
+        Self {
+            assignments: Mutex::new(Some(assignments)),
+            changed: Condvar::new(),
+        }
+    }
+
+    /// Waits for the next assignment, or returns `None` once the render is finished.
+    fn next(&self) -> Option<Assignment> {
+        let mut assignments = self.assignments.lock().unwrap();
+        loop {
+            if let Some(assignment) = assignments.as_mut()?.pop_front() {
+                return Some(assignment);
+            }
+            // Assignments of lost workers may still come back
+            assignments = self.changed.wait(assignments).unwrap();
+        }
+    }
+
+    /// Returns the assignment of a lost worker to the queue.
+    fn put_back(&self, assignment: Assignment) {
+        if let Some(assignments) = self.assignments.lock().unwrap().as_mut() {
+            assignments.push_back(assignment);
+        }
+        self.changed.notify_one();
+    }
+
+    /// Ends the render, workers finish their current assignment and take no more.
+    fn finish(&self) {
+        *self.assignments.lock().unwrap() = None;
+        self.changed.notify_all();
+    }
+}
+
+/// Splits frames into tiles and renders them on workers.
+pub struct Coordinator {
+    /// Addresses of the workers, such as `render-box:7878`
+    workers: Vec<String>,
+    /// Time to wait for a worker to connect or to return a tile before it is considered lost
+    timeout: Duration,
+}
+
+impl Coordinator {
+    pub fn new(workers: Vec<String>, timeout: Duration) -> Self {
+        Self { workers, timeout }
+    }
+
+    /// Renders all samples of a frame on the workers and returns their sum, like
+    /// [`Renderer::frame_buffer`](super::Renderer::frame_buffer).
+    ///
+    /// `on_progress` is called after every rendered assignment. When it returns
+    /// [`ControlFlow::Break`] the render is cancelled once the workers finished their current
+    /// assignments.
+    pub fn render(
+        &self,
+        scene: &Scene,
+        config: &RendererConfig,
+        on_progress: &mut dyn FnMut(TileProgress) -> ControlFlow<()>,
+    ) -> Result<Rgba32FImage, DistributedError> {
+        let start = Instant::now();
+        let scene_file = scene.to_json().expect("scenes can always be serialized");
+
+        let (width, height) = (scene.camera.resolution_x(), scene.camera.resolution_y());
+        let assignments = split_assignments(width, height, config.max_sample_count);
+        let total_assignments = assignments.len();
+        let queue = AssignmentQueue::new(assignments);
+
+        thread::scope(|scope| {
+            let (sender, receiver) = mpsc::channel();
+            for address in &self.workers {
+                let sender = sender.clone();
+                let (scene_file, queue) = (&scene_file, &queue);
+                scope.spawn(move || {
+                    let result = self.run_worker(address, scene_file, config, queue, &sender);
+                    if let Err(error) = result {
+                        let address = address.clone();
+                        // The render is over if nobody receives the event anymore
+                        let _ = sender.send(Event::WorkerLost { address, error });
+                    }
+                });
+            }
+            drop(sender);
+
+            let mut frame_buffer = Rgba32FImage::new(width, height);
+            let mut assignments = 0;
+            let mut workers = self.workers.len();
+            let mut last_error = None;
+            while assignments < total_assignments {
+                // Receiving fails once all worker threads have ended
+                let Ok(event) = receiver.recv() else {
+                    break;
+                };
+
+                match event {
+                    Event::Rendered(assignment, buffer) => {
+                        let Tile { x, y, .. } = assignment.tile;
+                        for (tile_x, tile_y, rendered) in buffer.enumerate_pixels() {
+                            let pixel = frame_buffer.get_pixel_mut(x + tile_x, y + tile_y);
+                            for (sum, value) in pixel.0.iter_mut().zip(rendered.0) {
+                                *sum += value;
+                            }
+                        }
+                        assignments += 1;
+                    }
+                    Event::WorkerLost { address, error } => {
+                        eprintln!("Lost worker {address}: {error}");
+                        workers -= 1;
+                        last_error = Some(error);
+                    }
+                }
+
+                let progress = TileProgress {
+                    assignments,
+                    total_assignments,
+                    workers,
+                    elapsed: start.elapsed(),
+                };
+                if on_progress(progress).is_break() {
+                    queue.finish();
+                    return Err(DistributedError::Cancelled);
+                }
+            }
+            queue.finish();
+
+            if assignments < total_assignments {
+                Err(DistributedError::NoWorkersLeft { last_error })
+            } else {
+                Ok(frame_buffer)
+            }
+        })
+    }
+
+    /// Renders assignments from the queue on a worker until the frame is finished.
+    ///
+    /// If the worker fails, its current assignment is put back into the queue.
+    fn run_worker(
+        &self,
+        address: &str,
+        scene_file: &str,
+        config: &RendererConfig,
+        queue: &AssignmentQueue,
+        events: &Sender<Event>,
+    ) -> io::Result<()> {
+        let mut connection = WorkerConnection::open(address, self.timeout, scene_file, config)?;
+        while let Some(assignment) = queue.next() {
+            match connection.render(&assignment) {
+                Ok(buffer) => {
+                    let _ = events.send(Event::Rendered(assignment, buffer));
+                }
+                Err(err) => {
+                    queue.put_back(assignment);
+                    return Err(err);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        net::TcpListener,
+        ops::ControlFlow,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::{
+        read_message, serve_worker, split_assignments, write_message, Assignment, Coordinator,
+        DistributedError, Request, MAX_REQUEST_SIZE, MAX_RESPONSE_SIZE, STATUS_OK,
+        STATUS_RENDERING, TILE_SIZE,
+    };
+    use crate::{
+        renderer::{
+            cpu::{CpuRenderer, Tile},
+            Renderer, RendererConfig,
+        },
+        scene::{render_settings::Resolution, Scene},
+    };
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    fn config() -> RendererConfig {
+        RendererConfig {
+            max_sample_count: 2,
+            seed: 5,
+            ..Default::default()
+        }
+    }
+
+    /// A scene with partial tiles at the right and bottom edges.
+    fn scene() -> Scene {
+        let mut scene = Scene::default();
+        scene.render_settings.resolution = Resolution::new(200, 150);
+        scene.apply_resolution();
+        scene
+    }
+
+    fn spawn_worker() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || serve_worker(&listener));
+        address
+    }
+
+    /// A worker that accepts the scene, then disconnects when it is asked to render.
+    fn spawn_failing_worker() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                read_message(&mut stream, MAX_REQUEST_SIZE).unwrap();
+                write_message(&mut stream, &[0], MAX_RESPONSE_SIZE).unwrap();
+                let _ = read_message(&mut stream, MAX_REQUEST_SIZE);
+            }
+        });
+        address
+    }
+
+    /// A worker that answers every assignment with black pixels after sending heartbeats for
+    /// longer than the timeout of the coordinator.
+    fn spawn_slow_worker(heartbeats: u32, interval: Duration) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                read_message(&mut stream, MAX_REQUEST_SIZE).unwrap();
+                write_message(&mut stream, &[STATUS_OK], MAX_RESPONSE_SIZE).unwrap();
+                while let Ok(request) = read_message(&mut stream, MAX_REQUEST_SIZE) {
+                    let Ok(Request::Render(Assignment { tile, .. })) =
+                        serde_json::from_slice(&request)
+                    else {
+                        break;
+                    };
+                    for _ in 0..heartbeats {
+                        thread::sleep(interval);
+                        write_message(&mut stream, &[STATUS_RENDERING], MAX_RESPONSE_SIZE).unwrap();
+                    }
+                    let mut response = vec![0; 1 + (tile.width * tile.height * 16) as usize];
+                    response[0] = STATUS_OK;
+                    write_message(&mut stream, &response, MAX_RESPONSE_SIZE).unwrap();
+                }
+            }
+        });
+        address
+    }
+
+    fn unreachable_address() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().to_string()
+    }
+
+    fn local_render(scene: &Scene) -> image::Rgba32FImage {
+        let mut renderer = CpuRenderer::new(config());
+        renderer.render_frame(scene);
+        renderer.frame_buffer().unwrap().clone()
+    }
+
+    #[test]
+    fn samples_of_tiles_are_split_into_ranges() {
+        let assignments = split_assignments(200, 150, 150);
+
+        assert_eq!(assignments.len(), 4 * 3);
+        for tile in Tile::split_frame(200, 150, TILE_SIZE) {
+            let ranges: Vec<_> = assignments
+                .iter()
+                .filter(|assignment| assignment.tile == tile)
+                .map(|assignment| assignment.samples.clone())
+                .collect();
+            assert_eq!(ranges, [0..64, 64..128, 128..150]);
+        }
+    }
+
+    #[test]
+    fn oversized_messages_are_rejected_before_reading_them() {
+        let message = (MAX_RESPONSE_SIZE + 1).to_le_bytes();
+
+        let err = read_message(&mut message.as_slice(), MAX_RESPONSE_SIZE).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn distributed_renders_match_local_ones() {
+        let scene = scene();
+        let coordinator = Coordinator::new(
+            vec![spawn_worker(), spawn_worker(), spawn_worker()],
+            TIMEOUT,
+        );
+
+        let frame_buffer = coordinator
+            .render(&scene, &config(), &mut |_| ControlFlow::Continue(()))
+            .unwrap();
+
+        assert_eq!(frame_buffer, local_render(&scene));
+    }
+
+    #[test]
+    fn tiles_of_lost_workers_are_rendered_by_the_others() {
+        let scene = scene();
+        let coordinator = Coordinator::new(
+            vec![
+                unreachable_address(),
+                spawn_failing_worker(),
+                spawn_worker(),
+            ],
+            TIMEOUT,
+        );
+
+        let frame_buffer = coordinator
+            .render(&scene, &config(), &mut |_| ControlFlow::Continue(()))
+            .unwrap();
+
+        assert_eq!(frame_buffer, local_render(&scene));
+    }
+
+    #[test]
+    fn workers_that_send_heartbeats_are_not_lost() {
+        let mut scene = Scene::default();
+        scene.render_settings.resolution = Resolution::new(4, 4);
+        scene.apply_resolution();
+        let timeout = Duration::from_millis(500);
+        let coordinator = Coordinator::new(
+            vec![spawn_slow_worker(4, Duration::from_millis(200))],
+            timeout,
+        );
+
+        let start = Instant::now();
+        let frame_buffer = coordinator
+            .render(&scene, &config(), &mut |_| ControlFlow::Continue(()))
+            .unwrap();
+
+        assert!(start.elapsed() > timeout);
+        assert_eq!(frame_buffer, image::Rgba32FImage::new(4, 4));
+    }
+
+    #[test]
+    fn render_fails_when_all_workers_are_lost() {
+        let coordinator =
+            Coordinator::new(vec![unreachable_address(), spawn_failing_worker()], TIMEOUT);
+
+        let start = Instant::now();
+        let result = coordinator.render(&scene(), &config(), &mut |_| ControlFlow::Continue(()));
+
+        assert!(matches!(
+            result,
+            Err(DistributedError::NoWorkersLeft {
+                last_error: Some(_)
+            })
+        ));
+        assert!(start.elapsed() < TIMEOUT);
+    }
+}
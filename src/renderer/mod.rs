@@ -1,35 +1,137 @@
-use image::RgbaImage;
+use std::{error::Error, ops::ControlFlow, time::Instant};
+
+use image::{Rgba32FImage, RgbaImage};
+use progress::Progress;
+use serde::{Deserialize, Serialize};
 use timing::Profiler;
 
-use crate::scene::Scene;
+use crate::scene::{
+    render_settings::{BounceLimits, Clamping, Filter, RenderSettings, Tonemapping},
+    Scene,
+};
 
 pub mod cpu;
 pub mod vulkan;
 
+pub mod benchmark;
+pub mod bvh;
+pub mod checkpoint;
+pub mod distributed;
+pub mod output;
+pub mod progress;
 pub mod timing;
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct RendererConfig {
     pub max_sample_count: u32,
-    pub max_bounces: u32,
+    pub bounces: BounceLimits,
+    pub clamping: Clamping,
+    /// Seed for the random number generators, renders with the same seed are reproducible
+    pub seed: u32,
+    /// Randomly terminate paths that carry little light, weighting the surviving ones so that
+    /// the result stays unbiased
+    pub russian_roulette: bool,
+    /// Number of bounces a path makes before Russian roulette may terminate it
+    pub russian_roulette_min_depth: u32,
+    pub filter: Filter,
+    pub tonemapping: Tonemapping,
 }
 
 impl Default for RendererConfig {
     fn default() -> Self {
         Self {
             max_sample_count: 1024,
-            max_bounces: 12,
+            bounces: BounceLimits::default(),
+            clamping: Clamping::default(),
+            seed: 0,
+            russian_roulette: true,
+            russian_roulette_min_depth: 3,
+            filter: Filter::default(),
+            tonemapping: Tonemapping::default(),
+        }
+    }
+}
+
+impl From<&RenderSettings> for RendererConfig {
+    fn from(settings: &RenderSettings) -> Self {
+        Self {
+            max_sample_count: settings.samples,
+            bounces: settings.bounces,
+            clamping: settings.clamping,
+            seed: settings.seed,
+            russian_roulette: settings.russian_roulette,
+            russian_roulette_min_depth: settings.russian_roulette_min_depth,
+            filter: settings.filter,
+            tonemapping: settings.tonemapping,
         }
     }
 }
 
 pub trait Renderer {
     fn render_frame(&mut self, scene: &Scene) -> RgbaImage;
+    /// Renders a frame like [`Renderer::render_frame`], calling `on_progress` after every
+    /// rendered sample.
+    ///
+    /// Renderers that trace several samples at once report progress only after each batch.
+    /// When the callback returns [`ControlFlow::Break`] the render stops and the image
+    /// accumulated so far is returned.
+    fn render_frame_with_progress(
+        &mut self,
+        scene: &Scene,
+        on_progress: &mut dyn FnMut(Progress) -> ControlFlow<()>,
+    ) -> RgbaImage {
+        let start = Instant::now();
+        self.new_frame(scene);
+
+        let mut rendered_frame = None;
+        while let Some(frame) = self.render_sample(scene) {
+            rendered_frame = Some(frame);
+            let progress = Progress {
+                samples: self.sample_count(),
+                total_samples: self.config().max_sample_count,
+                elapsed: start.elapsed(),
+            };
+            if on_progress(progress).is_break() {
+                break;
+            }
+        }
+
+        rendered_frame.unwrap_or_else(|| {
+            RgbaImage::new(scene.camera.resolution_x(), scene.camera.resolution_y())
+        })
+    }
     fn render_sample(&mut self, scene: &Scene) -> Option<RgbaImage>;
     fn new_frame(&mut self, scene: &Scene);
+    /// Configures the renderer with the render settings stored in a scene, so that one
+    /// renderer can render several scenes.
+    fn apply_render_settings(&mut self, settings: &RenderSettings) {
+        *self.config_mut() = settings.into();
+    }
+    /// Returns the sum of all samples accumulated for the current frame.
+    fn frame_buffer(&self) -> Option<&Rgba32FImage>;
     fn profiler(&self) -> &Profiler;
     fn sample_count(&self) -> u32;
-    fn max_sample_count(&self) -> u32;
-    fn max_bounces(&self) -> u32;
-    fn set_max_sample_count(&mut self, count: u32);
-    fn set_max_bounces(&mut self, bounces: u32);
+    fn config(&self) -> &RendererConfig;
+    /// Changes to the configuration take effect with the next frame.
+    fn config_mut(&mut self) -> &mut RendererConfig;
+    /// Returns the error that stopped the current frame before all samples were rendered.
+    fn last_error(&self) -> Option<&dyn Error> {
+        None
+    }
+    fn name(&self) -> &'static str;
+}
+
+/// Describes a render that stopped before all samples were rendered, including the error that
+/// stopped it if the renderer reported one.
+pub fn describe_incomplete_render(renderer: &dyn Renderer) -> String {
+    let message = format!(
+        "{} renderer stopped after {} of {} samples",
+        renderer.name(),
+        renderer.sample_count(),
+        renderer.config().max_sample_count
+    );
+    match renderer.last_error() {
+        Some(err) => format!("{message}: {err}"),
+        None => message,
+    }
 }
@@ -0,0 +1,182 @@
+//! Post-processing of accumulated radiance shared by all renderer backends.
+//!
+//! Every backend accumulates the sum of its samples in a 32-bit float frame buffer.
+//! The functions in this module turn that buffer into the final image, so that the
+//! CPU and GPU backends produce directly comparable output.
+
+use std::{fs::File, io::BufWriter, path::Path};
+
+use image::{
+    error::EncodingError, ImageBuffer, ImageError, ImageFormat, ImageResult, Rgba, Rgba32FImage,
+    RgbaImage,
+};
+
+use crate::scene::render_settings::Tonemapping;
+
+/// Averages an accumulated frame buffer over the number of samples taken.
+///
+/// The result is the linear, high dynamic range estimate of the radiance for each pixel.
+pub fn resolve(frame_buffer: &Rgba32FImage, sample_count: u32) -> Rgba32FImage {
+    let sample_count = sample_count.max(1) as f32;
+    ImageBuffer::from_fn(frame_buffer.width(), frame_buffer.height(), |x, y| {
+        let pixel = frame_buffer.get_pixel(x, y);
+        Rgba(pixel.0.map(|channel| channel / sample_count))
+    })
+}
+
+/// Converts an accumulated frame buffer into a displayable 8-bit image.
+#[tracing::instrument(skip_all)]
+pub fn display_transform(
+    frame_buffer: &Rgba32FImage,
+    sample_count: u32,
+    tonemapping: Tonemapping,
+) -> RgbaImage {
+    let radiance = resolve(frame_buffer, sample_count);
+    ImageBuffer::from_fn(radiance.width(), radiance.height(), |x, y| {
+        let [r, g, b, a] = radiance.get_pixel(x, y).0;
+        Rgba([
+            to_display_value(tonemap(r, tonemapping)),
+            to_display_value(tonemap(g, tonemapping)),
+            to_display_value(tonemap(b, tonemapping)),
+            to_display_value(a),
+        ])
+    })
+}
+
+/// Maps a linear channel value of the radiance to the displayable range.
+pub fn tonemap(value: f32, tonemapping: Tonemapping) -> f32 {
+    let value = value.max(0.0);
+    match tonemapping {
+        Tonemapping::None => value,
+        Tonemapping::Reinhard => value / (1.0 + value),
+        // Fit of the ACES curve by Krzysztof Narkowicz
+        Tonemapping::Aces => {
+            ((value * (2.51 * value + 0.03)) / (value * (2.43 * value + 0.59) + 0.14)).min(1.0)
+        }
+    }
+}
+
+/// Maps a linear channel value to its 8-bit display value.
+fn to_display_value(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0) as u8
+}
+
+/// Information about a render that is stored in the saved image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageMetadata {
+    /// Name and version of the program that rendered the image
+    pub software: String,
+    /// Samples per pixel the image was actually rendered with
+    pub samples: u32,
+}
+
+impl ImageMetadata {
+    /// Text chunk keyword of the samples per pixel.
+    pub const SAMPLES_KEYWORD: &'static str = "Samples";
+    /// Text chunk keyword of the rendering software, as registered in the PNG specification.
+    pub const SOFTWARE_KEYWORD: &'static str = "Software";
+
+    /// Metadata of an image rendered by this version of Raydar.
+    pub fn new(samples: u32) -> Self {
+        Self {
+            software: format!("Raydar v{}", env!("CARGO_PKG_VERSION")),
+            samples,
+        }
+    }
+}
+
+/// Saves a rendered image in the format given by the extension of `path`.
+///
+/// PNG files store the metadata in text chunks, other formats are saved without it.
+pub fn save_image(image: &RgbaImage, path: &Path, metadata: &ImageMetadata) -> ImageResult<()> {
+    if ImageFormat::from_path(path).ok() != Some(ImageFormat::Png) {
+        return image.save(path);
+    }
+
+    let encoding_error = |err: png::EncodingError| {
+        ImageError::Encoding(EncodingError::new(ImageFormat::Png.into(), err))
+    };
+
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, image.width(), image.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    for (keyword, text) in [
+        (ImageMetadata::SOFTWARE_KEYWORD, metadata.software.clone()),
+        (ImageMetadata::SAMPLES_KEYWORD, metadata.samples.to_string()),
+    ] {
+        encoder
+            .add_text_chunk(keyword.to_string(), text)
+            .map_err(encoding_error)?;
+    }
+
+    let mut writer = encoder.write_header().map_err(encoding_error)?;
+    writer
+        .write_image_data(image.as_raw())
+        .map_err(encoding_error)?;
+    writer.finish().map_err(encoding_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs::File};
+
+    use image::{Rgba, RgbaImage};
+
+    use super::{save_image, tonemap, ImageMetadata};
+    use crate::scene::render_settings::Tonemapping;
+
+    #[test]
+    fn png_files_store_the_metadata() {
+        let path = env::temp_dir().join(format!("raydar-metadata-{}.png", std::process::id()));
+        let image = RgbaImage::from_pixel(3, 2, Rgba([255, 128, 0, 255]));
+        let metadata = ImageMetadata {
+            software: "Raydar test".to_string(),
+            samples: 37,
+        };
+        save_image(&image, &path, &metadata).unwrap();
+
+        let reader = png::Decoder::new(File::open(&path).unwrap())
+            .read_info()
+            .unwrap();
+        let text: Vec<_> = reader
+            .info()
+            .uncompressed_latin1_text
+            .iter()
+            .map(|chunk| (chunk.keyword.as_str(), chunk.text.as_str()))
+            .collect();
+        assert_eq!(text, [("Software", "Raydar test"), ("Samples", "37")]);
+        assert_eq!(image::open(&path).unwrap().to_rgba8(), image);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn no_tonemapping_keeps_the_radiance() {
+        assert_eq!(tonemap(0.25, Tonemapping::None), 0.25);
+        assert_eq!(tonemap(4.0, Tonemapping::None), 4.0);
+        assert_eq!(tonemap(-1.0, Tonemapping::None), 0.0);
+    }
+
+    #[test]
+    fn tonemapping_operators_compress_highlights() {
+        for tonemapping in [Tonemapping::Reinhard, Tonemapping::Aces] {
+            let mut previous = tonemap(0.0, tonemapping);
+            assert!(
+                previous.abs() < 1e-6,
+                "{tonemapping:?} maps black to {previous}"
+            );
+            for step in 1..=1000 {
+                let value = tonemap(step as f32 * 0.1, tonemapping);
+                assert!(value >= previous, "{tonemapping:?} is not monotonic");
+                assert!(
+                    value <= 1.0,
+                    "{tonemapping:?} maps {} to {value}",
+                    step as f32 * 0.1
+                );
+                previous = value;
+            }
+        }
+        assert_eq!(tonemap(1.0, Tonemapping::Reinhard), 0.5);
+    }
+}
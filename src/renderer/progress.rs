@@ -0,0 +1,88 @@
+//! Progress of a frame that is rendered sample by sample.
+
+use std::{fmt, time::Duration};
+
+/// How far a frame has been rendered, passed to the callback of
+/// [`Renderer::render_frame_with_progress`](super::Renderer::render_frame_with_progress).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    /// Samples accumulated so far
+    pub samples: u32,
+    /// Samples the frame is rendered with
+    pub total_samples: u32,
+    /// Time since the frame was started, including the preparation of the scene
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    /// The fraction of the samples that has been rendered, between 0 and 1.
+    pub fn fraction(&self) -> f32 {
+        if self.total_samples == 0 {
+            1.0
+        } else {
+            self.samples as f32 / self.total_samples as f32
+        }
+    }
+
+    /// The average number of samples rendered per second so far.
+    pub fn samples_per_second(&self) -> Option<f64> {
+        let seconds = self.elapsed.as_secs_f64();
+        (self.samples > 0 && seconds > 0.0).then(|| f64::from(self.samples) / seconds)
+    }
+
+    /// The estimated time until the remaining samples are rendered, assuming they take as long
+    /// as the ones rendered so far.
+    pub fn eta(&self) -> Option<Duration> {
+        let samples_per_second = self.samples_per_second()?;
+        let remaining = self.total_samples.saturating_sub(self.samples);
+        Some(Duration::from_secs_f64(
+            f64::from(remaining) / samples_per_second,
+        ))
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} samples ({:.0}%), {:.1}s elapsed",
+            self.samples,
+            self.total_samples,
+            self.fraction() * 100.0,
+            self.elapsed.as_secs_f64()
+        )?;
+        if let Some(eta) = self.eta() {
+            write!(f, ", ETA {:.1}s", eta.as_secs_f64())?;
+        }
+        if let Some(samples_per_second) = self.samples_per_second() {
+            write!(f, ", {samples_per_second:.1} samples/s")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Progress;
+
+    #[test]
+    fn eta_extrapolates_the_rendered_samples() {
+        let progress = Progress {
+            samples: 4,
+            total_samples: 16,
+            elapsed: Duration::from_secs(2),
+        };
+        assert_eq!(progress.fraction(), 0.25);
+        assert_eq!(progress.samples_per_second(), Some(2.0));
+        assert_eq!(progress.eta(), Some(Duration::from_secs(6)));
+
+        let started = Progress {
+            samples: 0,
+            ..progress
+        };
+        assert_eq!(started.eta(), None);
+        assert_eq!(started.to_string(), "0/16 samples (0%), 2.0s elapsed");
+    }
+}
@@ -1,7 +1,7 @@
 //! Time measurement utilities for profiling and benchmarking renderer operations.
 //!
 //! This module provides tools for measuring execution time of rendering operations,
-//! including frame timings and sample profiling.
+//! including frame timings and sample profiling, along with statistics about the traced rays and paths.
 
 use std::time::{Duration, Instant};
 
@@ -16,6 +16,8 @@ pub struct Profiler {
     pub(super) prepare_timer: Timer,
     /// The timer for measuring the time it takes to render the next sample
     pub(super) render_timer: Timer,
+    /// Statistics about the paths traced for the current frame
+    pub(super) path_statistics: PathStatistics,
 }
 
 impl Profiler {
@@ -38,6 +40,120 @@ impl Profiler {
     pub fn render_timer(&self) -> &Timer {
         &self.render_timer
     }
+
+    /// Returns a reference to the path statistics
+    pub fn path_statistics(&self) -> &PathStatistics {
+        &self.path_statistics
+    }
+
+    /// Returns the number of samples rendered per second, measured over the last sample.
+    pub fn samples_per_second(&self) -> Option<f64> {
+        self.sample_timer
+            .duration()
+            .filter(|duration| !duration.is_zero())
+            .map(|duration| 1.0 / duration.as_secs_f64())
+    }
+}
+
+/// The number of bins of [`PathStatistics::length_histogram`].
+pub const PATH_LENGTH_BINS: usize = 16;
+
+/// Statistics about the rays and paths traced for a frame.
+///
+/// The length of a path is the number of rays traced along it, so a camera ray that escapes
+/// into the world has a length of one.
+#[derive(Default, Clone, Copy)]
+pub struct PathStatistics {
+    /// The number of traced paths
+    pub(super) path_count: u64,
+    /// Rays leaving the camera, the first ray of every path
+    pub(super) primary_rays: u64,
+    /// Rays scattered off surfaces
+    pub(super) secondary_rays: u64,
+    /// Rays testing the visibility of light sources
+    pub(super) shadow_rays: u64,
+    /// Tests of rays against the objects of the scene
+    pub(super) intersection_tests: u64,
+    /// Nodes of the BVH visited while looking for intersections
+    pub(super) bvh_nodes_visited: u64,
+    /// Whether the renderer traverses the scene itself and counts intersection tests and
+    /// visited BVH nodes, which hardware ray tracing does not expose
+    pub(super) counts_traversal: bool,
+    /// The number of paths of each length, the last bin counts all longer paths
+    pub(super) length_histogram: [u64; PATH_LENGTH_BINS],
+}
+
+impl PathStatistics {
+    /// Records a path of `length` rays.
+    pub(super) fn record_path(&mut self, length: u32) {
+        self.path_count += 1;
+        self.primary_rays += u64::from(length.min(1));
+        self.secondary_rays += u64::from(length.saturating_sub(1));
+        self.length_histogram[(length as usize).min(PATH_LENGTH_BINS - 1)] += 1;
+    }
+
+    /// Adds the counts of `other` to these statistics.
+    pub(super) fn merge(&mut self, other: &PathStatistics) {
+        self.path_count += other.path_count;
+        self.primary_rays += other.primary_rays;
+        self.secondary_rays += other.secondary_rays;
+        self.shadow_rays += other.shadow_rays;
+        self.intersection_tests += other.intersection_tests;
+        self.bvh_nodes_visited += other.bvh_nodes_visited;
+        self.counts_traversal |= other.counts_traversal;
+        for (bin, other_bin) in self.length_histogram.iter_mut().zip(other.length_histogram) {
+            *bin += other_bin;
+        }
+    }
+
+    /// Returns the number of traced paths.
+    pub fn path_count(&self) -> u64 {
+        self.path_count
+    }
+
+    /// Returns the number of rays leaving the camera.
+    pub fn primary_rays(&self) -> u64 {
+        self.primary_rays
+    }
+
+    /// Returns the number of rays scattered off surfaces.
+    pub fn secondary_rays(&self) -> u64 {
+        self.secondary_rays
+    }
+
+    /// Returns the number of rays testing the visibility of light sources.
+    ///
+    /// Neither renderer samples light sources directly yet, so this is always zero.
+    pub fn shadow_rays(&self) -> u64 {
+        self.shadow_rays
+    }
+
+    /// Returns the number of rays of all kinds.
+    pub fn ray_count(&self) -> u64 {
+        self.primary_rays + self.secondary_rays + self.shadow_rays
+    }
+
+    /// Returns the number of ray-object intersection tests, if the renderer counts them.
+    pub fn intersection_tests(&self) -> Option<u64> {
+        self.counts_traversal.then_some(self.intersection_tests)
+    }
+
+    /// Returns the number of visited BVH nodes, if the renderer counts them.
+    pub fn bvh_nodes_visited(&self) -> Option<u64> {
+        self.counts_traversal.then_some(self.bvh_nodes_visited)
+    }
+
+    /// Returns the number of paths of each length, starting at zero. The last bin counts all
+    /// paths of at least `PATH_LENGTH_BINS - 1` rays.
+    pub fn length_histogram(&self) -> &[u64; PATH_LENGTH_BINS] {
+        &self.length_histogram
+    }
+
+    /// Returns the average length of the traced paths, if any paths have been traced.
+    pub fn average_length(&self) -> Option<f64> {
+        (self.path_count > 0)
+            .then(|| (self.primary_rays + self.secondary_rays) as f64 / self.path_count as f64)
+    }
 }
 
 /// A timer utility for measuring the duration of operations with manual start/stop control.
@@ -51,6 +167,7 @@ impl Profiler {
 /// # Example
 ///
 /// ```
+/// # use raydar::renderer::timing::Timer;
 /// let mut timer = Timer::default();
 /// timer.start();
 /// // ... perform some operation ...
@@ -125,3 +242,36 @@ impl Timer {
         self.duration
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{PathStatistics, PATH_LENGTH_BINS};
+
+    #[test]
+    fn path_statistics_count_rays_and_path_lengths() {
+        let mut statistics = PathStatistics::default();
+        statistics.record_path(1);
+        statistics.record_path(3);
+        statistics.record_path(0);
+
+        let mut other = PathStatistics::default();
+        other.record_path(40);
+        statistics.merge(&other);
+
+        assert_eq!(statistics.path_count(), 4);
+        assert_eq!(statistics.primary_rays(), 3);
+        assert_eq!(statistics.secondary_rays(), 2 + 39);
+        assert_eq!(statistics.ray_count(), 44);
+        assert_eq!(statistics.average_length(), Some(11.0));
+
+        let histogram = statistics.length_histogram();
+        assert_eq!(histogram[0], 1);
+        assert_eq!(histogram[1], 1);
+        assert_eq!(histogram[3], 1);
+        assert_eq!(histogram[PATH_LENGTH_BINS - 1], 1);
+        assert_eq!(histogram.iter().sum::<u64>(), 4);
+
+        // Only renderers that traverse the scene themselves count the traversal
+        assert_eq!(statistics.intersection_tests(), None);
+    }
+}
@@ -1,7 +1,7 @@
-use std::{iter, sync::Arc};
+use std::{array, convert::Infallible, error::Error, fmt, iter, str::FromStr, sync::Arc};
 
 use cgmath::{EuclideanSpace, Matrix4, Point3, SquareMatrix};
-use image::RgbaImage;
+use image::{Rgba32FImage, RgbaImage};
 use shaders::raygen;
 use vulkano::{
     acceleration_structure::{
@@ -15,8 +15,9 @@ use vulkano::{
     },
     buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, IndexBuffer, Subbuffer},
     command_buffer::{
-        allocator::StandardCommandBufferAllocator, AutoCommandBufferBuilder, CommandBufferUsage,
-        CopyImageToBufferInfo, PrimaryCommandBufferAbstract,
+        allocator::StandardCommandBufferAllocator, AutoCommandBufferBuilder,
+        CommandBufferExecError, CommandBufferUsage, CopyImageToBufferInfo,
+        PrimaryCommandBufferAbstract,
     },
     descriptor_set::{
         allocator::StandardDescriptorSetAllocator,
@@ -27,31 +28,42 @@ use vulkano::{
         DescriptorSet, WriteDescriptorSet,
     },
     device::{
-        physical::PhysicalDeviceType, Device, DeviceCreateInfo, DeviceExtensions, DeviceFeatures,
-        Queue, QueueCreateInfo, QueueFlags,
+        physical::{PhysicalDevice, PhysicalDeviceType},
+        Device, DeviceCreateInfo, DeviceExtensions, DeviceFeatures, Queue, QueueCreateInfo,
+        QueueFlags,
     },
     format::Format,
     image::{view::ImageView, Image, ImageCreateInfo, ImageUsage},
     instance::{Instance, InstanceCreateFlags, InstanceCreateInfo, InstanceExtensions},
-    memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator},
+    memory::{
+        allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator},
+        MemoryHeapFlags,
+    },
     padded::Padded,
     pipeline::{
+        compute::ComputePipelineCreateInfo,
         graphics::vertex_input,
-        layout::PipelineLayoutCreateInfo,
+        layout::{PipelineLayoutCreateInfo, PushConstantRange},
         ray_tracing::{
             RayTracingPipeline, RayTracingPipelineCreateInfo, RayTracingShaderGroupCreateInfo,
             ShaderBindingTable,
         },
-        PipelineBindPoint, PipelineLayout, PipelineShaderStageCreateInfo,
+        ComputePipeline, Pipeline, PipelineBindPoint, PipelineLayout,
+        PipelineShaderStageCreateInfo,
     },
-    shader::ShaderStages,
-    sync::{self, GpuFuture},
-    Packed24_8, Version, VulkanLibrary,
+    shader::{EntryPoint, ShaderModule, ShaderStages},
+    sync::{self, GpuFuture, HostAccessError},
+    LoadingError, Packed24_8, Validated, ValidationError, Version, VulkanError, VulkanLibrary,
 };
 
 use crate::scene::{objects::Geometry, world::World, Scene};
 
-use super::{timing::Profiler, Renderer, RendererConfig};
+use super::{
+    bvh::Bvh,
+    output,
+    timing::{PathStatistics, Profiler, PATH_LENGTH_BINS},
+    Renderer, RendererConfig,
+};
 
 pub struct VulkanRenderer {
     profiler: Profiler,
@@ -61,31 +73,77 @@ pub struct VulkanRenderer {
     instance: Arc<Instance>,
     device: Arc<Device>,
     queue: Arc<Queue>,
+    backend: Backend,
+
+    memory_allocator: Arc<StandardMemoryAllocator>,
+    command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
+    descriptor_set_allocator: Arc<StandardDescriptorSetAllocator>,
+
+    bound_scene: Option<BoundScene>,
+    frame_buffer: Option<Rgba32FImage>,
+    sample_count: u32,
+    /// The error that stopped the current frame
+    last_error: Option<VulkanRendererError>,
+}
+
+/// The local size of the compute shader in both dimensions, see `raytrace.comp`.
+const COMPUTE_WORKGROUP_SIZE: u32 = 8;
+
+/// The number of samples traced by one dispatch. Progress, time limits and interrupts are only
+/// checked between dispatches, while each dispatch reads back the whole image.
+const SAMPLES_PER_DISPATCH: u32 = 16;
+
+/// Indices of the counters of `PathStatistics` in `pathtrace.glsl`.
+const STATISTIC_PATHS: usize = 0;
+const STATISTIC_PRIMARY_RAYS: usize = 1;
+const STATISTIC_SECONDARY_RAYS: usize = 2;
+const STATISTIC_INTERSECTION_TESTS: usize = 3;
+const STATISTIC_BVH_NODES_VISITED: usize = 4;
+/// The first bin of the path length histogram
+const STATISTIC_PATH_LENGTHS: usize = 5;
+const STATISTIC_COUNT: usize = STATISTIC_PATH_LENGTHS + PATH_LENGTH_BINS;
+
+/// The way the selected device traces rays through the scene.
+enum Backend {
+    /// Hardware accelerated tracing through `VK_KHR_ray_tracing_pipeline`.
+    RayTracing(RayTracingBackend),
+    /// Traversal of a host-built BVH in a compute shader, for devices without ray tracing support.
+    Compute(ComputeBackend),
+}
+
+struct RayTracingBackend {
     pipeline: Arc<RayTracingPipeline>,
-    pipeline_layout: Arc<PipelineLayout>,
     shader_binding_table: ShaderBindingTable,
 
     cube_vertex_buffer: Subbuffer<[Vertex]>,
     cube_index_buffer: Subbuffer<[u32]>,
     cube_blas: Arc<AccelerationStructure>,
     sphere_blas: Arc<AccelerationStructure>,
+}
 
-    memory_allocator: Arc<StandardMemoryAllocator>,
-    command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
-    descriptor_set_allocator: Arc<StandardDescriptorSetAllocator>,
+struct ComputeBackend {
+    pipeline: Arc<ComputePipeline>,
+}
 
-    bound_scene: Option<BoundScene>,
-    sample_count: u32,
+impl Backend {
+    fn pipeline_layout(&self) -> &Arc<PipelineLayout> {
+        match self {
+            Backend::RayTracing(backend) => backend.pipeline.layout(),
+            Backend::Compute(backend) => backend.pipeline.layout(),
+        }
+    }
 }
 
 struct BoundScene {
+    /// Only present for the ray tracing backend
     #[allow(unused)]
-    tlas: Arc<AccelerationStructure>,
+    tlas: Option<Arc<AccelerationStructure>>,
     scene_descriptor_set: Arc<DescriptorSet>,
     image_descriptor_set: Arc<DescriptorSet>,
     image: Arc<Image>,
     image_view: Arc<ImageView>,
-    output_buffer: Subbuffer<[u8]>,
+    output_buffer: Subbuffer<[f32]>,
+    path_statistics_buffer: Subbuffer<shaders::raygen::PathStatistics>,
 }
 
 #[derive(BufferContents, vertex_input::Vertex)]
@@ -97,103 +155,386 @@ struct Vertex {
     normal: [f32; 3],
 }
 
+/// Errors that can occur while setting up or driving the Vulkan renderer.
+#[derive(Debug)]
+pub enum VulkanRendererError {
+    /// The Vulkan loader library could not be found or loaded.
+    NoLoader(LoadingError),
+    /// There is no Vulkan 1.3 device with a graphics and compute queue.
+    NoSuitableDevice,
+    /// No physical device matches the requested selector.
+    DeviceNotFound(DeviceSelector),
+    /// A compiled shader module does not contain a `main` entry point.
+    MissingEntryPoint,
+    /// A Vulkan operation failed.
+    Vulkan(Box<dyn Error + Send + Sync>),
+}
+
+impl fmt::Display for VulkanRendererError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoLoader(err) => write!(f, "cannot load the Vulkan library: {err}"),
+            Self::NoSuitableDevice => {
+                write!(f, "no Vulkan 1.3 device with graphics and compute support")
+            }
+            Self::DeviceNotFound(selector) => write!(f, "no Vulkan device matches {selector}"),
+            Self::MissingEntryPoint => write!(f, "shader module has no `main` entry point"),
+            Self::Vulkan(err) => write!(f, "Vulkan error: {err}"),
+        }
+    }
+}
+
+impl Error for VulkanRendererError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::NoLoader(err) => Some(err),
+            Self::Vulkan(err) => Some(err.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<LoadingError> for VulkanRendererError {
+    fn from(err: LoadingError) -> Self {
+        Self::NoLoader(err)
+    }
+}
+
+impl<E: Error + Send + Sync + 'static> From<Validated<E>> for VulkanRendererError {
+    fn from(err: Validated<E>) -> Self {
+        Self::Vulkan(Box::new(err))
+    }
+}
+
+impl From<VulkanError> for VulkanRendererError {
+    fn from(err: VulkanError) -> Self {
+        Self::Vulkan(Box::new(err))
+    }
+}
+
+impl From<Box<ValidationError>> for VulkanRendererError {
+    fn from(err: Box<ValidationError>) -> Self {
+        Self::Vulkan(err)
+    }
+}
+
+impl From<CommandBufferExecError> for VulkanRendererError {
+    fn from(err: CommandBufferExecError) -> Self {
+        Self::Vulkan(Box::new(err))
+    }
+}
+
+impl From<HostAccessError> for VulkanRendererError {
+    fn from(err: HostAccessError) -> Self {
+        Self::Vulkan(Box::new(err))
+    }
+}
+
+/// Selects a physical device by its index in `--list-devices` or by (part of) its name.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeviceSelector {
+    Index(usize),
+    Name(String),
+}
+
+impl DeviceSelector {
+    fn matches(&self, index: usize, physical_device: &PhysicalDevice) -> bool {
+        match self {
+            Self::Index(selected_index) => *selected_index == index,
+            Self::Name(name) => physical_device
+                .properties()
+                .device_name
+                .to_lowercase()
+                .contains(&name.to_lowercase()),
+        }
+    }
+}
+
+impl FromStr for DeviceSelector {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.parse() {
+            Ok(index) => Self::Index(index),
+            Err(_) => Self::Name(s.to_string()),
+        })
+    }
+}
+
+impl fmt::Display for DeviceSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Index(index) => write!(f, "index {index}"),
+            Self::Name(name) => write!(f, "name `{name}`"),
+        }
+    }
+}
+
+/// A summary of a Vulkan physical device and its capabilities.
+#[derive(Debug, Clone)]
+pub struct DeviceInfo {
+    pub index: usize,
+    pub name: String,
+    pub device_type: PhysicalDeviceType,
+    pub driver: Option<String>,
+    pub api_version: Version,
+    /// The total size of all device-local memory heaps in bytes
+    pub memory: u64,
+    pub supports_ray_tracing: bool,
+}
+
+/// Lists every physical device exposed by the Vulkan loader, in enumeration order.
+pub fn list_devices() -> Result<Vec<DeviceInfo>, VulkanRendererError> {
+    let instance = create_instance()?;
+    let devices = instance
+        .enumerate_physical_devices()?
+        .enumerate()
+        .map(|(index, physical_device)| {
+            let properties = physical_device.properties();
+            let driver = match (&properties.driver_name, &properties.driver_info) {
+                (Some(name), Some(info)) => Some(format!("{name} {info}")),
+                (Some(name), None) => Some(name.clone()),
+                (None, info) => info.clone(),
+            };
+            let memory = physical_device
+                .memory_properties()
+                .memory_heaps
+                .iter()
+                .filter(|heap| heap.flags.intersects(MemoryHeapFlags::DEVICE_LOCAL))
+                .map(|heap| heap.size)
+                .sum();
+
+            DeviceInfo {
+                index,
+                name: properties.device_name.clone(),
+                device_type: properties.device_type,
+                driver,
+                api_version: physical_device.api_version(),
+                memory,
+                supports_ray_tracing: supports_ray_tracing(&physical_device),
+            }
+        })
+        .collect();
+
+    Ok(devices)
+}
+
 impl Renderer for VulkanRenderer {
     fn render_frame(&mut self, scene: &Scene) -> RgbaImage {
         self.new_frame(scene);
 
-        let frame = self.render_sample(scene).unwrap();
+        let mut rendered_frame = None;
+        while let Some(frame) = self.render_sample(scene) {
+            rendered_frame = Some(frame);
+        }
 
-        frame
+        // Errors are kept in `last_error`, fall back to a blank frame
+        rendered_frame.unwrap_or_else(|| {
+            RgbaImage::new(scene.camera.resolution_x(), scene.camera.resolution_y())
+        })
     }
 
-    fn render_sample(&mut self, scene: &Scene) -> Option<RgbaImage> {
+    fn render_sample(&mut self, _scene: &Scene) -> Option<RgbaImage> {
         self.profiler.sample_timer.start();
 
         if self.sample_count >= self.config.max_sample_count {
             return None;
         }
 
+        match self.trace_samples() {
+            Ok(frame) => frame,
+            Err(err) => {
+                tracing::error!("Failed to render sample: {err}");
+                self.last_error = Some(err);
+                None
+            }
+        }
+    }
+
+    fn new_frame(&mut self, scene: &Scene) {
+        self.profiler.frame_timer.start();
+        self.profiler.prepare_timer.start();
+
+        let prepare_span = tracing::info_span!("prepare_scene").entered();
+        self.last_error = None;
+        self.bound_scene = match self.bind_scene(scene) {
+            Ok(bound_scene) => Some(bound_scene),
+            Err(err) => {
+                tracing::error!("Failed to prepare scene: {err}");
+                self.last_error = Some(err);
+                None
+            }
+        };
+        self.frame_buffer = None;
+        self.sample_count = 0;
+        self.profiler.path_statistics = Default::default();
+        drop(prepare_span);
+
+        self.profiler.prepare_timer.end();
+        self.profiler.render_timer.start();
+    }
+
+    fn frame_buffer(&self) -> Option<&Rgba32FImage> {
+        self.frame_buffer.as_ref()
+    }
+
+    fn profiler(&self) -> &Profiler {
+        &self.profiler
+    }
+
+    fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    fn config(&self) -> &RendererConfig {
+        &self.config
+    }
+
+    fn config_mut(&mut self) -> &mut RendererConfig {
+        &mut self.config
+    }
+
+    fn last_error(&self) -> Option<&dyn Error> {
+        self.last_error.as_ref().map(|err| err as &dyn Error)
+    }
+
+    fn name(&self) -> &'static str {
+        match self.backend {
+            Backend::RayTracing(_) => "Vulkan (ray tracing)",
+            Backend::Compute(_) => "Vulkan (compute)",
+        }
+    }
+}
+
+impl VulkanRenderer {
+    /// Traces the next batch of samples of the bound scene and reads back the accumulated
+    /// radiance.
+    fn trace_samples(&mut self) -> Result<Option<RgbaImage>, VulkanRendererError> {
+        let Some(bound_scene) = self.bound_scene.as_ref() else {
+            return Ok(None);
+        };
+
+        let sample_batch = shaders::raygen::SampleBatch {
+            first_sample: self.sample_count,
+            sample_count: SAMPLES_PER_DISPATCH
+                .min(self.config.max_sample_count - self.sample_count),
+        };
+        // The samples of a batch are traced by a single dispatch, so they share one span
+        let sample_span =
+            tracing::info_span!("sample", count = sample_batch.sample_count).entered();
         let mut builder = AutoCommandBufferBuilder::primary(
             self.command_buffer_allocator.clone(),
             self.queue.queue_family_index(),
             CommandBufferUsage::OneTimeSubmit,
-        )
-        .unwrap();
-
-        let bound_scene = self.bound_scene.as_ref()?;
+        )?;
 
-        builder
-            .bind_descriptor_sets(
-                PipelineBindPoint::RayTracing,
-                self.pipeline_layout.clone(),
-                0,
-                vec![
-                    bound_scene.scene_descriptor_set.clone(),
-                    bound_scene.image_descriptor_set.clone(),
-                ],
-            )
-            .ok()?;
-
-        builder
-            .bind_pipeline_ray_tracing(self.pipeline.clone())
-            .ok()?;
+        let bind_point = match self.backend {
+            Backend::RayTracing(_) => PipelineBindPoint::RayTracing,
+            Backend::Compute(_) => PipelineBindPoint::Compute,
+        };
+        builder.bind_descriptor_sets(
+            bind_point,
+            self.backend.pipeline_layout().clone(),
+            0,
+            vec![
+                bound_scene.scene_descriptor_set.clone(),
+                bound_scene.image_descriptor_set.clone(),
+            ],
+        )?;
+        builder.push_constants(self.backend.pipeline_layout().clone(), 0, sample_batch)?;
 
         let extent = bound_scene.image_view.image().extent();
-        unsafe {
-            builder
-                .trace_rays(
-                    self.shader_binding_table.addresses().clone(),
-                    extent[0],
-                    extent[1],
-                    1,
-                )
-                .ok()?;
+        match &self.backend {
+            Backend::RayTracing(backend) => {
+                builder.bind_pipeline_ray_tracing(backend.pipeline.clone())?;
+                unsafe {
+                    builder.trace_rays(
+                        backend.shader_binding_table.addresses().clone(),
+                        extent[0],
+                        extent[1],
+                        1,
+                    )?;
+                }
+            }
+            Backend::Compute(backend) => {
+                builder.bind_pipeline_compute(backend.pipeline.clone())?;
+                unsafe {
+                    builder.dispatch([
+                        extent[0].div_ceil(COMPUTE_WORKGROUP_SIZE),
+                        extent[1].div_ceil(COMPUTE_WORKGROUP_SIZE),
+                        1,
+                    ])?;
+                }
+            }
         }
 
-        builder
-            .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
-                bound_scene.image.clone(),
-                bound_scene.output_buffer.clone(),
-            ))
-            .ok()?;
+        builder.copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
+            bound_scene.image.clone(),
+            bound_scene.output_buffer.clone(),
+        ))?;
 
-        let command_buffer = builder.build().unwrap();
+        let command_buffer = builder.build()?;
 
         let future = sync::now(self.device.clone())
-            .then_execute(self.queue.clone(), command_buffer)
-            .unwrap()
-            .then_signal_fence_and_flush()
-            .unwrap();
-
-        future.wait(None).unwrap();
-
-        // Read the buffer data and save it as an image
-        let buffer_content = bound_scene.output_buffer.read().unwrap();
+            .then_execute(self.queue.clone(), command_buffer)?
+            .then_signal_fence_and_flush()?;
+
+        future.wait(None)?;
+        drop(sample_span);
+
+        // Read back the accumulated radiance and pass it through the shared post-processing
+        let readback_span = tracing::info_span!("readback").entered();
+        let buffer_content = bound_scene.output_buffer.read()?;
+        let Some(frame_buffer) =
+            Rgba32FImage::from_raw(extent[0], extent[1], buffer_content.to_vec())
+        else {
+            return Ok(None);
+        };
 
-        self.sample_count = self.config.max_sample_count;
+        self.sample_count += sample_batch.sample_count;
+
+        // The counters accumulate over all batches of the frame
+        self.profiler.path_statistics = {
+            let buffer = bound_scene.path_statistics_buffer.read()?;
+            let counter = |statistic: usize| {
+                (u64::from(buffer.counters[2 * statistic + 1]) << 32)
+                    | u64::from(buffer.counters[2 * statistic])
+            };
+            PathStatistics {
+                path_count: counter(STATISTIC_PATHS),
+                primary_rays: counter(STATISTIC_PRIMARY_RAYS),
+                secondary_rays: counter(STATISTIC_SECONDARY_RAYS),
+                shadow_rays: 0,
+                intersection_tests: counter(STATISTIC_INTERSECTION_TESTS),
+                bvh_nodes_visited: counter(STATISTIC_BVH_NODES_VISITED),
+                counts_traversal: matches!(self.backend, Backend::Compute(_)),
+                length_histogram: array::from_fn(|bin| counter(STATISTIC_PATH_LENGTHS + bin)),
+            }
+        };
+        drop(readback_span);
 
         self.profiler
             .sample_timer
-            .end_multiple(self.config.max_sample_count);
-        self.profiler.render_timer.end();
-        self.profiler.frame_timer.end();
-
-        image::RgbaImage::from_raw(
-            scene.camera.resolution_x(),
-            scene.camera.resolution_y(),
-            buffer_content.to_vec(),
-        )
-    }
+            .end_multiple(sample_batch.sample_count);
+        if self.sample_count == self.config.max_sample_count {
+            self.profiler.render_timer.end();
+            self.profiler.frame_timer.end();
+        }
 
-    fn new_frame(&mut self, scene: &Scene) {
-        self.profiler.frame_timer.start();
-        self.profiler.prepare_timer.start();
+        let rendered_frame =
+            output::display_transform(&frame_buffer, self.sample_count, self.config.tonemapping);
+        self.frame_buffer = Some(frame_buffer);
 
+        Ok(Some(rendered_frame))
+    }
+
+    /// Uploads the scene to the device along with the data structures the backend traverses.
+    fn bind_scene(&self, scene: &Scene) -> Result<BoundScene, VulkanRendererError> {
         let image = Image::new(
             self.memory_allocator.clone(),
             ImageCreateInfo {
                 usage: ImageUsage::STORAGE | ImageUsage::TRANSFER_SRC,
-                format: Format::R8G8B8A8_UNORM,
+                format: Format::R32G32B32A32_SFLOAT,
                 extent: [scene.camera.resolution_x(), scene.camera.resolution_y(), 1],
                 ..Default::default()
             },
@@ -201,52 +542,7 @@ impl Renderer for VulkanRenderer {
                 memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
                 ..Default::default()
             },
-        )
-        .unwrap();
-
-        let instances: Vec<_> = scene
-            .objects
-            .iter()
-            .enumerate()
-            // FIXME: Add support for spheres
-            .map(|(index, object)| {
-                let get_transform_matrix = |location: Point3<f32>, scale: f32| {
-                    let transform =
-                        Matrix4::from_translation(location.to_vec()) * Matrix4::from_scale(scale);
-                    [
-                        [transform.x.x, transform.x.y, transform.x.z, transform.w.x],
-                        [transform.y.x, transform.y.y, transform.y.z, transform.w.y],
-                        [transform.z.x, transform.z.y, transform.z.z, transform.w.z],
-                    ]
-                };
-
-                match &object.geometry {
-                    Geometry::Sphere(sphere) => AccelerationStructureInstance {
-                        acceleration_structure_reference: self.sphere_blas.device_address().into(),
-                        transform: get_transform_matrix(sphere.center, sphere.radius),
-                        instance_custom_index_and_mask: Packed24_8::new(index as u32, 0xFF),
-                        instance_shader_binding_table_record_offset_and_flags: Packed24_8::new(
-                            1, 0,
-                        ),
-                        ..Default::default()
-                    },
-                    Geometry::Cube(cube) => AccelerationStructureInstance {
-                        acceleration_structure_reference: self.cube_blas.device_address().into(),
-                        transform: get_transform_matrix(cube.center, cube.side_length),
-                        instance_custom_index_and_mask: Packed24_8::new(index as u32, 0xFF),
-                        ..Default::default()
-                    },
-                }
-            })
-            .collect();
-
-        let tlas = build_tlas(
-            instances,
-            self.device.clone(),
-            self.memory_allocator.clone(),
-            self.command_buffer_allocator.clone(),
-            self.queue.clone(),
-        );
+        )?;
 
         // Convert from left-handed to right-handed coordinate system and flip Y
         let view = Matrix4::look_at_rh(
@@ -268,11 +564,10 @@ impl Renderer for VulkanRenderer {
             },
             raygen::Camera {
                 view_proj: (scene.camera.proj_matrix() * view).into(),
-                inverse_view: view.invert().unwrap().into(),
+                inverse_view: view.invert().unwrap_or(Matrix4::identity()).into(),
                 inverse_proj: scene.camera.inverse_proj_matrix().into(),
             },
-        )
-        .unwrap();
+        )?;
 
         let world_uniform_buffer = Buffer::from_data(
             self.memory_allocator.clone(),
@@ -292,15 +587,20 @@ impl Renderer for VulkanRenderer {
                 } => raygen::World {
                     top_color: Padded(top_color.into()),
                     bottom_color: bottom_color.into(),
+                    is_transparent: 0,
                 },
                 World::SolidColor(color) => raygen::World {
                     top_color: Padded(color.into()),
                     bottom_color: color.into(),
+                    is_transparent: 0,
+                },
+                World::Transparent => raygen::World {
+                    top_color: Padded([0.0; 3]),
+                    bottom_color: [0.0; 3],
+                    is_transparent: 1,
                 },
-                World::Transparent => todo!(),
             },
-        )
-        .unwrap();
+        )?;
 
         let materials = scene.objects.iter().map(|object| raygen::Material {
             albedo: object.material.albedo.into(),
@@ -325,23 +625,25 @@ impl Renderer for VulkanRenderer {
                 ..Default::default()
             },
             materials,
-        )
-        .unwrap();
+        )?;
 
-        let sphere_data = scene.objects.iter().map(|object| match &object.geometry {
-            Geometry::Sphere(sphere) => shaders::intersection::Sphere {
-                center: sphere.center.into(),
-                radius: sphere.radius,
-            },
-            _ => shaders::intersection::Sphere {
-                center: [0.0, 0.0, 0.0],
-                radius: 0.0,
-            },
-        });
-        let sphere_buffer = Buffer::from_iter(
+        let renderer_properties = shaders::raygen::RendererProperties {
+            max_bounces: self.config.bounces.total,
+            seed: self.config.seed,
+            russian_roulette: self.config.russian_roulette.into(),
+            russian_roulette_min_depth: self.config.russian_roulette_min_depth,
+            max_diffuse_bounces: self.config.bounces.diffuse,
+            max_glossy_bounces: self.config.bounces.glossy,
+            max_transmission_bounces: self.config.bounces.transmission,
+            // The shader treats a limit of zero as unclamped
+            clamp_direct: self.config.clamping.direct.unwrap_or(0.0),
+            clamp_indirect: self.config.clamping.indirect.unwrap_or(0.0),
+            reconstruction_filter: self.config.filter as u32,
+        };
+        let renderer_properties_buffer = Buffer::from_data(
             self.memory_allocator.clone(),
             BufferCreateInfo {
-                usage: BufferUsage::STORAGE_BUFFER,
+                usage: BufferUsage::UNIFORM_BUFFER,
                 ..Default::default()
             },
             AllocationCreateInfo {
@@ -349,58 +651,62 @@ impl Renderer for VulkanRenderer {
                     | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
                 ..Default::default()
             },
-            sphere_data,
-        )
-        .unwrap();
+            renderer_properties,
+        )?;
 
-        let renderer_properties = shaders::raygen::RendererProperties {
-            max_bounces: self.config.max_bounces,
-            max_sample_count: self.config.max_sample_count,
-        };
-        let renderer_properties_buffer = Buffer::from_data(
+        let path_statistics_buffer = Buffer::from_data(
             self.memory_allocator.clone(),
             BufferCreateInfo {
-                usage: BufferUsage::UNIFORM_BUFFER,
+                usage: BufferUsage::STORAGE_BUFFER,
                 ..Default::default()
             },
             AllocationCreateInfo {
                 memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
-                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                    | MemoryTypeFilter::HOST_RANDOM_ACCESS,
                 ..Default::default()
             },
-            renderer_properties,
-        )
-        .unwrap();
+            shaders::raygen::PathStatistics {
+                counters: [0; 2 * STATISTIC_COUNT],
+            },
+        )?;
+
+        let mut descriptor_writes = vec![
+            WriteDescriptorSet::buffer(1, camera_uniform_buffer),
+            WriteDescriptorSet::buffer(2, world_uniform_buffer),
+            WriteDescriptorSet::buffer(5, materials_buffer),
+            WriteDescriptorSet::buffer(7, renderer_properties_buffer),
+            WriteDescriptorSet::buffer(11, path_statistics_buffer.clone()),
+        ];
+        let tlas = match &self.backend {
+            Backend::RayTracing(backend) => {
+                let (tlas, writes) = self.bind_ray_tracing_scene(backend, scene)?;
+                descriptor_writes.extend(writes);
+                Some(tlas)
+            }
+            Backend::Compute(_) => {
+                descriptor_writes.extend(self.bind_compute_scene(scene)?);
+                None
+            }
+        };
 
         let scene_descriptor_set = DescriptorSet::new(
             self.descriptor_set_allocator.clone(),
-            self.pipeline_layout.set_layouts()[0].clone(),
-            [
-                WriteDescriptorSet::acceleration_structure(0, tlas.clone()),
-                WriteDescriptorSet::buffer(1, camera_uniform_buffer),
-                WriteDescriptorSet::buffer(2, world_uniform_buffer),
-                WriteDescriptorSet::buffer(3, self.cube_vertex_buffer.clone()),
-                WriteDescriptorSet::buffer(4, self.cube_index_buffer.clone()),
-                WriteDescriptorSet::buffer(5, materials_buffer),
-                WriteDescriptorSet::buffer(6, sphere_buffer),
-                WriteDescriptorSet::buffer(7, renderer_properties_buffer),
-            ],
+            self.backend.pipeline_layout().set_layouts()[0].clone(),
+            descriptor_writes,
             [],
-        )
-        .unwrap();
+        )?;
 
-        let image_view = ImageView::new_default(image.clone()).unwrap();
+        let image_view = ImageView::new_default(image.clone())?;
         let image_descriptor_set = DescriptorSet::new(
             self.descriptor_set_allocator.clone(),
-            self.pipeline_layout.set_layouts()[1].clone(),
+            self.backend.pipeline_layout().set_layouts()[1].clone(),
             [WriteDescriptorSet::image_view(0, image_view.clone())],
             [],
-        )
-        .unwrap();
+        )?;
 
         let scratch_memory_allocator =
             Arc::new(StandardMemoryAllocator::new_default(self.device.clone()));
-        let output_buffer = Buffer::new_slice::<u8>(
+        let output_buffer = Buffer::new_slice::<f32>(
             scratch_memory_allocator.clone(),
             BufferCreateInfo {
                 usage: BufferUsage::TRANSFER_DST,
@@ -412,75 +718,246 @@ impl Renderer for VulkanRenderer {
                 ..Default::default()
             },
             (scene.camera.resolution_x() * scene.camera.resolution_y() * 4) as u64,
-        )
-        .unwrap();
-
-        self.profiler.prepare_timer.end();
-        self.profiler.render_timer.start();
+        )?;
 
-        self.bound_scene = Some(BoundScene {
+        Ok(BoundScene {
             tlas,
             scene_descriptor_set,
             image_descriptor_set,
             image_view,
             image,
             output_buffer,
-        });
-        self.sample_count = 0;
+            path_statistics_buffer,
+        })
     }
 
-    fn max_sample_count(&self) -> u32 {
-        self.config.max_sample_count
-    }
+    /// Builds the top-level acceleration structure of the scene and the per-object sphere data
+    /// used by the intersection shader.
+    fn bind_ray_tracing_scene(
+        &self,
+        backend: &RayTracingBackend,
+        scene: &Scene,
+    ) -> Result<(Arc<AccelerationStructure>, [WriteDescriptorSet; 4]), VulkanRendererError> {
+        let instances: Vec<_> = scene
+            .objects
+            .iter()
+            .enumerate()
+            // FIXME: Add support for spheres
+            .map(|(index, object)| {
+                let get_transform_matrix = |location: Point3<f32>, scale: f32| {
+                    let transform =
+                        Matrix4::from_translation(location.to_vec()) * Matrix4::from_scale(scale);
+                    [
+                        [transform.x.x, transform.x.y, transform.x.z, transform.w.x],
+                        [transform.y.x, transform.y.y, transform.y.z, transform.w.y],
+                        [transform.z.x, transform.z.y, transform.z.z, transform.w.z],
+                    ]
+                };
 
-    fn max_bounces(&self) -> u32 {
-        self.config.max_bounces
-    }
+                match &object.geometry {
+                    Geometry::Sphere(sphere) => AccelerationStructureInstance {
+                        acceleration_structure_reference: backend
+                            .sphere_blas
+                            .device_address()
+                            .into(),
+                        transform: get_transform_matrix(sphere.center, sphere.radius),
+                        instance_custom_index_and_mask: Packed24_8::new(index as u32, 0xFF),
+                        instance_shader_binding_table_record_offset_and_flags: Packed24_8::new(
+                            1, 0,
+                        ),
+                        ..Default::default()
+                    },
+                    Geometry::Cube(cube) => AccelerationStructureInstance {
+                        acceleration_structure_reference: backend.cube_blas.device_address().into(),
+                        transform: get_transform_matrix(cube.center, cube.side_length),
+                        instance_custom_index_and_mask: Packed24_8::new(index as u32, 0xFF),
+                        ..Default::default()
+                    },
+                }
+            })
+            .collect();
 
-    fn profiler(&self) -> &Profiler {
-        &self.profiler
-    }
+        let tlas = build_tlas(
+            instances,
+            self.device.clone(),
+            self.memory_allocator.clone(),
+            self.command_buffer_allocator.clone(),
+            self.queue.clone(),
+        )?;
 
-    fn sample_count(&self) -> u32 {
-        self.sample_count
-    }
+        let sphere_data = scene.objects.iter().map(|object| match &object.geometry {
+            Geometry::Sphere(sphere) => shaders::intersection::Sphere {
+                center: sphere.center.into(),
+                radius: sphere.radius,
+            },
+            _ => shaders::intersection::Sphere {
+                center: [0.0, 0.0, 0.0],
+                radius: 0.0,
+            },
+        });
+        let sphere_buffer = Buffer::from_iter(
+            self.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            sphere_data,
+        )?;
+
+        let writes = [
+            WriteDescriptorSet::acceleration_structure(0, tlas.clone()),
+            WriteDescriptorSet::buffer(3, backend.cube_vertex_buffer.clone()),
+            WriteDescriptorSet::buffer(4, backend.cube_index_buffer.clone()),
+            WriteDescriptorSet::buffer(6, sphere_buffer),
+        ];
 
-    fn set_max_sample_count(&mut self, count: u32) {
-        self.config.max_sample_count = count;
+        Ok((tlas, writes))
     }
 
-    fn set_max_bounces(&mut self, bounces: u32) {
-        self.config.max_bounces = bounces;
+    /// Builds a BVH over the objects of the scene and uploads it for the compute shader.
+    fn bind_compute_scene(
+        &self,
+        scene: &Scene,
+    ) -> Result<[WriteDescriptorSet; 3], VulkanRendererError> {
+        let bvh = Bvh::build(&scene.objects);
+
+        // Storage buffers cannot be empty, an empty scene is marked by a root node with
+        // no objects that points to itself
+        let nodes: Vec<_> = if bvh.nodes.is_empty() {
+            vec![shaders::compute::BvhNode {
+                aabb_min: [0.0; 3],
+                left_first: 0,
+                aabb_max: [0.0; 3],
+                count: 0,
+            }]
+        } else {
+            bvh.nodes
+                .iter()
+                .map(|node| shaders::compute::BvhNode {
+                    aabb_min: node.bounds.min.into(),
+                    left_first: node.left_first,
+                    aabb_max: node.bounds.max.into(),
+                    count: node.count,
+                })
+                .collect()
+        };
+
+        let mut objects: Vec<_> = scene
+            .objects
+            .iter()
+            .map(|object| match &object.geometry {
+                Geometry::Sphere(sphere) => shaders::compute::GpuObject {
+                    center: sphere.center.into(),
+                    size: sphere.radius,
+                    geometry_type: 0,
+                    _padding0: 0.0,
+                    _padding1: 0.0,
+                    _padding2: 0.0,
+                },
+                Geometry::Cube(cube) => shaders::compute::GpuObject {
+                    center: cube.center.into(),
+                    size: cube.side_length,
+                    geometry_type: 1,
+                    _padding0: 0.0,
+                    _padding1: 0.0,
+                    _padding2: 0.0,
+                },
+            })
+            .collect();
+        if objects.is_empty() {
+            objects.push(shaders::compute::GpuObject {
+                center: [0.0; 3],
+                size: 0.0,
+                geometry_type: 0,
+                _padding0: 0.0,
+                _padding1: 0.0,
+                _padding2: 0.0,
+            });
+        }
+
+        let mut object_indices = bvh.object_indices;
+        if object_indices.is_empty() {
+            object_indices.push(0);
+        }
+
+        let storage_buffer_info = || BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        };
+        let allocation_info = || AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+            ..Default::default()
+        };
+
+        let nodes_buffer = Buffer::from_iter(
+            self.memory_allocator.clone(),
+            storage_buffer_info(),
+            allocation_info(),
+            nodes,
+        )?;
+        let objects_buffer = Buffer::from_iter(
+            self.memory_allocator.clone(),
+            storage_buffer_info(),
+            allocation_info(),
+            objects,
+        )?;
+        let object_indices_buffer = Buffer::from_iter(
+            self.memory_allocator.clone(),
+            storage_buffer_info(),
+            allocation_info(),
+            object_indices,
+        )?;
+
+        Ok([
+            WriteDescriptorSet::buffer(8, nodes_buffer),
+            WriteDescriptorSet::buffer(9, objects_buffer),
+            WriteDescriptorSet::buffer(10, object_indices_buffer),
+        ])
     }
 }
 
 impl VulkanRenderer {
-    pub fn new(config: RendererConfig) -> Self {
-        let library = VulkanLibrary::new().unwrap();
-        let instance = Instance::new(
-            library,
-            InstanceCreateInfo {
-                flags: InstanceCreateFlags::ENUMERATE_PORTABILITY,
-                enabled_extensions: InstanceExtensions::empty(),
-                ..Default::default()
-            },
-        )
-        .unwrap();
-
-        let device_extensions = DeviceExtensions {
-            khr_ray_tracing_pipeline: true,
-            khr_ray_tracing_maintenance1: true,
-            khr_synchronization2: true,
-            khr_deferred_host_operations: true,
-            khr_acceleration_structure: true,
-            ..DeviceExtensions::empty()
+    /// Creates a renderer on the most capable device.
+    pub fn new(config: RendererConfig) -> Result<Self, VulkanRendererError> {
+        Self::create(config, None)
+    }
+
+    /// Creates a renderer on the device that matches `selector`.
+    pub fn with_device(
+        config: RendererConfig,
+        selector: &DeviceSelector,
+    ) -> Result<Self, VulkanRendererError> {
+        Self::create(config, Some(selector))
+    }
+
+    fn create(
+        config: RendererConfig,
+        selector: Option<&DeviceSelector>,
+    ) -> Result<Self, VulkanRendererError> {
+        let instance = create_instance()?;
+
+        let physical_devices: Vec<_> = match selector {
+            Some(selector) => {
+                let physical_device = instance
+                    .enumerate_physical_devices()?
+                    .enumerate()
+                    .find(|(index, p)| selector.matches(*index, p))
+                    .map(|(_, p)| p)
+                    .ok_or_else(|| VulkanRendererError::DeviceNotFound(selector.clone()))?;
+                vec![physical_device]
+            }
+            None => instance.enumerate_physical_devices()?.collect(),
         };
 
-        let (physical_device, queue_family_index) = instance
-            .enumerate_physical_devices()
-            .unwrap()
+        let candidates: Vec<_> = physical_devices
+            .into_iter()
             .filter(|p| p.api_version() >= Version::V1_3)
-            .filter(|p| p.supported_extensions().contains(&device_extensions))
             .filter_map(|p| {
                 p.queue_family_properties()
                     .iter()
@@ -491,37 +968,50 @@ impl VulkanRenderer {
                     })
                     .map(|i| (p, i as u32))
             })
-            .min_by_key(|(p, _)| match p.properties().device_type {
-                PhysicalDeviceType::DiscreteGpu => 0,
-                PhysicalDeviceType::IntegratedGpu => 1,
-                PhysicalDeviceType::VirtualGpu => 2,
-                PhysicalDeviceType::Cpu => 3,
-                PhysicalDeviceType::Other => 4,
-                _ => 5,
+            .collect();
+
+        // Among devices of the same type, prefer the ones that support ray tracing
+        let (physical_device, queue_family_index) = candidates
+            .into_iter()
+            .min_by_key(|(p, _)| {
+                (
+                    device_type_rank(p.properties().device_type),
+                    !supports_ray_tracing(p),
+                )
             })
-            .unwrap();
+            .ok_or(VulkanRendererError::NoSuitableDevice)?;
+
+        // Devices without ray tracing pipelines fall back to tracing in a compute shader
+        let use_ray_tracing = supports_ray_tracing(&physical_device);
+        let (enabled_extensions, enabled_features) = if use_ray_tracing {
+            (
+                RAY_TRACING_EXTENSIONS,
+                DeviceFeatures {
+                    acceleration_structure: true,
+                    ray_tracing_pipeline: true,
+                    buffer_device_address: true,
+                    synchronization2: true,
+                    ..Default::default()
+                },
+            )
+        } else {
+            (DeviceExtensions::empty(), DeviceFeatures::empty())
+        };
 
         let (device, mut queues) = Device::new(
             physical_device,
             DeviceCreateInfo {
-                enabled_extensions: device_extensions,
+                enabled_extensions,
                 queue_create_infos: vec![QueueCreateInfo {
                     queue_family_index,
                     ..Default::default()
                 }],
-                enabled_features: DeviceFeatures {
-                    acceleration_structure: true,
-                    ray_tracing_pipeline: true,
-                    buffer_device_address: true,
-                    synchronization2: true,
-                    ..Default::default()
-                },
+                enabled_features,
                 ..Default::default()
             },
-        )
-        .unwrap();
+        )?;
 
-        let queue = queues.next().unwrap();
+        let queue = queues.next().ok_or(VulkanRendererError::NoSuitableDevice)?;
 
         let command_buffer_allocator = Arc::new(StandardCommandBufferAllocator::new(
             device.clone(),
@@ -534,6 +1024,45 @@ impl VulkanRenderer {
             Default::default(),
         ));
 
+        let backend = if use_ray_tracing {
+            Backend::RayTracing(RayTracingBackend::new(
+                device.clone(),
+                queue.clone(),
+                memory_allocator.clone(),
+                command_buffer_allocator.clone(),
+            )?)
+        } else {
+            Backend::Compute(ComputeBackend::new(device.clone())?)
+        };
+
+        Ok(Self {
+            profiler: Profiler::default(),
+            config,
+
+            instance,
+            device,
+            queue,
+            backend,
+
+            memory_allocator,
+            command_buffer_allocator,
+            descriptor_set_allocator,
+
+            bound_scene: None,
+            frame_buffer: None,
+            sample_count: 0,
+            last_error: None,
+        })
+    }
+}
+
+impl RayTracingBackend {
+    fn new(
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        memory_allocator: Arc<StandardMemoryAllocator>,
+        command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
+    ) -> Result<Self, VulkanRendererError> {
         let pipeline_layout = PipelineLayout::new(
             device.clone(),
             PipelineLayoutCreateInfo {
@@ -623,13 +1152,22 @@ impl VulkanRenderer {
                                         )
                                     },
                                 ),
+                                // Path statistics binding
+                                (
+                                    11,
+                                    DescriptorSetLayoutBinding {
+                                        stages: ShaderStages::RAYGEN,
+                                        ..DescriptorSetLayoutBinding::descriptor_type(
+                                            DescriptorType::StorageBuffer,
+                                        )
+                                    },
+                                ),
                             ]
                             .into_iter()
                             .collect(),
                             ..Default::default()
                         },
-                    )
-                    .unwrap(),
+                    )?,
                     DescriptorSetLayout::new(
                         device.clone(),
                         DescriptorSetLayoutCreateInfo {
@@ -646,36 +1184,26 @@ impl VulkanRenderer {
                             .collect(),
                             ..Default::default()
                         },
-                    )
-                    .unwrap(),
+                    )?,
                 ],
+                push_constant_ranges: vec![PushConstantRange {
+                    stages: ShaderStages::RAYGEN,
+                    offset: 0,
+                    size: size_of::<shaders::raygen::SampleBatch>() as u32,
+                }],
                 ..Default::default()
             },
-        )
-        .unwrap();
+        )?;
 
         let pipeline = {
-            let raygen = shaders::raygen::load(device.clone())
-                .unwrap()
-                .entry_point("main")
-                .unwrap();
-            let triangle_closest_hit = shaders::triangle_closest_hit::load(device.clone())
-                .unwrap()
-                .entry_point("main")
-                .unwrap();
-            let sphere_closest_hit = shaders::sphere_closest_hit::load(device.clone())
-                .unwrap()
-                .entry_point("main")
-                .unwrap();
-
-            let miss = shaders::miss::load(device.clone())
-                .unwrap()
-                .entry_point("main")
-                .unwrap();
-            let intersection = shaders::intersection::load(device.clone())
-                .unwrap()
-                .entry_point("main")
-                .unwrap();
+            let raygen = load_entry_point(shaders::raygen::load(device.clone())?)?;
+            let triangle_closest_hit =
+                load_entry_point(shaders::triangle_closest_hit::load(device.clone())?)?;
+            let sphere_closest_hit =
+                load_entry_point(shaders::sphere_closest_hit::load(device.clone())?)?;
+
+            let miss = load_entry_point(shaders::miss::load(device.clone())?)?;
+            let intersection = load_entry_point(shaders::intersection::load(device.clone())?)?;
 
             // Make a list of the shader stages that the pipeline will have.
             let stages = [
@@ -712,12 +1240,10 @@ impl VulkanRenderer {
 
                     ..RayTracingPipelineCreateInfo::layout(pipeline_layout.clone())
                 },
-            )
-            .unwrap()
+            )?
         };
 
-        let shader_binding_table =
-            ShaderBindingTable::new(memory_allocator.clone(), &pipeline).unwrap();
+        let shader_binding_table = ShaderBindingTable::new(memory_allocator.clone(), &pipeline)?;
 
         let (cube_vertex_buffer, cube_index_buffer) = {
             // TODO: Use Rust metaprogramming (macros) to generate the vertex data at compile time instead of hardcoding it manually
@@ -857,8 +1383,7 @@ impl VulkanRenderer {
                     ..Default::default()
                 },
                 vertices,
-            )
-            .unwrap();
+            )?;
 
             let index_buffer = Buffer::from_iter(
                 memory_allocator.clone(),
@@ -875,8 +1400,7 @@ impl VulkanRenderer {
                     ..Default::default()
                 },
                 indices,
-            )
-            .unwrap();
+            )?;
 
             (vertex_buffer, index_buffer)
         };
@@ -889,7 +1413,7 @@ impl VulkanRenderer {
                 memory_allocator.clone(),
                 command_buffer_allocator.clone(),
                 queue.clone(),
-            )
+            )?
         };
 
         let sphere_aabb_buffer = Buffer::from_iter(
@@ -908,8 +1432,7 @@ impl VulkanRenderer {
                 min: [-1.0, -1.0, -1.0],
                 max: [1.0, 1.0, 1.0],
             }],
-        )
-        .unwrap();
+        )?;
 
         let sphere_blas = build_blas_aabb(
             sphere_aabb_buffer.clone(),
@@ -917,40 +1440,163 @@ impl VulkanRenderer {
             memory_allocator.clone(),
             command_buffer_allocator.clone(),
             queue.clone(),
-        );
+        )?;
 
-        Self {
-            profiler: Profiler::default(),
-            config,
-
-            instance,
-            device,
-            queue,
+        Ok(Self {
             pipeline,
-            pipeline_layout,
             shader_binding_table,
 
             cube_vertex_buffer,
             cube_index_buffer,
             cube_blas,
             sphere_blas,
+        })
+    }
+}
 
-            memory_allocator,
-            command_buffer_allocator,
-            descriptor_set_allocator,
+impl ComputeBackend {
+    fn new(device: Arc<Device>) -> Result<Self, VulkanRendererError> {
+        let storage_buffer = |binding| {
+            (
+                binding,
+                DescriptorSetLayoutBinding {
+                    stages: ShaderStages::COMPUTE,
+                    ..DescriptorSetLayoutBinding::descriptor_type(DescriptorType::StorageBuffer)
+                },
+            )
+        };
+        let uniform_buffer = |binding| {
+            (
+                binding,
+                DescriptorSetLayoutBinding {
+                    stages: ShaderStages::COMPUTE,
+                    ..DescriptorSetLayoutBinding::descriptor_type(DescriptorType::UniformBuffer)
+                },
+            )
+        };
 
-            bound_scene: None,
-            sample_count: 0,
-        }
+        // Same bindings as the ray tracing pipeline, with the BVH in place of the
+        // acceleration structure and the geometry buffers
+        let pipeline_layout = PipelineLayout::new(
+            device.clone(),
+            PipelineLayoutCreateInfo {
+                set_layouts: vec![
+                    DescriptorSetLayout::new(
+                        device.clone(),
+                        DescriptorSetLayoutCreateInfo {
+                            bindings: [
+                                // Camera
+                                uniform_buffer(1),
+                                // World
+                                uniform_buffer(2),
+                                // Materials
+                                storage_buffer(5),
+                                // Renderer properties
+                                uniform_buffer(7),
+                                // BVH nodes
+                                storage_buffer(8),
+                                // Objects
+                                storage_buffer(9),
+                                // Object indices referenced by BVH leaves
+                                storage_buffer(10),
+                                // Path statistics
+                                storage_buffer(11),
+                            ]
+                            .into_iter()
+                            .collect(),
+                            ..Default::default()
+                        },
+                    )?,
+                    DescriptorSetLayout::new(
+                        device.clone(),
+                        DescriptorSetLayoutCreateInfo {
+                            bindings: [(
+                                0,
+                                DescriptorSetLayoutBinding {
+                                    stages: ShaderStages::COMPUTE,
+                                    ..DescriptorSetLayoutBinding::descriptor_type(
+                                        DescriptorType::StorageImage,
+                                    )
+                                },
+                            )]
+                            .into_iter()
+                            .collect(),
+                            ..Default::default()
+                        },
+                    )?,
+                ],
+                push_constant_ranges: vec![PushConstantRange {
+                    stages: ShaderStages::COMPUTE,
+                    offset: 0,
+                    size: size_of::<shaders::raygen::SampleBatch>() as u32,
+                }],
+                ..Default::default()
+            },
+        )?;
+
+        let compute = load_entry_point(shaders::compute::load(device.clone())?)?;
+        let pipeline = ComputePipeline::new(
+            device,
+            None,
+            ComputePipelineCreateInfo::stage_layout(
+                PipelineShaderStageCreateInfo::new(compute),
+                pipeline_layout,
+            ),
+        )?;
+
+        Ok(Self { pipeline })
     }
 }
 
-impl Default for VulkanRenderer {
-    fn default() -> Self {
-        Self::new(RendererConfig::default())
+fn create_instance() -> Result<Arc<Instance>, VulkanRendererError> {
+    let library = VulkanLibrary::new()?;
+    let instance = Instance::new(
+        library,
+        InstanceCreateInfo {
+            flags: InstanceCreateFlags::ENUMERATE_PORTABILITY,
+            enabled_extensions: InstanceExtensions::empty(),
+            ..Default::default()
+        },
+    )?;
+
+    Ok(instance)
+}
+
+/// Ranks physical device types by preference, lower is better.
+fn device_type_rank(device_type: PhysicalDeviceType) -> u32 {
+    match device_type {
+        PhysicalDeviceType::DiscreteGpu => 0,
+        PhysicalDeviceType::IntegratedGpu => 1,
+        PhysicalDeviceType::VirtualGpu => 2,
+        PhysicalDeviceType::Cpu => 3,
+        PhysicalDeviceType::Other => 4,
+        _ => 5,
     }
 }
 
+/// The extensions the ray tracing backend enables.
+const RAY_TRACING_EXTENSIONS: DeviceExtensions = DeviceExtensions {
+    khr_ray_tracing_pipeline: true,
+    khr_ray_tracing_maintenance1: true,
+    khr_synchronization2: true,
+    khr_deferred_host_operations: true,
+    khr_acceleration_structure: true,
+    ..DeviceExtensions::empty()
+};
+
+/// Checks whether the device supports everything the ray tracing backend needs.
+fn supports_ray_tracing(physical_device: &PhysicalDevice) -> bool {
+    physical_device
+        .supported_extensions()
+        .contains(&RAY_TRACING_EXTENSIONS)
+}
+
+fn load_entry_point(module: Arc<ShaderModule>) -> Result<EntryPoint, VulkanRendererError> {
+    module
+        .entry_point("main")
+        .ok_or(VulkanRendererError::MissingEntryPoint)
+}
+
 fn build_blas_triangles(
     vertex_buffer: Subbuffer<[Vertex]>,
     index_buffer: Subbuffer<[u32]>,
@@ -958,7 +1604,7 @@ fn build_blas_triangles(
     memory_allocator: Arc<StandardMemoryAllocator>,
     command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
     queue: Arc<Queue>,
-) -> Arc<AccelerationStructure> {
+) -> Result<Arc<AccelerationStructure>, VulkanRendererError> {
     let triangle_count = (index_buffer.len() / 3) as u32;
     let triangles_data = AccelerationStructureGeometryTrianglesData {
         max_vertex: vertex_buffer.len() as _,
@@ -986,7 +1632,7 @@ fn build_blas_aabb(
     memory_allocator: Arc<StandardMemoryAllocator>,
     command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
     queue: Arc<Queue>,
-) -> Arc<AccelerationStructure> {
+) -> Result<Arc<AccelerationStructure>, VulkanRendererError> {
     let aabb_data = AccelerationStructureGeometryAabbsData {
         data: Some(aabb_buffer.into_bytes()),
         stride: size_of::<AabbPositions>() as _,
@@ -1005,13 +1651,14 @@ fn build_blas_aabb(
     )
 }
 
+#[tracing::instrument(name = "tlas_build", skip_all, fields(instances = instances.len()))]
 fn build_tlas(
     instances: Vec<AccelerationStructureInstance>,
     device: Arc<Device>,
     memory_allocator: Arc<StandardMemoryAllocator>,
     command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
     queue: Arc<Queue>,
-) -> Arc<AccelerationStructure> {
+) -> Result<Arc<AccelerationStructure>, VulkanRendererError> {
     let instance_count = instances.len() as u32;
     let instance_buffer = Buffer::from_iter(
         memory_allocator.clone(),
@@ -1026,8 +1673,7 @@ fn build_tlas(
             ..Default::default()
         },
         instances,
-    )
-    .unwrap();
+    )?;
 
     let geometry_instances_data = AccelerationStructureGeometryInstancesData::new(
         AccelerationStructureGeometryInstancesDataType::Values(Some(instance_buffer)),
@@ -1054,19 +1700,17 @@ fn build_acceleration_structure(
     memory_allocator: Arc<StandardMemoryAllocator>,
     command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
     queue: Arc<Queue>,
-) -> Arc<AccelerationStructure> {
+) -> Result<Arc<AccelerationStructure>, VulkanRendererError> {
     let mut build_geometry_info = AccelerationStructureBuildGeometryInfo {
         mode: BuildAccelerationStructureMode::Build,
         flags: BuildAccelerationStructureFlags::PREFER_FAST_TRACE,
         ..AccelerationStructureBuildGeometryInfo::new(geometries)
     };
-    let build_sizes_info = device
-        .acceleration_structure_build_sizes(
-            AccelerationStructureBuildType::Device,
-            &build_geometry_info,
-            &[primitive_count],
-        )
-        .unwrap();
+    let build_sizes_info = device.acceleration_structure_build_sizes(
+        AccelerationStructureBuildType::Device,
+        &build_geometry_info,
+        &[primitive_count],
+    )?;
 
     let scratch_buffer = Buffer::new_slice::<u8>(
         memory_allocator.clone(),
@@ -1076,28 +1720,23 @@ fn build_acceleration_structure(
         },
         AllocationCreateInfo::default(),
         build_sizes_info.build_scratch_size,
-    )
-    .unwrap();
+    )?;
 
     let create_info = AccelerationStructureCreateInfo {
         ty,
-        ..AccelerationStructureCreateInfo::new(
-            Buffer::new_slice::<u8>(
-                memory_allocator,
-                BufferCreateInfo {
-                    usage: BufferUsage::ACCELERATION_STRUCTURE_STORAGE
-                        | BufferUsage::SHADER_DEVICE_ADDRESS,
-                    ..Default::default()
-                },
-                AllocationCreateInfo::default(),
-                build_sizes_info.acceleration_structure_size,
-            )
-            .unwrap(),
-        )
+        ..AccelerationStructureCreateInfo::new(Buffer::new_slice::<u8>(
+            memory_allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::ACCELERATION_STRUCTURE_STORAGE
+                    | BufferUsage::SHADER_DEVICE_ADDRESS,
+                ..Default::default()
+            },
+            AllocationCreateInfo::default(),
+            build_sizes_info.acceleration_structure_size,
+        )?)
     };
 
-    let acceleration_structure =
-        unsafe { AccelerationStructure::new(device, create_info).unwrap() };
+    let acceleration_structure = unsafe { AccelerationStructure::new(device, create_info)? };
 
     build_geometry_info.dst_acceleration_structure = Some(acceleration_structure.clone());
     build_geometry_info.scratch_data = Some(scratch_buffer);
@@ -1111,26 +1750,20 @@ fn build_acceleration_structure(
         command_buffer_allocator,
         queue.queue_family_index(),
         CommandBufferUsage::OneTimeSubmit,
-    )
-    .unwrap();
+    )?;
 
     unsafe {
         builder
-            .build_acceleration_structure(build_geometry_info, iter::once(range_info).collect())
-            .unwrap();
+            .build_acceleration_structure(build_geometry_info, iter::once(range_info).collect())?;
     }
 
     builder
-        .build()
-        .unwrap()
-        .execute(queue)
-        .unwrap()
-        .then_signal_fence_and_flush()
-        .unwrap()
-        .wait(None)
-        .unwrap();
-
-    acceleration_structure
+        .build()?
+        .execute(queue)?
+        .then_signal_fence_and_flush()?
+        .wait(None)?;
+
+    Ok(acceleration_structure)
 }
 
 mod shaders {
@@ -1173,4 +1806,12 @@ mod shaders {
             vulkan_version: "1.2"
         }
     }
+
+    pub(super) mod compute {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "shaders/vulkan/raytrace.comp",
+            vulkan_version: "1.2"
+        }
+    }
 }
@@ -0,0 +1,468 @@
+//! Keyframe animation of scenes.
+//!
+//! A [`Track`] holds keyframes of one value of the scene, such as the position of the camera
+//! or the roughness of a material. Between two keyframes the value is interpolated with the
+//! [`Interpolation`] of the first one; before the first and after the last keyframe it keeps
+//! the value of that keyframe. Values without a track keep the value of the scene.
+
+use std::{
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+use cgmath::{Point3, Vector3, VectorSpace};
+use serde::{Deserialize, Serialize};
+
+use super::{objects::Geometry, world::World, Scene};
+
+/// How a value changes from a keyframe to the next one.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default, Debug)]
+pub enum Interpolation {
+    /// At a constant rate
+    #[default]
+    Linear,
+    /// Along a cubic Bezier curve from (0, 0) to (1, 1) over the time and the progress between
+    /// the keyframes, like the CSS `cubic-bezier()` timing function. `(0.42, 0, 0.58, 1)`
+    /// eases in and out.
+    Bezier { x1: f32, y1: f32, x2: f32, y2: f32 },
+}
+
+impl Interpolation {
+    /// Maps the fraction of the time between two keyframes to the fraction of the change of
+    /// the value.
+    pub fn progress(&self, time: f32) -> f32 {
+        let time = time.clamp(0.0, 1.0);
+        match *self {
+            Self::Linear => time,
+            Self::Bezier { x1, y1, x2, y2 } => {
+                let bezier = |p1: f32, p2: f32, s: f32| {
+                    let inverse = 1.0 - s;
+                    3.0 * inverse * inverse * s * p1 + 3.0 * inverse * s * s * p2 + s * s * s
+                };
+
+                // The time of the curve increases monotonically for x1 and x2 between 0 and 1,
+                // so the curve parameter can be found by bisection
+                let (mut low, mut high) = (0.0, 1.0);
+                for _ in 0..32 {
+                    let middle = (low + high) * 0.5;
+                    if bezier(x1, x2, middle) < time {
+                        low = middle;
+                    } else {
+                        high = middle;
+                    }
+                }
+                bezier(y1, y2, (low + high) * 0.5)
+            }
+        }
+    }
+}
+
+/// Values that can be interpolated between keyframes.
+pub trait Animatable: Copy {
+    fn interpolate(self, other: Self, amount: f32) -> Self;
+    fn is_finite(self) -> bool;
+}
+
+impl Animatable for f32 {
+    fn interpolate(self, other: Self, amount: f32) -> Self {
+        self + (other - self) * amount
+    }
+
+    fn is_finite(self) -> bool {
+        f32::is_finite(self)
+    }
+}
+
+impl Animatable for Vector3<f32> {
+    fn interpolate(self, other: Self, amount: f32) -> Self {
+        self.lerp(other, amount)
+    }
+
+    fn is_finite(self) -> bool {
+        self.x.is_finite() && self.y.is_finite() && self.z.is_finite()
+    }
+}
+
+impl Animatable for Point3<f32> {
+    fn interpolate(self, other: Self, amount: f32) -> Self {
+        self + (other - self) * amount
+    }
+
+    fn is_finite(self) -> bool {
+        self.x.is_finite() && self.y.is_finite() && self.z.is_finite()
+    }
+}
+
+/// The value of a track at a frame.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Keyframe<T> {
+    pub frame: u32,
+    pub value: T,
+    /// Interpolation towards the next keyframe
+    #[serde(default)]
+    pub interpolation: Interpolation,
+}
+
+/// Keyframes of a value, in increasing order of their frames.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(transparent)]
+pub struct Track<T> {
+    pub keyframes: Vec<Keyframe<T>>,
+}
+
+impl<T> Default for Track<T> {
+    fn default() -> Self {
+        Self {
+            keyframes: Vec::new(),
+        }
+    }
+}
+
+impl<T: Animatable> Track<T> {
+    /// Returns the value at a frame, `None` if the track has no keyframes.
+    pub fn sample(&self, frame: u32) -> Option<T> {
+        let next = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.frame <= frame);
+        match (next.checked_sub(1), self.keyframes.get(next)) {
+            (Some(previous), Some(next)) => {
+                let previous = &self.keyframes[previous];
+                let time = (frame as f32 - previous.frame as f32)
+                    / (next.frame as f32 - previous.frame as f32);
+                let progress = previous.interpolation.progress(time);
+                Some(previous.value.interpolate(next.value, progress))
+            }
+            (Some(previous), None) => Some(self.keyframes[previous].value),
+            (None, next) => next.map(|keyframe| keyframe.value),
+        }
+    }
+
+    /// Sets `value` to the value of the track at a frame, if the track has keyframes.
+    fn apply(&self, value: &mut T, frame: u32) {
+        if let Some(sample) = self.sample(frame) {
+            *value = sample;
+        }
+    }
+}
+
+/// Tracks of the camera.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default, Debug)]
+#[serde(default)]
+pub struct CameraTracks {
+    pub position: Track<Point3<f32>>,
+    pub target: Track<Point3<f32>>,
+    /// Field of view of perspective cameras, in degrees
+    pub fov: Track<f32>,
+}
+
+/// Tracks of the parameters of a material, see [`Material`](super::material::Material).
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default, Debug)]
+#[serde(default)]
+pub struct MaterialTracks {
+    pub albedo: Track<Vector3<f32>>,
+    pub roughness: Track<f32>,
+    pub metallic: Track<f32>,
+    pub emission_color: Track<Vector3<f32>>,
+    pub emission_strength: Track<f32>,
+    pub transmission: Track<f32>,
+    pub ior: Track<f32>,
+}
+
+/// Tracks of an object of the scene.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ObjectTracks {
+    /// Index of the object in the objects of the scene
+    pub object: usize,
+    #[serde(default)]
+    pub center: Track<Point3<f32>>,
+    /// Radius of spheres and side length of cubes
+    #[serde(default)]
+    pub size: Track<f32>,
+    #[serde(default)]
+    pub material: MaterialTracks,
+}
+
+/// Tracks of the colors of the world.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default, Debug)]
+#[serde(default)]
+pub struct WorldTracks {
+    /// Top color of [`World::SkyColor`]
+    pub top_color: Track<Vector3<f32>>,
+    /// Bottom color of [`World::SkyColor`]
+    pub bottom_color: Track<Vector3<f32>>,
+    /// Color of [`World::SolidColor`]
+    pub color: Track<Vector3<f32>>,
+}
+
+/// All animation tracks of a scene.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default, Debug)]
+#[serde(default)]
+pub struct Animation {
+    pub camera: CameraTracks,
+    pub objects: Vec<ObjectTracks>,
+    pub world: WorldTracks,
+}
+
+impl Animation {
+    /// Whether the animation has no tracks, so the scene is static.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Sets the animated values of a scene to their values at a frame.
+    ///
+    /// Tracks of objects that do not exist and of world colors that the world does not have
+    /// are ignored, scene validation reports them.
+    pub fn apply(&self, scene: &mut Scene, frame: u32) {
+        let camera = &self.camera;
+        scene.camera.animate(
+            camera.position.sample(frame),
+            camera.target.sample(frame),
+            camera.fov.sample(frame),
+        );
+
+        for tracks in &self.objects {
+            let Some(object) = scene.objects.get_mut(tracks.object) else {
+                continue;
+            };
+            match &mut object.geometry {
+                Geometry::Sphere(sphere) => {
+                    tracks.center.apply(&mut sphere.center, frame);
+                    tracks.size.apply(&mut sphere.radius, frame);
+                }
+                Geometry::Cube(cube) => {
+                    tracks.center.apply(&mut cube.center, frame);
+                    tracks.size.apply(&mut cube.side_length, frame);
+                }
+            }
+
+            let (material, material_tracks) = (&mut object.material, &tracks.material);
+            material_tracks.albedo.apply(&mut material.albedo, frame);
+            material_tracks
+                .roughness
+                .apply(&mut material.roughness, frame);
+            material_tracks
+                .metallic
+                .apply(&mut material.metallic, frame);
+            material_tracks
+                .emission_color
+                .apply(&mut material.emission_color, frame);
+            material_tracks
+                .emission_strength
+                .apply(&mut material.emission_strength, frame);
+            material_tracks
+                .transmission
+                .apply(&mut material.transmission, frame);
+            material_tracks.ior.apply(&mut material.ior, frame);
+        }
+
+        match &mut scene.world {
+            World::SkyColor {
+                top_color,
+                bottom_color,
+            } => {
+                self.world.top_color.apply(top_color, frame);
+                self.world.bottom_color.apply(bottom_color, frame);
+            }
+            World::SolidColor(color) => self.world.color.apply(color, frame),
+            World::Transparent => {}
+        }
+    }
+}
+
+/// Parses a range of frames like `1-120`, or a single frame like `12`.
+pub fn parse_frames(s: &str) -> Result<RangeInclusive<u32>, String> {
+    let invalid = || format!("expected frames like `1-120` or `12`, found `{s}`");
+    let parse = |frame: &str| frame.trim().parse::<u32>().map_err(|_| invalid());
+    let (start, end) = match s.split_once('-') {
+        Some((start, end)) => (parse(start)?, parse(end)?),
+        None => (parse(s)?, parse(s)?),
+    };
+    if start > end {
+        return Err(format!("the range of frames `{s}` is empty"));
+    }
+    Ok(start..=end)
+}
+
+/// Replaces the last run of `#` in the file name of `pattern` with the frame number, padded
+/// with zeros to the length of the run, such as `out_####.png` to `out_0012.png`.
+///
+/// Returns `None` if the file name has no `#`.
+pub fn frame_path(pattern: &Path, frame: u32) -> Option<PathBuf> {
+    let file_name = pattern.file_name()?.to_str()?;
+    let end = file_name.rfind('#')? + 1;
+    let start = file_name[..end].trim_end_matches('#').len();
+    let width = end - start;
+
+    let file_name = format!(
+        "{}{frame:0width$}{}",
+        &file_name[..start],
+        &file_name[end..]
+    );
+    Some(pattern.with_file_name(file_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use cgmath::{Deg, Point3, Vector3};
+
+    use super::{
+        frame_path, parse_frames, Animation, Interpolation, Keyframe, ObjectTracks, Track,
+    };
+    use crate::scene::{camera::Projection, objects::Geometry, world::World, Scene};
+
+    const EASE_IN_OUT: Interpolation = Interpolation::Bezier {
+        x1: 0.42,
+        y1: 0.0,
+        x2: 0.58,
+        y2: 1.0,
+    };
+
+    fn track<T>(keyframes: &[(u32, T)], interpolation: Interpolation) -> Track<T>
+    where
+        T: Copy,
+    {
+        Track {
+            keyframes: keyframes
+                .iter()
+                .map(|&(frame, value)| Keyframe {
+                    frame,
+                    value,
+                    interpolation,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn linear_tracks_interpolate_between_keyframes() {
+        let track: Track<f32> = track(&[(10, 1.0), (20, 3.0), (40, 2.0)], Interpolation::Linear);
+        assert_eq!(track.sample(0), Some(1.0));
+        assert_eq!(track.sample(10), Some(1.0));
+        assert_eq!(track.sample(15), Some(2.0));
+        assert_eq!(track.sample(20), Some(3.0));
+        assert_eq!(track.sample(30), Some(2.5));
+        assert_eq!(track.sample(100), Some(2.0));
+        assert_eq!(Track::<f32>::default().sample(10), None);
+    }
+
+    #[test]
+    fn bezier_tracks_ease_between_keyframes() {
+        let track: Track<f32> = track(&[(0, 0.0), (100, 10.0)], EASE_IN_OUT);
+        assert_eq!(track.sample(0), Some(0.0));
+        assert_eq!(track.sample(100), Some(10.0));
+        assert!((track.sample(50).unwrap() - 5.0).abs() < 1e-3);
+        // Slower than linear at the start, faster in the middle
+        assert!(track.sample(10).unwrap() < 1.0);
+        assert!(track.sample(60).unwrap() - track.sample(40).unwrap() > 2.0);
+    }
+
+    #[test]
+    fn animated_cameras_get_new_matrices() {
+        let mut scene = Scene::default();
+        let start = scene.camera.position();
+        let end = Point3::new(0.0, 2.0, -4.0);
+        scene.animation.camera.position = track(&[(1, start), (11, end)], Interpolation::Linear);
+        scene.animation.camera.fov = track(&[(1, 90.0), (11, 40.0)], Interpolation::Linear);
+
+        let frame = scene.at_frame(11);
+        let mut expected = Scene::default();
+        expected.camera.set_position(end);
+        expected
+            .camera
+            .set_projection(Projection::Perspective { fov: Deg(40.0) });
+        assert_eq!(frame.camera.view_matrix(), expected.camera.view_matrix());
+        assert_eq!(frame.camera.proj_matrix(), expected.camera.proj_matrix());
+
+        assert_eq!(
+            scene.at_frame(1).camera.view_matrix(),
+            Scene::default().camera.view_matrix()
+        );
+    }
+
+    #[test]
+    fn objects_materials_and_world_colors_are_animated() {
+        let mut scene = Scene::default();
+        scene.animation = Animation {
+            objects: vec![ObjectTracks {
+                object: 2,
+                center: track(
+                    &[
+                        (0, Point3::new(0.0, 0.0, 0.0)),
+                        (10, Point3::new(10.0, 0.0, 0.0)),
+                    ],
+                    Interpolation::Linear,
+                ),
+                size: track(&[(0, 1.0)], Interpolation::Linear),
+                material: Default::default(),
+            }],
+            ..Default::default()
+        };
+        scene.animation.objects[0].material.emission_strength =
+            track(&[(0, 0.0), (10, 20.0)], Interpolation::Linear);
+        scene.animation.world.top_color = track(
+            &[
+                (0, Vector3::new(0.0, 0.0, 0.0)),
+                (10, Vector3::new(1.0, 0.5, 0.0)),
+            ],
+            Interpolation::Linear,
+        );
+
+        let frame = scene.at_frame(5);
+        let Geometry::Cube(cube) = &frame.objects[2].geometry else {
+            panic!("expected a cube");
+        };
+        assert_eq!(cube.center, Point3::new(5.0, 0.0, 0.0));
+        assert_eq!(cube.side_length, 1.0);
+        assert_eq!(frame.objects[2].material.emission_strength, 10.0);
+        assert!(matches!(
+            frame.world,
+            World::SkyColor { top_color, .. } if top_color == Vector3::new(0.5, 0.25, 0.0)
+        ));
+        // Values without tracks keep the values of the scene
+        assert_eq!(
+            frame.objects[1].material.roughness,
+            scene.objects[1].material.roughness
+        );
+    }
+
+    #[test]
+    fn animations_are_stored_in_scene_files() {
+        let mut scene = Scene::default();
+        let json = scene.to_json().unwrap();
+        assert!(!json.contains("animation"));
+
+        scene.animation.camera.target = track(
+            &[
+                (1, Point3::new(0.0, 0.0, 0.0)),
+                (24, Point3::new(1.0, 0.0, 0.0)),
+            ],
+            EASE_IN_OUT,
+        );
+        let loaded = Scene::from_json(&scene.to_json().unwrap()).unwrap();
+        assert_eq!(loaded.animation, scene.animation);
+    }
+
+    #[test]
+    fn frames_are_parsed_as_inclusive_ranges() {
+        assert_eq!(parse_frames("1-120"), Ok(1..=120));
+        assert_eq!(parse_frames("12"), Ok(12..=12));
+        assert!(parse_frames("20-10").is_err());
+        assert!(parse_frames("1..5").is_err());
+    }
+
+    #[test]
+    fn frame_paths_replace_the_hashes() {
+        assert_eq!(
+            frame_path(Path::new("renders/out_####.png"), 12),
+            Some(PathBuf::from("renders/out_0012.png"))
+        );
+        assert_eq!(
+            frame_path(Path::new("frame#.png"), 120),
+            Some(PathBuf::from("frame120.png"))
+        );
+        assert_eq!(frame_path(Path::new("out.png"), 1), None);
+    }
+}
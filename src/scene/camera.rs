@@ -1,16 +1,23 @@
 use cgmath::{
-    Deg, InnerSpace, Matrix4, MetricSpace, Point3, SquareMatrix, Transform, Vector2, Vector3,
-    Vector4,
+    Array, Deg, InnerSpace, Matrix4, MetricSpace, Point3, SquareMatrix, Transform, Vector2,
+    Vector3, Vector4,
 };
 use serde::{Deserialize, Serialize};
 
+use super::render_settings::Resolution;
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
 pub enum Projection {
     Perspective { fov: Deg<f32> },
     Orthographic { size: f32 },
 }
 
+/// A camera with its view and projection matrices.
+///
+/// Only the parameters of the camera are serialized, the matrices are derived from them when
+/// it is loaded. The resolution is part of the scene's render settings instead.
 #[derive(Serialize, Deserialize, Clone)]
+#[serde(from = "CameraParameters", into = "CameraParameters")]
 pub struct Camera {
     position: Point3<f32>,
     target: Point3<f32>,
@@ -29,6 +36,46 @@ pub struct Camera {
     inverse_proj_matrix: Matrix4<f32>,
 }
 
+/// The serialized form of a camera.
+#[derive(Serialize, Deserialize)]
+struct CameraParameters {
+    position: Point3<f32>,
+    target: Point3<f32>,
+    up: Vector3<f32>,
+    projection: Projection,
+    near_clip: f32,
+    far_clip: f32,
+}
+
+impl From<CameraParameters> for Camera {
+    fn from(parameters: CameraParameters) -> Self {
+        let resolution = Resolution::default();
+        Self::new(
+            parameters.position,
+            parameters.target,
+            parameters.up,
+            resolution.width,
+            resolution.height,
+            parameters.near_clip,
+            parameters.far_clip,
+            parameters.projection,
+        )
+    }
+}
+
+impl From<Camera> for CameraParameters {
+    fn from(camera: Camera) -> Self {
+        Self {
+            position: camera.position,
+            target: camera.target,
+            up: camera.up,
+            projection: camera.projection,
+            near_clip: camera.near_clip,
+            far_clip: camera.far_clip,
+        }
+    }
+}
+
 impl Camera {
     pub fn new(
         position: Point3<f32>,
@@ -207,26 +254,66 @@ impl Camera {
         self.inverse_proj_matrix
     }
 
+    /// Sets the animated parameters of the camera at a frame, see
+    /// [`Animation`](super::animation::Animation). The field of view only applies to
+    /// perspective cameras.
+    pub(super) fn animate(
+        &mut self,
+        position: Option<Point3<f32>>,
+        target: Option<Point3<f32>>,
+        fov: Option<f32>,
+    ) {
+        if let Some(position) = position {
+            self.position = position;
+        }
+        if let Some(target) = target {
+            self.target = target;
+        }
+        if let (Some(fov), Projection::Perspective { .. }) = (fov, self.projection) {
+            self.projection = Projection::Perspective { fov: Deg(fov) };
+        }
+        self.update_matrices();
+    }
+
     fn update_matrices(&mut self) {
         self.view_matrix = Matrix4::look_at_lh(self.position, self.target, self.up);
 
+        // Cameras with invalid parameters get NaN matrices, scene validation reports them
+        let invalid = Matrix4::from_cols(
+            Vector4::from_value(f32::NAN),
+            Vector4::from_value(f32::NAN),
+            Vector4::from_value(f32::NAN),
+            Vector4::from_value(f32::NAN),
+        );
+        self.proj_matrix = self.projection_matrix().unwrap_or(invalid);
+        self.inverse_view_matrix = self.view_matrix.invert().unwrap_or(invalid);
+        self.inverse_proj_matrix = self.proj_matrix.invert().unwrap_or(invalid);
+    }
+
+    /// Computes the projection matrix, if the parameters describe a valid projection.
+    fn projection_matrix(&self) -> Option<Matrix4<f32>> {
         let aspect_ratio = self.aspect_ratio();
         let near = self.near_clip;
         let far = self.far_clip;
-        self.proj_matrix = match self.projection {
-            Projection::Perspective { fov } => cgmath::perspective(fov, aspect_ratio, near, far),
-            Projection::Orthographic { size } => cgmath::ortho(
+        match self.projection {
+            Projection::Perspective { fov } => {
+                // Checked by `cgmath::perspective` with assertions
+                let valid = fov.0 > 0.0
+                    && fov.0 < 180.0
+                    && aspect_ratio.is_normal()
+                    && near > 0.0
+                    && far > 0.0
+                    && near != far;
+                valid.then(|| cgmath::perspective(fov, aspect_ratio, near, far))
+            }
+            Projection::Orthographic { size } => Some(cgmath::ortho(
                 -size * aspect_ratio,
                 size * aspect_ratio,
                 -size,
                 size,
                 near,
                 far,
-            ),
-        };
-
-        // FIXME: what to do when the determinant is 0 (inverse matrix does not exist)
-        self.inverse_view_matrix = self.view_matrix.invert().unwrap();
-        self.inverse_proj_matrix = self.proj_matrix.invert().unwrap();
+            )),
+        }
     }
 }
@@ -0,0 +1,445 @@
+//! The scene file format (`.rscn`).
+//!
+//! Scene files are JSON documents with a `version` field. Only the parameters of a scene are
+//! stored, derived state such as the camera matrices is recomputed when a file is loaded.
+//!
+//! Files of older versions are read with their own schema and migrated to the current one.
+//! When the format changes, move the schema of the current version into a module of its own,
+//! convert it to the new schema there and bump [`VERSION`].
+
+use std::{error::Error, fmt};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::{
+    animation::Animation, camera::Camera, objects::Object, render_settings::RenderSettings,
+    world::World, Scene,
+};
+
+pub mod blender;
+mod v0;
+
+/// Version of the scene files written by this version of Raydar.
+pub const VERSION: u32 = 1;
+
+/// Error when reading a scene file.
+#[derive(Debug)]
+pub enum SceneFileError {
+    /// The file is not valid JSON or does not match the schema of its version.
+    Invalid {
+        /// Path of the field the error was found in, such as `objects[2].material.roughness`,
+        /// empty for the top level of the file
+        path: String,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// The file was written by a newer version of Raydar.
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for SceneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Invalid {
+                path,
+                line,
+                column,
+                message,
+            } => {
+                if !path.is_empty() {
+                    write!(f, "`{path}` ")?;
+                }
+                write!(f, "at line {line}, column {column}: {message}")
+            }
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "scene file version {version} is newer than the supported version {VERSION}"
+            ),
+        }
+    }
+}
+
+impl Error for SceneFileError {}
+
+/// The fields every scene file starts with, used to pick the schema to read it with.
+#[derive(Deserialize)]
+struct Header {
+    /// Missing in files written before the format was versioned
+    #[serde(default)]
+    version: u32,
+}
+
+/// The current version of the scene file schema.
+#[derive(Deserialize)]
+struct SceneFile {
+    camera: Camera,
+    world: World,
+    objects: Vec<Object>,
+    #[serde(default)]
+    render_settings: RenderSettings,
+    #[serde(default)]
+    animation: Animation,
+}
+
+#[derive(Serialize)]
+struct SceneFileRef<'a> {
+    version: u32,
+    camera: &'a Camera,
+    world: &'a World,
+    objects: &'a [Object],
+    render_settings: &'a RenderSettings,
+    /// Left out of static scenes
+    #[serde(skip_serializing_if = "is_static")]
+    animation: &'a Animation,
+}
+
+fn is_static(animation: &&Animation) -> bool {
+    animation.is_empty()
+}
+
+impl<'a> SceneFileRef<'a> {
+    fn new(scene: &'a Scene) -> Self {
+        Self {
+            version: VERSION,
+            camera: &scene.camera,
+            world: &scene.world,
+            objects: &scene.objects,
+            render_settings: &scene.render_settings,
+            animation: &scene.animation,
+        }
+    }
+}
+
+impl From<SceneFile> for Scene {
+    fn from(file: SceneFile) -> Self {
+        Self {
+            camera: file.camera,
+            world: file.world,
+            objects: file.objects,
+            render_settings: file.render_settings,
+            animation: file.animation,
+        }
+    }
+}
+
+/// Reads a scene file of any supported version.
+pub fn from_json(json: &str) -> Result<Scene, SceneFileError> {
+    let Header { version } = parse(json)?;
+    let mut scene: Scene = match version {
+        0 => parse::<v0::SceneFile>(json)?.into(),
+        VERSION => parse::<SceneFile>(json)?.into(),
+        version => return Err(SceneFileError::UnsupportedVersion(version)),
+    };
+    scene.apply_resolution();
+    Ok(scene)
+}
+
+/// Writes a scene file of the current version.
+pub fn to_json(scene: &Scene) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(&SceneFileRef::new(scene))
+}
+
+/// Converts a scene to the JSON document of its scene file.
+pub(super) fn to_value(scene: &Scene) -> serde_json::Result<serde_json::Value> {
+    serde_json::to_value(SceneFileRef::new(scene))
+}
+
+/// Reads a scene from the JSON document of a scene file of the current version.
+pub(super) fn from_value(value: serde_json::Value) -> serde_json::Result<Scene> {
+    let mut scene: Scene = serde_json::from_value::<SceneFile>(value)?.into();
+    scene.apply_resolution();
+    Ok(scene)
+}
+
+fn parse<T: DeserializeOwned>(json: &str) -> Result<T, SceneFileError> {
+    serde_json::from_str(json).map_err(|err| {
+        let (line, column) = (err.line(), err.column());
+        let message = err.to_string();
+        let message = message
+            .strip_suffix(&format!(" at line {line} column {column}"))
+            .unwrap_or(&message)
+            .to_string();
+
+        SceneFileError::Invalid {
+            path: field_path(json, line, column),
+            line,
+            column,
+            message,
+        }
+    })
+}
+
+/// A JSON object or array that encloses a position in a document.
+enum Container {
+    Object {
+        key: String,
+        /// Whether the position is inside the value of `key`, rather than the key itself
+        in_value: bool,
+    },
+    Array {
+        index: usize,
+    },
+}
+
+/// Finds the path of the field at a (1-based) line and column of a JSON document.
+///
+/// Errors of `serde_json` point at the end of the offending value, or at the end of the
+/// object a field is missing from.
+fn field_path(json: &str, line: usize, column: usize) -> String {
+    let mut containers: Vec<Container> = Vec::new();
+    let mut bytes = json.bytes();
+    let (mut current_line, mut current_column) = (1, 0);
+
+    let mut advance = |bytes: &mut std::str::Bytes| {
+        let byte = bytes.next()?;
+        if byte == b'\n' {
+            current_line += 1;
+            current_column = 0;
+        } else {
+            current_column += 1;
+        }
+        let done = current_line > line || (current_line == line && current_column >= column);
+        Some((byte, done))
+    };
+
+    while let Some((byte, mut done)) = advance(&mut bytes) {
+        match byte {
+            b'{' => containers.push(Container::Object {
+                key: String::new(),
+                in_value: false,
+            }),
+            b'[' => containers.push(Container::Array { index: 0 }),
+            b'}' | b']' => {
+                containers.pop();
+            }
+            b':' => {
+                if let Some(Container::Object { in_value, .. }) = containers.last_mut() {
+                    *in_value = true;
+                }
+            }
+            b',' => match containers.last_mut() {
+                Some(Container::Object { in_value, .. }) => *in_value = false,
+                Some(Container::Array { index }) => *index += 1,
+                None => {}
+            },
+            b'"' => {
+                let mut string = Vec::new();
+                let mut escaped = false;
+                while !done {
+                    let Some((byte, string_done)) = advance(&mut bytes) else {
+                        break;
+                    };
+                    done = string_done;
+                    match byte {
+                        b'"' if !escaped => break,
+                        b'\\' if !escaped => escaped = true,
+                        _ => {
+                            escaped = false;
+                            string.push(byte);
+                        }
+                    }
+                }
+
+                if let Some(Container::Object { key, in_value }) = containers.last_mut() {
+                    if !*in_value {
+                        *key = String::from_utf8_lossy(&string).into_owned();
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        if done {
+            break;
+        }
+    }
+
+    let mut path = String::new();
+    for container in &containers {
+        match container {
+            Container::Object {
+                key,
+                in_value: true,
+            } => {
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(key);
+            }
+            Container::Object { .. } => {}
+            Container::Array { index } => path.push_str(&format!("[{index}]")),
+        }
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{from_json, to_json, SceneFileError, VERSION};
+    use crate::scene::{render_settings::Resolution, Scene};
+
+    /// A scene file in the format used before scene files were versioned, with a camera
+    /// resolution of 320x240.
+    fn unversioned_scene(render_settings: Option<serde_json::Value>) -> String {
+        let mut scene: serde_json::Value =
+            serde_json::from_str(&to_json(&Scene::default()).unwrap()).unwrap();
+        let object = scene.as_object_mut().unwrap();
+        object.remove("version");
+        object.remove("render_settings");
+        if let Some(render_settings) = render_settings {
+            object.insert("render_settings".to_string(), render_settings);
+        }
+
+        let camera = scene["camera"].as_object_mut().unwrap();
+        camera.insert("resolution_x".to_string(), 320.into());
+        camera.insert("resolution_y".to_string(), 240.into());
+        // Stale matrices, which are ignored
+        for matrix in [
+            "view_matrix",
+            "proj_matrix",
+            "inverse_view_matrix",
+            "inverse_proj_matrix",
+        ] {
+            let column = json!({ "x": 0.0, "y": 0.0, "z": 0.0, "w": 0.0 });
+            camera.insert(
+                matrix.to_string(),
+                json!({ "x": column, "y": column, "z": column, "w": column }),
+            );
+        }
+
+        serde_json::to_string_pretty(&scene).unwrap()
+    }
+
+    fn parse_error(json: &str) -> (String, usize, String) {
+        match from_json(json) {
+            Err(SceneFileError::Invalid {
+                path,
+                line,
+                message,
+                ..
+            }) => (path, line, message),
+            Err(err) => panic!("unexpected error: {err}"),
+            Ok(_) => panic!("scene file was parsed"),
+        }
+    }
+
+    #[test]
+    fn scene_files_omit_derived_state() {
+        let json = to_json(&Scene::default()).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value["version"], VERSION);
+        let camera = value["camera"].as_object().unwrap();
+        assert!(!camera.contains_key("view_matrix"));
+        assert!(!camera.contains_key("inverse_proj_matrix"));
+        assert!(!camera.contains_key("resolution_x"));
+    }
+
+    #[test]
+    fn scene_files_round_trip() {
+        let mut scene = Scene::default();
+        scene.render_settings.resolution = Resolution::new(64, 32);
+        scene.apply_resolution();
+        let loaded = from_json(&to_json(&scene).unwrap()).unwrap();
+
+        assert_eq!(loaded.render_settings, scene.render_settings);
+        assert_eq!(loaded.camera.resolution_x(), 64);
+        assert_eq!(loaded.camera.resolution_y(), 32);
+        assert_eq!(loaded.camera.view_matrix(), scene.camera.view_matrix());
+        assert_eq!(loaded.camera.proj_matrix(), scene.camera.proj_matrix());
+    }
+
+    #[test]
+    fn camera_matrices_are_recomputed_on_load() {
+        let mut value: serde_json::Value =
+            serde_json::from_str(&to_json(&Scene::default()).unwrap()).unwrap();
+        value["camera"]["position"] = json!({ "x": 0.0, "y": 0.0, "z": -5.0 });
+        let scene = from_json(&value.to_string()).unwrap();
+
+        let mut expected = Scene::default();
+        expected
+            .camera
+            .set_position(cgmath::Point3::new(0.0, 0.0, -5.0));
+        assert_eq!(scene.camera.view_matrix(), expected.camera.view_matrix());
+    }
+
+    #[test]
+    fn unversioned_scenes_are_migrated() {
+        let scene = from_json(&unversioned_scene(None)).unwrap();
+        assert_eq!(scene.render_settings.resolution, Resolution::new(320, 240));
+        assert_eq!(scene.camera.resolution_x(), 320);
+        assert_eq!(
+            scene.camera.view_matrix(),
+            Scene::default().camera.view_matrix()
+        );
+
+        // Render settings without a resolution keep the one of the camera
+        let json = unversioned_scene(Some(json!({ "samples": 8 })));
+        let scene = from_json(&json).unwrap();
+        assert_eq!(scene.render_settings.resolution, Resolution::new(320, 240));
+        assert_eq!(scene.render_settings.samples, 8);
+
+        let json = unversioned_scene(Some(json!({ "resolution": { "width": 64, "height": 32 } })));
+        let scene = from_json(&json).unwrap();
+        assert_eq!(scene.render_settings.resolution, Resolution::new(64, 32));
+        assert_eq!(scene.camera.resolution_y(), 32);
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let json = json!({ "version": VERSION + 1 }).to_string();
+        assert!(matches!(
+            from_json(&json),
+            Err(SceneFileError::UnsupportedVersion(version)) if version == VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn parse_errors_report_the_path_and_line_of_the_field() {
+        let mut value: serde_json::Value =
+            serde_json::from_str(&to_json(&Scene::default()).unwrap()).unwrap();
+        value["objects"][1]["material"]["roughness"] = "smooth".into();
+        let json = serde_json::to_string_pretty(&value).unwrap();
+        let line = json
+            .lines()
+            .position(|line| line.contains("\"smooth\""))
+            .unwrap()
+            + 1;
+
+        let (path, error_line, message) = parse_error(&json);
+        assert_eq!(path, "objects[1].material.roughness");
+        assert_eq!(error_line, line);
+        assert!(message.contains("expected f32"), "{message}");
+    }
+
+    #[test]
+    fn parse_errors_report_the_object_a_field_is_missing_from() {
+        let mut value: serde_json::Value =
+            serde_json::from_str(&to_json(&Scene::default()).unwrap()).unwrap();
+        value["objects"][2]["material"]
+            .as_object_mut()
+            .unwrap()
+            .remove("albedo");
+
+        let (path, _, message) = parse_error(&serde_json::to_string_pretty(&value).unwrap());
+        assert_eq!(path, "objects[2].material");
+        assert!(message.contains("missing field `albedo`"), "{message}");
+    }
+
+    #[test]
+    fn parse_errors_in_unversioned_scenes_report_the_field() {
+        let json = unversioned_scene(None).replacen("\"near_clip\": 0.01", "\"near_clip\": []", 1);
+        let (path, _, _) = parse_error(&json);
+        assert_eq!(path, "camera.near_clip");
+    }
+
+    #[test]
+    fn syntax_errors_report_the_line() {
+        let (path, line, _) = parse_error("{\n  \"version\": 1,\n  \"camera\": {,\n}");
+        assert_eq!(path, "camera");
+        assert_eq!(line, 3);
+    }
+}
@@ -0,0 +1,71 @@
+//! Scene files written before the format was versioned.
+//!
+//! Their cameras store the output resolution along with the cached view and projection
+//! matrices, which are ignored. Render settings, if present, may lack a resolution.
+
+use cgmath::{Point3, Vector3};
+use serde::Deserialize;
+
+use crate::scene::{
+    camera::{self, Projection},
+    objects::Object,
+    render_settings::{self, Resolution},
+    world::World,
+    Scene,
+};
+
+#[derive(Deserialize)]
+pub(super) struct SceneFile {
+    camera: Camera,
+    world: World,
+    objects: Vec<Object>,
+    #[serde(default)]
+    render_settings: RenderSettings,
+}
+
+#[derive(Deserialize)]
+struct Camera {
+    position: Point3<f32>,
+    target: Point3<f32>,
+    up: Vector3<f32>,
+    resolution_x: u32,
+    resolution_y: u32,
+    projection: Projection,
+    near_clip: f32,
+    far_clip: f32,
+}
+
+#[derive(Deserialize, Default)]
+struct RenderSettings {
+    resolution: Option<Resolution>,
+    #[serde(flatten)]
+    settings: render_settings::RenderSettings,
+}
+
+impl From<SceneFile> for Scene {
+    fn from(file: SceneFile) -> Self {
+        let camera = file.camera;
+        let mut render_settings = file.render_settings.settings;
+        render_settings.resolution = file
+            .render_settings
+            .resolution
+            .unwrap_or(Resolution::new(camera.resolution_x, camera.resolution_y));
+
+        Self {
+            camera: camera::Camera::new(
+                camera.position,
+                camera.target,
+                camera.up,
+                camera.resolution_x,
+                camera.resolution_y,
+                camera.near_clip,
+                camera.far_clip,
+                camera.projection,
+            ),
+            world: file.world,
+            objects: file.objects,
+            render_settings,
+            animation: Default::default(),
+        }
+    }
+}
@@ -27,6 +27,11 @@ impl Default for Material {
 }
 
 impl Material {
+    /// Whether the material emits any light.
+    pub fn is_emissive(&self) -> bool {
+        self.emission_strength > 0.0 && self.emission_color != Vector3::new(0.0, 0.0, 0.0)
+    }
+
     pub fn with_albedo(albedo: Vector3<f32>) -> Self {
         Self {
             albedo,
@@ -1,20 +1,99 @@
+use animation::Animation;
 use camera::{Camera, Projection};
 use cgmath::{Deg, Point3, Vector3};
+use format::SceneFileError;
 use material::Material;
 use objects::{Cube, Geometry, Object, Sphere};
-use serde::{Deserialize, Serialize};
+use render_settings::RenderSettings;
+use validation::Diagnostic;
 use world::World;
 
+use crate::renderer::bvh::Aabb;
+
+pub mod animation;
 pub mod camera;
+pub mod format;
 pub mod material;
 pub mod objects;
+pub mod overrides;
+pub mod render_settings;
+pub mod validation;
 pub mod world;
 
-#[derive(Serialize, Deserialize, Clone)]
+/// A scene, see [`format`] for how scenes are stored in scene files.
+#[derive(Clone)]
 pub struct Scene {
     pub camera: Camera,
     pub world: World,
     pub objects: Vec<Object>,
+    pub render_settings: RenderSettings,
+    /// Keyframes of animated values, empty for static scenes
+    pub animation: Animation,
+}
+
+impl Scene {
+    /// Reads a scene file of any supported version.
+    pub fn from_json(json: &str) -> Result<Self, SceneFileError> {
+        format::from_json(json)
+    }
+
+    /// Writes a scene file of the current version.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        format::to_json(self)
+    }
+
+    /// Returns the scene with its animated values set to their values at a frame.
+    pub fn at_frame(&self, frame: u32) -> Self {
+        let mut scene = self.clone();
+        self.animation.apply(&mut scene, frame);
+        scene
+    }
+
+    /// Checks the scene for invalid or suspicious values, see [`validation::validate`].
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        validation::validate(self)
+    }
+
+    /// Returns the bounding box of all objects of the scene.
+    pub fn bounds(&self) -> Aabb {
+        self.objects.iter().fold(Aabb::empty(), |bounds, object| {
+            bounds.union(&object.geometry.bounds())
+        })
+    }
+
+    /// Hashes the parts of the scene that determine the radiance of its samples.
+    ///
+    /// Render settings that only decide how many samples are taken and how the result is
+    /// saved are left out, so that a render can be resumed with more samples or merged with
+    /// renders of another seed. The hash is stable across runs and versions of Rust.
+    pub fn content_hash(&self) -> u64 {
+        let settings = &self.render_settings;
+        let scene = Self {
+            render_settings: RenderSettings {
+                resolution: settings.resolution,
+                bounces: settings.bounces,
+                clamping: settings.clamping,
+                russian_roulette: settings.russian_roulette,
+                russian_roulette_min_depth: settings.russian_roulette_min_depth,
+                filter: settings.filter,
+                ..Default::default()
+            },
+            ..self.clone()
+        };
+        let json = scene.to_json().expect("scenes can always be serialized");
+
+        // 64-bit FNV-1a
+        json.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+        })
+    }
+
+    /// Sets the resolution of the camera to the one of the render settings.
+    pub fn apply_resolution(&mut self) {
+        let resolution = self.render_settings.resolution;
+        self.camera.set_resolution_x(resolution.width);
+        self.camera.set_resolution_y(resolution.height);
+    }
 }
 
 impl Default for Scene {
@@ -63,6 +142,8 @@ impl Default for Scene {
                     material: Material::with_emission(Vector3::new(0.8, 0.5, 0.2), 30.0),
                 },
             ],
+            render_settings: RenderSettings::default(),
+            animation: Animation::default(),
         }
     }
 }
@@ -1,7 +1,7 @@
-use cgmath::Point3;
+use cgmath::{Point3, Vector3};
 use serde::{Deserialize, Serialize};
 
-use crate::scene::material::Material;
+use crate::{renderer::bvh::Aabb, scene::material::Material};
 
 #[derive(Serialize, Deserialize, Clone)]
 pub enum Geometry {
@@ -9,6 +9,21 @@ pub enum Geometry {
     Cube(Cube),
 }
 
+impl Geometry {
+    pub fn bounds(&self) -> Aabb {
+        let (center, half_extent) = match self {
+            Geometry::Sphere(sphere) => (sphere.center, sphere.radius.abs()),
+            Geometry::Cube(cube) => (cube.center, cube.side_length.abs() * 0.5),
+        };
+        let half_extent = Vector3::new(half_extent, half_extent, half_extent);
+
+        Aabb {
+            min: center - half_extent,
+            max: center + half_extent,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Object {
     pub geometry: Geometry,
@@ -0,0 +1,310 @@
+//! Overrides of single values of a scene, such as `objects[2].material.roughness=0.1`.
+//!
+//! Paths address fields of the scene file, see [`super::format`]. Enum variants are addressed
+//! by their name, like `camera.projection.Perspective.fov`.
+
+use std::{error::Error, fmt, str::FromStr};
+
+use serde_json::Value;
+
+use super::{format, Scene};
+
+/// A value to set at a path of the scene file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Override {
+    pub path: String,
+    /// The new value as JSON, or a plain string
+    pub value: String,
+}
+
+impl FromStr for Override {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (path, value) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected `PATH=VALUE`, found `{s}`"))?;
+        Ok(Self {
+            path: path.trim().to_string(),
+            value: value.trim().to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Override {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.path, self.value)
+    }
+}
+
+#[derive(Debug)]
+pub enum OverrideError {
+    /// The path is not of the form `field.field[index]`.
+    InvalidPath(String),
+    /// A field of the path does not exist.
+    UnknownField {
+        path: String,
+        field: String,
+        /// The fields that exist at that point of the path
+        expected: Vec<String>,
+    },
+    /// An index of the path is out of bounds.
+    IndexOutOfBounds {
+        path: String,
+        index: usize,
+        len: usize,
+    },
+    /// The value does not have the type of the field.
+    InvalidValue { path: String, message: String },
+}
+
+impl fmt::Display for OverrideError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidPath(path) => write!(f, "`{path}` is not a valid path"),
+            Self::UnknownField {
+                path,
+                field,
+                expected,
+            } => {
+                if path.is_empty() {
+                    write!(f, "unknown field `{field}`")?;
+                } else {
+                    write!(f, "unknown field `{field}` in `{path}`")?;
+                }
+                if expected.is_empty() {
+                    Ok(())
+                } else {
+                    write!(f, ", expected one of `{}`", expected.join("`, `"))
+                }
+            }
+            Self::IndexOutOfBounds { path, index, len } => {
+                write!(
+                    f,
+                    "index {index} is out of bounds, `{path}` has {len} elements"
+                )
+            }
+            Self::InvalidValue { path, message } => {
+                write!(f, "invalid value for `{path}`: {message}")
+            }
+        }
+    }
+}
+
+impl Error for OverrideError {}
+
+enum Segment {
+    Field(String),
+    Index(usize),
+}
+
+fn parse_path(path: &str) -> Result<Vec<Segment>, OverrideError> {
+    let invalid = || OverrideError::InvalidPath(path.to_string());
+
+    let mut segments = Vec::new();
+    for part in path.split('.') {
+        let (field, mut indices) = part
+            .find('[')
+            .map_or((part, ""), |start| part.split_at(start));
+        if field.is_empty() || !field.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return Err(invalid());
+        }
+        segments.push(Segment::Field(field.to_string()));
+
+        while !indices.is_empty() {
+            let (index, rest) = indices
+                .strip_prefix('[')
+                .and_then(|indices| indices.split_once(']'))
+                .ok_or_else(invalid)?;
+            segments.push(Segment::Index(index.parse().map_err(|_| invalid())?));
+            indices = rest;
+        }
+    }
+    Ok(segments)
+}
+
+/// Finds the value at a path, checking that every field and index along it exists.
+fn lookup<'a>(root: &'a mut Value, path: &str) -> Result<&'a mut Value, OverrideError> {
+    let mut value = root;
+    let mut current_path = String::new();
+    for segment in parse_path(path)? {
+        match segment {
+            Segment::Field(field) => {
+                let Value::Object(object) = value else {
+                    return Err(OverrideError::UnknownField {
+                        path: current_path,
+                        field,
+                        expected: Vec::new(),
+                    });
+                };
+                if !object.contains_key(&field) {
+                    return Err(OverrideError::UnknownField {
+                        path: current_path,
+                        field,
+                        expected: object.keys().cloned().collect(),
+                    });
+                }
+
+                if !current_path.is_empty() {
+                    current_path.push('.');
+                }
+                current_path.push_str(&field);
+                value = &mut object[&field];
+            }
+            Segment::Index(index) => {
+                let Value::Array(array) = value else {
+                    return Err(OverrideError::InvalidValue {
+                        path: current_path,
+                        message: "cannot index a value that is not a list".to_string(),
+                    });
+                };
+                if index >= array.len() {
+                    return Err(OverrideError::IndexOutOfBounds {
+                        path: current_path,
+                        index,
+                        len: array.len(),
+                    });
+                }
+
+                current_path.push_str(&format!("[{index}]"));
+                value = &mut array[index];
+            }
+        }
+    }
+    Ok(value)
+}
+
+/// Applies overrides to a scene, in order.
+///
+/// The scene is left unchanged if any of the overrides fails.
+pub fn apply(scene: &mut Scene, overrides: &[Override]) -> Result<(), OverrideError> {
+    if overrides.is_empty() {
+        return Ok(());
+    }
+
+    let invalid_scene = |err: serde_json::Error| OverrideError::InvalidValue {
+        path: String::new(),
+        message: err.to_string(),
+    };
+    let mut document = format::to_value(scene).map_err(invalid_scene)?;
+
+    for scene_override in overrides {
+        // Values that are not valid JSON are taken as strings, so that paths and enum
+        // variants do not need to be quoted
+        let value = serde_json::from_str(&scene_override.value)
+            .unwrap_or_else(|_| Value::String(scene_override.value.clone()));
+        *lookup(&mut document, &scene_override.path)? = value;
+
+        // Check the type of the value right away, to blame the right override
+        format::from_value(document.clone()).map_err(|err| OverrideError::InvalidValue {
+            path: scene_override.path.clone(),
+            message: err.to_string(),
+        })?;
+    }
+
+    *scene = format::from_value(document).map_err(invalid_scene)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Deg;
+
+    use super::{apply, Override, OverrideError};
+    use crate::scene::{
+        camera::Projection,
+        render_settings::{Resolution, Tonemapping},
+        Scene,
+    };
+
+    fn apply_overrides(overrides: &[&str]) -> Result<Scene, OverrideError> {
+        let overrides: Vec<Override> = overrides
+            .iter()
+            .map(|scene_override| scene_override.parse().unwrap())
+            .collect();
+        let mut scene = Scene::default();
+        apply(&mut scene, &overrides)?;
+        Ok(scene)
+    }
+
+    fn override_error(overrides: &[&str]) -> OverrideError {
+        apply_overrides(overrides)
+            .err()
+            .expect("overrides should be rejected")
+    }
+
+    #[test]
+    fn overrides_set_values() {
+        let scene = apply_overrides(&[
+            "camera.projection.Perspective.fov=45",
+            "objects[2].material.roughness=0.1",
+            "objects[0].material.albedo.y = 0.5",
+            "render_settings.resolution={\"width\": 64, \"height\": 32}",
+            "render_settings.tonemapping=Aces",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            scene.camera.projection(),
+            Projection::Perspective { fov: Deg(45.0) }
+        );
+        assert_eq!(scene.objects[2].material.roughness, 0.1);
+        assert_eq!(scene.objects[0].material.albedo.y, 0.5);
+        assert_eq!(scene.render_settings.resolution, Resolution::new(64, 32));
+        assert_eq!(scene.camera.resolution_x(), 64);
+        assert_eq!(scene.render_settings.tonemapping, Tonemapping::Aces);
+    }
+
+    #[test]
+    fn unknown_paths_are_rejected() {
+        let err = override_error(&["objects[0].material.roughnes=0.1"]);
+        assert!(
+            matches!(&err, OverrideError::UnknownField { path, field, expected }
+                if path == "objects[0].material"
+                    && field == "roughnes"
+                    && expected.contains(&"roughness".to_string())),
+            "{err}"
+        );
+
+        let err = override_error(&["camera.projection.Orthographic.size=2"]);
+        assert!(
+            matches!(&err, OverrideError::UnknownField { path, .. } if path == "camera.projection"),
+            "{err}"
+        );
+
+        let err = override_error(&["objects[3].material.roughness=0.1"]);
+        assert!(
+            matches!(
+                err,
+                OverrideError::IndexOutOfBounds {
+                    index: 3,
+                    len: 3,
+                    ..
+                }
+            ),
+            "{err}"
+        );
+
+        let err = override_error(&["objects[x]=1"]);
+        assert!(matches!(err, OverrideError::InvalidPath(_)), "{err}");
+    }
+
+    #[test]
+    fn values_of_the_wrong_type_are_rejected() {
+        let err = override_error(&[
+            "objects[2].material.roughness=0.1",
+            "objects[1].material.roughness=rough",
+        ]);
+        assert!(
+            matches!(&err, OverrideError::InvalidValue { path, .. }
+                if path == "objects[1].material.roughness"),
+            "{err}"
+        );
+        assert!(err.to_string().contains("expected f32"), "{err}");
+    }
+
+    #[test]
+    fn overrides_must_have_a_value() {
+        assert!("camera.near_clip".parse::<Override>().is_err());
+    }
+}
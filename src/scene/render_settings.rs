@@ -0,0 +1,286 @@
+use std::{fmt, path::PathBuf, str::FromStr, time::Duration};
+
+use cgmath::Vector3;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// Settings that control how a scene is rendered, stored alongside it in the scene file.
+///
+/// Together with the scene they fully describe a render, command line arguments only
+/// override them.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct RenderSettings {
+    /// Resolution of the rendered image
+    pub resolution: Resolution,
+    /// Number of samples per pixel
+    pub samples: u32,
+    pub bounces: BounceLimits,
+    pub clamping: Clamping,
+    /// Seed for the random number generators, renders with the same seed are reproducible
+    pub seed: u32,
+    /// Randomly terminate paths that carry little light, weighting the surviving ones so that
+    /// the result stays unbiased
+    pub russian_roulette: bool,
+    /// Number of bounces a path makes before Russian roulette may terminate it
+    pub russian_roulette_min_depth: u32,
+    pub filter: Filter,
+    pub tonemapping: Tonemapping,
+    /// Wall time budget of the render in seconds, after which no more samples are started
+    pub time_limit: Option<f32>,
+    /// Path the rendered image is saved to, relative to the working directory
+    pub output: PathBuf,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            resolution: Resolution::default(),
+            samples: 1024,
+            bounces: BounceLimits::default(),
+            clamping: Clamping::default(),
+            seed: 0,
+            russian_roulette: true,
+            russian_roulette_min_depth: 3,
+            filter: Filter::default(),
+            tonemapping: Tonemapping::default(),
+            time_limit: None,
+            output: PathBuf::from("output.png"),
+        }
+    }
+}
+
+/// Resolution of an image in pixels.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Resolution {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { width, height }
+    }
+}
+
+impl FromStr for Resolution {
+    type Err = String;
+
+    /// Parses resolutions of the form `1920x1080`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected a resolution like `1920x1080`, found `{s}`");
+        let (width, height) = s.split_once('x').ok_or_else(invalid)?;
+        Ok(Self::new(
+            width.trim().parse().map_err(|_| invalid())?,
+            height.trim().parse().map_err(|_| invalid())?,
+        ))
+    }
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+impl Default for Resolution {
+    fn default() -> Self {
+        Self::new(854, 480)
+    }
+}
+
+/// Parses a duration like `90s`, `1.5m` or `2h` into seconds.
+///
+/// Plain numbers are taken as seconds. Durations longer than [`Duration::MAX`] are rejected.
+pub fn parse_seconds(s: &str) -> Result<f32, String> {
+    let invalid = || format!("expected a duration like `90s`, `5m` or `1h`, found `{s}`");
+    let s = s.trim();
+    let (value, unit_seconds) = if let Some(hours) = s.strip_suffix('h') {
+        (hours, 3600.0)
+    } else if let Some(minutes) = s.strip_suffix('m') {
+        (minutes, 60.0)
+    } else {
+        (s.strip_suffix('s').unwrap_or(s), 1.0)
+    };
+
+    let value: f32 = value.trim().parse().map_err(|_| invalid())?;
+    if !value.is_finite() || value <= 0.0 {
+        return Err(invalid());
+    }
+
+    // The conversion to hours or minutes can overflow, even for finite values
+    let seconds = value * unit_seconds;
+    if Duration::try_from_secs_f32(seconds).is_err() {
+        return Err(format!("the duration `{s}` is too long"));
+    }
+    Ok(seconds)
+}
+
+/// Parses a duration like [`parse_seconds`].
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    parse_seconds(s).map(Duration::from_secs_f32)
+}
+
+/// Reconstruction filter that weights the samples taken around the center of a pixel.
+///
+/// Camera rays are offset from the pixel center proportionally to the filter, so that
+/// averaging the samples applies it.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug, ValueEnum)]
+pub enum Filter {
+    /// Weights every point inside the pixel equally
+    #[default]
+    Box,
+    /// Falls off linearly up to one pixel away from the center
+    Tent,
+    /// Gaussian with a standard deviation of half a pixel
+    Gaussian,
+}
+
+/// Mapping of the high dynamic range radiance to displayable values.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug, ValueEnum)]
+pub enum Tonemapping {
+    /// Clips values outside of the displayable range
+    #[default]
+    None,
+    /// Compresses highlights with the Reinhard operator
+    Reinhard,
+    /// Filmic curve approximating the ACES reference rendering transform
+    Aces,
+}
+
+/// Limits on the number of bounces a path makes, in total and for each kind of scattering.
+///
+/// A path ends as soon as any of its limits is reached.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(default)]
+pub struct BounceLimits {
+    /// Maximum number of rays traced along a path, including the camera ray
+    pub total: u32,
+    /// Maximum number of diffuse reflections
+    pub diffuse: u32,
+    /// Maximum number of specular reflections, including reflections off transmissive surfaces
+    pub glossy: u32,
+    /// Maximum number of refractions into or out of transmissive surfaces
+    pub transmission: u32,
+}
+
+impl BounceLimits {
+    /// Limits every kind of bounce only by the total number of bounces.
+    pub fn uniform(limit: u32) -> Self {
+        Self {
+            total: limit,
+            diffuse: limit,
+            glossy: limit,
+            transmission: limit,
+        }
+    }
+}
+
+impl Default for BounceLimits {
+    fn default() -> Self {
+        Self::uniform(12)
+    }
+}
+
+/// Clamping of the light a single path contributes to a pixel, which suppresses fireflies at
+/// the cost of losing some energy.
+///
+/// Contributions brighter than the limit are scaled down, keeping their hue. Light that
+/// reaches the camera after at most one bounce is direct, the rest is indirect.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default, Debug)]
+#[serde(default)]
+pub struct Clamping {
+    /// Maximum brightness of direct light contributions, unclamped if `None`
+    pub direct: Option<f32>,
+    /// Maximum brightness of indirect light contributions, unclamped if `None`
+    pub indirect: Option<f32>,
+}
+
+impl Clamping {
+    /// Clamps the light a path picked up after `bounce` bounces.
+    pub fn clamp(&self, contribution: Vector3<f32>, bounce: u32) -> Vector3<f32> {
+        let limit = if bounce <= 1 {
+            self.direct
+        } else {
+            self.indirect
+        };
+
+        let brightness = contribution.x.max(contribution.y).max(contribution.z);
+        match limit {
+            Some(limit) if brightness > limit => contribution * (limit / brightness),
+            _ => contribution,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use std::time::Duration;
+
+    use super::{parse_duration, parse_seconds, Clamping, Resolution};
+
+    const CLAMPING: Clamping = Clamping {
+        direct: Some(2.0),
+        indirect: Some(0.5),
+    };
+
+    #[test]
+    fn clamp_scales_bright_contributions_keeping_their_hue() {
+        let clamped = CLAMPING.clamp(Vector3::new(8.0, 4.0, 2.0), 1);
+        assert_eq!(clamped, Vector3::new(2.0, 1.0, 0.5));
+    }
+
+    #[test]
+    fn clamp_keeps_dim_contributions() {
+        let contribution = Vector3::new(0.4, 0.3, 0.1);
+        assert_eq!(CLAMPING.clamp(contribution, 0), contribution);
+        assert_eq!(CLAMPING.clamp(contribution, 5), contribution);
+    }
+
+    #[test]
+    fn clamp_uses_the_indirect_limit_after_the_first_bounce() {
+        let contribution = Vector3::new(1.0, 1.0, 1.0);
+        assert_eq!(CLAMPING.clamp(contribution, 1), contribution);
+        assert_eq!(CLAMPING.clamp(contribution, 2), Vector3::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn clamp_without_limits_keeps_everything() {
+        let contribution = Vector3::new(100.0, 0.0, 50.0);
+        assert_eq!(Clamping::default().clamp(contribution, 0), contribution);
+        assert_eq!(Clamping::default().clamp(contribution, 3), contribution);
+    }
+
+    #[test]
+    fn resolution_parses_width_and_height() {
+        assert_eq!("1920x1080".parse(), Ok(Resolution::new(1920, 1080)));
+        assert_eq!(Resolution::new(64, 32).to_string(), "64x32");
+        assert!("1920".parse::<Resolution>().is_err());
+        assert!("1920x".parse::<Resolution>().is_err());
+        assert!("-1x5".parse::<Resolution>().is_err());
+    }
+
+    #[test]
+    fn parse_seconds_converts_units() {
+        assert_eq!(parse_seconds("90s"), Ok(90.0));
+        assert_eq!(parse_seconds("90"), Ok(90.0));
+        assert_eq!(parse_seconds("1.5m"), Ok(90.0));
+        assert_eq!(parse_seconds("2h"), Ok(7200.0));
+        assert!(parse_seconds("s").is_err());
+        assert!(parse_seconds("0s").is_err());
+        assert!(parse_seconds("-5s").is_err());
+        assert!(parse_seconds("5d").is_err());
+        assert!(parse_seconds("inf").is_err());
+        assert!(parse_seconds("1e38h").is_err());
+        assert!(parse_seconds("1e30s").is_err());
+        assert!(parse_seconds("1e15s").is_ok());
+    }
+
+    #[test]
+    fn parse_duration_accepts_the_durations_of_parse_seconds() {
+        assert_eq!(parse_duration("1.5m"), Ok(Duration::from_secs(90)));
+        assert!(parse_duration("1e38h").is_err());
+    }
+}
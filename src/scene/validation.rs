@@ -0,0 +1,611 @@
+//! Validation of scenes, which catches values the renderers cannot make sense of before
+//! they reach them.
+
+use std::fmt;
+
+use cgmath::{EuclideanSpace, InnerSpace, Vector3};
+
+use super::{
+    animation::{Animatable, Animation, Interpolation, Track},
+    camera::{Camera, Projection},
+    material::Material,
+    objects::Geometry,
+    render_settings::RenderSettings,
+    world::World,
+    Scene,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The scene renders, but probably not as intended
+    Warning,
+    /// The scene cannot be rendered correctly
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem found in a scene.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Index of the object the problem was found in, if any
+    pub object: Option<usize>,
+    /// Path of the offending field, such as `objects[2].material.ior`
+    pub path: String,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}: {}", self.severity, self.message)
+        } else {
+            write!(f, "{}: `{}`: {}", self.severity, self.path, self.message)
+        }
+    }
+}
+
+/// Checks a scene for invalid or suspicious values.
+///
+/// Diagnostics are returned in the order of the fields in the scene file.
+pub fn validate(scene: &Scene) -> Vec<Diagnostic> {
+    let mut validator = Validator::default();
+    validator.camera(&scene.camera);
+    validator.world(&scene.world);
+    for (index, object) in scene.objects.iter().enumerate() {
+        validator.object = Some(index);
+        let path = format!("objects[{index}]");
+        validator.geometry(&path, &object.geometry);
+        validator.material(&format!("{path}.material"), &object.material);
+    }
+    validator.object = None;
+    validator.render_settings(&scene.render_settings);
+    validator.animation(scene, &scene.animation);
+    validator.lighting(scene);
+    validator.diagnostics
+}
+
+#[derive(Default)]
+struct Validator {
+    diagnostics: Vec<Diagnostic>,
+    /// Index of the object being validated
+    object: Option<usize>,
+}
+
+impl Validator {
+    fn report(&mut self, severity: Severity, path: &str, message: impl Into<String>) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            object: self.object,
+            path: path.to_string(),
+            message: message.into(),
+        });
+    }
+
+    fn error(&mut self, path: &str, message: impl Into<String>) {
+        self.report(Severity::Error, path, message);
+    }
+
+    fn warning(&mut self, path: &str, message: impl Into<String>) {
+        self.report(Severity::Warning, path, message);
+    }
+
+    /// Reports non-finite values, returns whether the value is finite.
+    fn finite(&mut self, path: &str, value: f32) -> bool {
+        if !value.is_finite() {
+            self.error(path, format!("{value} is not a finite number"));
+        }
+        value.is_finite()
+    }
+
+    fn finite_vector(&mut self, path: &str, vector: Vector3<f32>) -> bool {
+        let finite = vector.x.is_finite() && vector.y.is_finite() && vector.z.is_finite();
+        if !finite {
+            self.error(
+                path,
+                format!("{vector:?} has components that are not finite"),
+            );
+        }
+        finite
+    }
+
+    fn color(&mut self, path: &str, color: Vector3<f32>) {
+        if self.finite_vector(path, color) && (color.x < 0.0 || color.y < 0.0 || color.z < 0.0) {
+            self.error(path, format!("{color:?} has negative components"));
+        }
+    }
+
+    /// Checks a surface color, which reflects at most all of the incoming light.
+    fn reflectance(&mut self, path: &str, color: Vector3<f32>) {
+        self.color(path, color);
+        if color.x > 1.0 || color.y > 1.0 || color.z > 1.0 {
+            self.warning(
+                path,
+                format!("{color:?} reflects more light than it receives"),
+            );
+        }
+    }
+
+    fn unit_interval(&mut self, path: &str, value: f32) {
+        if self.finite(path, value) && !(0.0..=1.0).contains(&value) {
+            self.error(path, format!("{value} is outside of the range from 0 to 1"));
+        }
+    }
+
+    fn positive(&mut self, path: &str, value: f32) {
+        if self.finite(path, value) && value <= 0.0 {
+            self.error(path, format!("{value} is not positive"));
+        }
+    }
+
+    fn camera(&mut self, camera: &Camera) {
+        let position = camera.position();
+        let target = camera.target();
+        let valid_position = self.finite_vector("camera.position", position.to_vec());
+        let valid_target = self.finite_vector("camera.target", target.to_vec());
+        let valid_up = self.finite_vector("camera.up", camera.up());
+
+        if valid_position && valid_target {
+            let direction = target - position;
+            if direction.magnitude2() == 0.0 {
+                self.error("camera.target", "the camera looks at its own position");
+            } else if valid_up && direction.normalize().cross(camera.up()).magnitude2() < 1e-12 {
+                self.error(
+                    "camera.up",
+                    "the up vector is parallel to the view direction",
+                );
+            }
+        }
+
+        match camera.projection() {
+            Projection::Perspective { fov } => {
+                let path = "camera.projection.Perspective.fov";
+                if self.finite(path, fov.0) && !(fov.0 > 0.0 && fov.0 < 180.0) {
+                    self.error(
+                        path,
+                        format!("{} is outside of the range from 0 to 180 degrees", fov.0),
+                    );
+                }
+            }
+            Projection::Orthographic { size } => {
+                self.positive("camera.projection.Orthographic.size", size);
+            }
+        }
+
+        let near_clip = camera.near_clip();
+        let far_clip = camera.far_clip();
+        let valid_near_clip = self.finite("camera.near_clip", near_clip);
+        let valid_far_clip = self.finite("camera.far_clip", far_clip);
+        if valid_near_clip && valid_far_clip {
+            if near_clip <= 0.0 && matches!(camera.projection(), Projection::Perspective { .. }) {
+                self.error("camera.near_clip", format!("{near_clip} is not positive"));
+            } else if near_clip >= far_clip {
+                self.error(
+                    "camera.far_clip",
+                    format!("{far_clip} is not beyond the near clip distance {near_clip}"),
+                );
+            }
+        }
+    }
+
+    fn world(&mut self, world: &World) {
+        match world {
+            World::SkyColor {
+                top_color,
+                bottom_color,
+            } => {
+                self.color("world.SkyColor.top_color", *top_color);
+                self.color("world.SkyColor.bottom_color", *bottom_color);
+            }
+            World::SolidColor(color) => self.color("world.SolidColor", *color),
+            World::Transparent => {}
+        }
+    }
+
+    fn geometry(&mut self, path: &str, geometry: &Geometry) {
+        match geometry {
+            Geometry::Sphere(sphere) => {
+                self.finite_vector(
+                    &format!("{path}.geometry.Sphere.center"),
+                    sphere.center.to_vec(),
+                );
+                self.positive(&format!("{path}.geometry.Sphere.radius"), sphere.radius);
+            }
+            Geometry::Cube(cube) => {
+                self.finite_vector(
+                    &format!("{path}.geometry.Cube.center"),
+                    cube.center.to_vec(),
+                );
+                self.positive(
+                    &format!("{path}.geometry.Cube.side_length"),
+                    cube.side_length,
+                );
+            }
+        }
+    }
+
+    fn material(&mut self, path: &str, material: &Material) {
+        self.reflectance(&format!("{path}.albedo"), material.albedo);
+        self.unit_interval(&format!("{path}.roughness"), material.roughness);
+        self.unit_interval(&format!("{path}.metallic"), material.metallic);
+        self.unit_interval(&format!("{path}.transmission"), material.transmission);
+        self.color(&format!("{path}.emission_color"), material.emission_color);
+
+        let emission_strength_path = format!("{path}.emission_strength");
+        if self.finite(&emission_strength_path, material.emission_strength)
+            && material.emission_strength < 0.0
+        {
+            self.error(
+                &emission_strength_path,
+                format!("{} is negative", material.emission_strength),
+            );
+        }
+
+        let ior_path = format!("{path}.ior");
+        if self.finite(&ior_path, material.ior) && material.ior < 1.0 {
+            self.error(
+                &ior_path,
+                format!(
+                    "index of refraction {} is below 1, the index of vacuum",
+                    material.ior
+                ),
+            );
+        }
+    }
+
+    fn render_settings(&mut self, settings: &RenderSettings) {
+        let resolution = settings.resolution;
+        if resolution.width == 0 || resolution.height == 0 {
+            self.error(
+                "render_settings.resolution",
+                format!("{}x{} has no pixels", resolution.width, resolution.height),
+            );
+        }
+
+        if settings.samples == 0 {
+            self.error("render_settings.samples", "no samples are taken");
+        }
+        if settings.bounces.total == 0 {
+            self.warning(
+                "render_settings.bounces.total",
+                "no rays are traced, the image is black",
+            );
+        }
+
+        for (path, limit) in [
+            ("render_settings.clamping.direct", settings.clamping.direct),
+            (
+                "render_settings.clamping.indirect",
+                settings.clamping.indirect,
+            ),
+        ] {
+            if let Some(limit) = limit {
+                self.positive(path, limit);
+            }
+        }
+        if let Some(time_limit) = settings.time_limit {
+            self.positive("render_settings.time_limit", time_limit);
+        }
+
+        if image::ImageFormat::from_path(&settings.output).is_err() {
+            self.error(
+                "render_settings.output",
+                format!(
+                    "the image format of {} is not supported",
+                    settings.output.display()
+                ),
+            );
+        }
+    }
+
+    fn animation(&mut self, scene: &Scene, animation: &Animation) {
+        let camera = &animation.camera;
+        self.track("animation.camera.position", &camera.position);
+        self.track("animation.camera.target", &camera.target);
+        self.track("animation.camera.fov", &camera.fov);
+        if !camera.fov.keyframes.is_empty()
+            && !matches!(scene.camera.projection(), Projection::Perspective { .. })
+        {
+            self.warning(
+                "animation.camera.fov",
+                "only perspective cameras have a field of view",
+            );
+        }
+
+        for (index, tracks) in animation.objects.iter().enumerate() {
+            let path = format!("animation.objects[{index}]");
+            if tracks.object >= scene.objects.len() {
+                self.error(
+                    &format!("{path}.object"),
+                    format!(
+                        "object {} does not exist, the scene has {} objects",
+                        tracks.object,
+                        scene.objects.len()
+                    ),
+                );
+            }
+            self.object = (tracks.object < scene.objects.len()).then_some(tracks.object);
+            self.track(&format!("{path}.center"), &tracks.center);
+            self.track(&format!("{path}.size"), &tracks.size);
+
+            let material = &tracks.material;
+            let path = format!("{path}.material");
+            self.track(&format!("{path}.albedo"), &material.albedo);
+            self.track(&format!("{path}.roughness"), &material.roughness);
+            self.track(&format!("{path}.metallic"), &material.metallic);
+            self.track(&format!("{path}.emission_color"), &material.emission_color);
+            self.track(
+                &format!("{path}.emission_strength"),
+                &material.emission_strength,
+            );
+            self.track(&format!("{path}.transmission"), &material.transmission);
+            self.track(&format!("{path}.ior"), &material.ior);
+        }
+        self.object = None;
+
+        let world = &animation.world;
+        self.track("animation.world.top_color", &world.top_color);
+        self.track("animation.world.bottom_color", &world.bottom_color);
+        self.track("animation.world.color", &world.color);
+        let (sky_tracks, solid_tracks) = match scene.world {
+            World::SkyColor { .. } => (true, false),
+            World::SolidColor(_) => (false, true),
+            World::Transparent => (false, false),
+        };
+        for (path, track, applies) in [
+            ("animation.world.top_color", &world.top_color, sky_tracks),
+            (
+                "animation.world.bottom_color",
+                &world.bottom_color,
+                sky_tracks,
+            ),
+            ("animation.world.color", &world.color, solid_tracks),
+        ] {
+            if !track.keyframes.is_empty() && !applies {
+                self.warning(
+                    path,
+                    "the world does not have this color, the track is ignored",
+                );
+            }
+        }
+    }
+
+    /// Checks the order, values and interpolation of the keyframes of a track.
+    ///
+    /// Animated values are not checked against the ranges of the fields they set.
+    fn track<T: Animatable>(&mut self, path: &str, track: &Track<T>) {
+        for (index, keyframe) in track.keyframes.iter().enumerate() {
+            let path = format!("{path}[{index}]");
+            if index > 0 && keyframe.frame <= track.keyframes[index - 1].frame {
+                self.error(
+                    &format!("{path}.frame"),
+                    format!(
+                        "frame {} is not after the frame of the previous keyframe",
+                        keyframe.frame
+                    ),
+                );
+            }
+            if !keyframe.value.is_finite() {
+                self.error(&format!("{path}.value"), "the value is not finite");
+            }
+            if let Interpolation::Bezier { x1, y1, x2, y2 } = keyframe.interpolation {
+                let path = format!("{path}.interpolation.Bezier");
+                self.unit_interval(&format!("{path}.x1"), x1);
+                self.finite(&format!("{path}.y1"), y1);
+                self.unit_interval(&format!("{path}.x2"), x2);
+                self.finite(&format!("{path}.y2"), y2);
+            }
+        }
+    }
+
+    /// Warns about scenes without any light.
+    fn lighting(&mut self, scene: &Scene) {
+        if scene.objects.is_empty() {
+            self.warning("objects", "the scene has no objects");
+        }
+
+        let has_emitters = scene
+            .objects
+            .iter()
+            .any(|object| object.material.is_emissive());
+        let has_world_light = match scene.world {
+            World::SkyColor {
+                top_color,
+                bottom_color,
+            } => {
+                top_color != Vector3::new(0.0, 0.0, 0.0)
+                    || bottom_color != Vector3::new(0.0, 0.0, 0.0)
+            }
+            World::SolidColor(color) => color != Vector3::new(0.0, 0.0, 0.0),
+            World::Transparent => false,
+        };
+        if !has_emitters && !has_world_light {
+            self.warning("", "the scene has no light sources, the image is black");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, Point3, Vector3};
+
+    use super::{validate, Diagnostic, Severity};
+    use crate::scene::{
+        animation::{Interpolation, Keyframe, ObjectTracks, Track},
+        camera::Projection,
+        objects::{Geometry, Object, Sphere},
+        render_settings::Resolution,
+        world::World,
+        Scene,
+    };
+
+    fn errors(scene: &Scene) -> Vec<Diagnostic> {
+        validate(scene)
+            .into_iter()
+            .filter(Diagnostic::is_error)
+            .collect()
+    }
+
+    fn error_paths(scene: &Scene) -> Vec<String> {
+        errors(scene)
+            .into_iter()
+            .map(|diagnostic| diagnostic.path)
+            .collect()
+    }
+
+    #[test]
+    fn default_scene_is_valid() {
+        assert_eq!(validate(&Scene::default()), vec![]);
+    }
+
+    #[test]
+    fn invalid_objects_are_reported_with_their_index() {
+        let mut scene = Scene::default();
+        scene.objects.push(Object {
+            geometry: Geometry::Sphere(Sphere {
+                center: Point3::new(0.0, 0.0, 0.0),
+                radius: -1.0,
+            }),
+            material: Default::default(),
+        });
+        scene.objects[1].material.ior = 0.5;
+        scene.objects[2].material.albedo = Vector3::new(f32::NAN, 0.0, 0.0);
+
+        let errors = errors(&scene);
+        assert_eq!(
+            errors
+                .iter()
+                .map(|error| (error.object, error.path.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (Some(1), "objects[1].material.ior"),
+                (Some(2), "objects[2].material.albedo"),
+                (Some(3), "objects[3].geometry.Sphere.radius"),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_camera_is_reported() {
+        let mut scene = Scene::default();
+        scene.camera.set_near_clip(10.0);
+        scene.camera.set_far_clip(1.0);
+        assert_eq!(error_paths(&scene), vec!["camera.far_clip"]);
+
+        let mut scene = Scene::default();
+        scene.camera.set_target(scene.camera.position());
+        assert_eq!(error_paths(&scene), vec!["camera.target"]);
+
+        let mut scene = Scene::default();
+        scene
+            .camera
+            .set_projection(Projection::Perspective { fov: Deg(180.0) });
+        assert_eq!(
+            error_paths(&scene),
+            vec!["camera.projection.Perspective.fov"]
+        );
+    }
+
+    #[test]
+    fn invalid_render_settings_are_reported() {
+        let mut scene = Scene::default();
+        scene.render_settings.resolution = Resolution::new(0, 480);
+        scene.render_settings.samples = 0;
+        scene.render_settings.output = "output.unknown".into();
+
+        assert_eq!(
+            error_paths(&scene),
+            vec![
+                "render_settings.resolution",
+                "render_settings.samples",
+                "render_settings.output",
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_animations_are_reported() {
+        let mut scene = Scene::default();
+        let keyframe = |frame, value| Keyframe {
+            frame,
+            value,
+            interpolation: Interpolation::Linear,
+        };
+        scene.animation.camera.fov = Track {
+            keyframes: vec![keyframe(10, 60.0), keyframe(5, 90.0)],
+        };
+        scene.animation.objects.push(ObjectTracks {
+            object: 3,
+            center: Track::default(),
+            size: Track {
+                keyframes: vec![Keyframe {
+                    interpolation: Interpolation::Bezier {
+                        x1: 1.5,
+                        y1: 0.0,
+                        x2: 0.5,
+                        y2: 1.0,
+                    },
+                    ..keyframe(1, f32::NAN)
+                }],
+            },
+            material: Default::default(),
+        });
+        scene.animation.world.color = Track {
+            keyframes: vec![Keyframe {
+                frame: 1,
+                value: Vector3::new(1.0, 1.0, 1.0),
+                interpolation: Interpolation::Linear,
+            }],
+        };
+
+        assert_eq!(
+            error_paths(&scene),
+            vec![
+                "animation.camera.fov[1].frame",
+                "animation.objects[0].object",
+                "animation.objects[0].size[0].value",
+                "animation.objects[0].size[0].interpolation.Bezier.x1",
+            ]
+        );
+        let warnings: Vec<_> = validate(&scene)
+            .into_iter()
+            .filter(|diagnostic| !diagnostic.is_error())
+            .map(|diagnostic| diagnostic.path)
+            .collect();
+        assert_eq!(warnings, vec!["animation.world.color"]);
+    }
+
+    #[test]
+    fn suspicious_values_are_warnings() {
+        let mut scene = Scene::default();
+        scene.objects[1].material.albedo = Vector3::new(1.5, 0.5, 0.5);
+        scene.objects.truncate(2);
+        scene.world = World::Transparent;
+
+        let diagnostics = validate(&scene);
+        assert!(diagnostics
+            .iter()
+            .all(|diagnostic| diagnostic.severity == Severity::Warning));
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.path.as_str())
+                .collect::<Vec<_>>(),
+            vec!["objects[1].material.albedo", ""]
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use cgmath::{InnerSpace, Vector3, VectorSpace};
+use cgmath::{InnerSpace, Vector3, VectorSpace, Zero};
 use serde::{Deserialize, Serialize};
 
 use crate::renderer::cpu::Ray;
@@ -29,7 +29,8 @@ impl World {
                 sky_color
             }
             World::SolidColor(color) => *color,
-            World::Transparent => todo!("transparent world support"),
+            // The background is left to the alpha channel, see `CpuRenderer::per_pixel`
+            World::Transparent => Vector3::zero(),
         }
     }
 }
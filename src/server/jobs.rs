@@ -0,0 +1,261 @@
+//! The queue of render jobs of the server.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+use image::{Rgba32FImage, RgbaImage};
+use serde::Serialize;
+
+use crate::{
+    renderer::{describe_incomplete_render, output, Renderer},
+    scene::Scene,
+};
+
+/// The number of finished jobs kept by default. Once more jobs finish, the oldest ones are
+/// removed.
+pub const MAX_FINISHED_JOBS: usize = 100;
+
+/// The stage a job is in.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    /// Waiting for the jobs submitted before it
+    Queued,
+    Rendering,
+    Done,
+    /// The renderer stopped before all samples were rendered
+    Failed,
+}
+
+impl JobState {
+    pub fn is_finished(self) -> bool {
+        matches!(self, JobState::Done | JobState::Failed)
+    }
+}
+
+/// The progress of a job, taken from the renderer and its
+/// [`Profiler`](crate::renderer::timing::Profiler).
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct JobStatus {
+    pub id: u64,
+    pub state: JobState,
+    /// Samples rendered so far
+    pub samples: u32,
+    /// Samples the job is rendered with
+    pub total_samples: u32,
+    /// Time since the job started rendering, in milliseconds
+    pub elapsed_ms: Option<f64>,
+    /// Time it took to prepare the scene, in milliseconds
+    pub prepare_ms: Option<f64>,
+    /// Duration of the last sample, in milliseconds
+    pub sample_ms: Option<f64>,
+    pub samples_per_second: Option<f64>,
+    /// Rays traced so far
+    pub rays: u64,
+    pub error: Option<String>,
+}
+
+impl JobStatus {
+    fn record_progress(&mut self, renderer: &dyn Renderer, start: Instant) {
+        let milliseconds = |duration: Duration| duration.as_secs_f64() * 1000.0;
+
+        let profiler = renderer.profiler();
+        self.samples = renderer.sample_count();
+        self.elapsed_ms = Some(milliseconds(start.elapsed()));
+        self.prepare_ms = profiler.prepare_timer().duration().map(milliseconds);
+        self.sample_ms = profiler.sample_timer().duration().map(milliseconds);
+        self.samples_per_second = profiler.samples_per_second();
+        self.rays = profiler.path_statistics().ray_count();
+    }
+}
+
+struct Job {
+    status: JobStatus,
+    scene: Scene,
+    /// The display image of the samples rendered so far
+    preview: Option<RgbaImage>,
+    /// The linear radiance of the finished render
+    output: Option<Rgba32FImage>,
+}
+
+#[derive(Default)]
+struct QueueState {
+    /// The jobs that have not been removed, by ID
+    jobs: BTreeMap<u64, Job>,
+    /// The ID of the last submitted job, IDs are never reused
+    last_id: u64,
+    /// IDs of the jobs waiting to be rendered
+    pending: VecDeque<u64>,
+}
+
+impl QueueState {
+    /// Removes the oldest finished jobs until at most `max_finished_jobs` are left.
+    fn evict_finished_jobs(&mut self, max_finished_jobs: usize) {
+        let finished: Vec<_> = self
+            .jobs
+            .iter()
+            .filter(|(_, job)| job.status.state.is_finished())
+            .map(|(&id, _)| id)
+            .collect();
+        let evicted = finished.len().saturating_sub(max_finished_jobs);
+        for id in &finished[..evicted] {
+            self.jobs.remove(id);
+        }
+    }
+}
+
+/// Jobs submitted to the server, rendered one after another in the order they were submitted.
+pub struct JobQueue {
+    state: Mutex<QueueState>,
+    job_queued: Condvar,
+    max_finished_jobs: usize,
+}
+
+impl Default for JobQueue {
+    fn default() -> Self {
+        Self::new(MAX_FINISHED_JOBS)
+    }
+}
+
+impl JobQueue {
+    /// Creates a queue that keeps the `max_finished_jobs` most recently finished jobs.
+    pub fn new(max_finished_jobs: usize) -> Self {
+        Self {
+            state: Mutex::default(),
+            job_queued: Condvar::new(),
+            max_finished_jobs,
+        }
+    }
+
+    /// Queues a scene for rendering and returns the ID of its job.
+    pub fn submit(&self, scene: Scene) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        state.last_id += 1;
+        let id = state.last_id;
+        state.jobs.insert(
+            id,
+            Job {
+                status: JobStatus {
+                    id,
+                    state: JobState::Queued,
+                    samples: 0,
+                    total_samples: scene.render_settings.samples,
+                    elapsed_ms: None,
+                    prepare_ms: None,
+                    sample_ms: None,
+                    samples_per_second: None,
+                    rays: 0,
+                    error: None,
+                },
+                scene,
+                preview: None,
+                output: None,
+            },
+        );
+        state.pending.push_back(id);
+        self.job_queued.notify_one();
+        id
+    }
+
+    pub fn status(&self, id: u64) -> Option<JobStatus> {
+        let state = self.state.lock().unwrap();
+        state.jobs.get(&id).map(|job| job.status.clone())
+    }
+
+    pub fn statuses(&self) -> Vec<JobStatus> {
+        let state = self.state.lock().unwrap();
+        state.jobs.values().map(|job| job.status.clone()).collect()
+    }
+
+    /// Removes a job that is queued or finished, returning `false` inside if it is being
+    /// rendered.
+    pub fn remove(&self, id: u64) -> Option<bool> {
+        let mut state = self.state.lock().unwrap();
+        let job = state.jobs.get(&id)?;
+        if job.status.state == JobState::Rendering {
+            return Some(false);
+        }
+
+        state.jobs.remove(&id);
+        state.pending.retain(|&pending| pending != id);
+        Some(true)
+    }
+
+    /// Returns the image of the samples of a job rendered so far, `None` inside if no sample
+    /// has been rendered yet.
+    pub fn preview(&self, id: u64) -> Option<Option<RgbaImage>> {
+        let state = self.state.lock().unwrap();
+        state.jobs.get(&id).map(|job| job.preview.clone())
+    }
+
+    /// Returns the linear radiance of a job, `None` inside if the job is not done.
+    pub fn output(&self, id: u64) -> Option<Option<Rgba32FImage>> {
+        let state = self.state.lock().unwrap();
+        state.jobs.get(&id).map(|job| job.output.clone())
+    }
+
+    /// Waits until a job is queued and renders it, configuring the renderer with the render
+    /// settings of its scene. Finished jobs beyond the limit of the queue are removed
+    /// afterwards.
+    pub fn render_next(&self, renderer: &mut dyn Renderer) {
+        let (id, scene) = {
+            let mut state = self.state.lock().unwrap();
+            let id = loop {
+                if let Some(id) = state.pending.pop_front() {
+                    break id;
+                }
+                state = self.job_queued.wait(state).unwrap();
+            };
+            let job = state.jobs.get_mut(&id).unwrap();
+            job.status.state = JobState::Rendering;
+            (id, job.scene.clone())
+        };
+        let _span = tracing::info_span!("job", id).entered();
+
+        let settings = &scene.render_settings;
+        renderer.apply_render_settings(settings);
+        let start = Instant::now();
+        renderer.new_frame(&scene);
+        let mut out_of_time = false;
+        while let Some(image) = renderer.render_sample(&scene) {
+            self.update(id, |job| {
+                job.preview = Some(image);
+                job.status.record_progress(renderer, start);
+            });
+
+            out_of_time = settings
+                .time_limit
+                .is_some_and(|limit| start.elapsed().as_secs_f32() >= limit);
+            if out_of_time {
+                break;
+            }
+        }
+
+        let radiance = renderer
+            .frame_buffer()
+            .map(|frame_buffer| output::resolve(frame_buffer, renderer.sample_count()));
+        self.update(id, |job| {
+            job.status.record_progress(renderer, start);
+            if renderer.sample_count() >= renderer.config().max_sample_count || out_of_time {
+                job.status.state = JobState::Done;
+                job.output = radiance;
+            } else {
+                job.status.state = JobState::Failed;
+                job.status.error = Some(describe_incomplete_render(renderer));
+            }
+        });
+        self.state
+            .lock()
+            .unwrap()
+            .evict_finished_jobs(self.max_finished_jobs);
+    }
+
+    fn update(&self, id: u64, update: impl FnOnce(&mut Job)) {
+        let mut state = self.state.lock().unwrap();
+        // Jobs cannot be removed while they are rendered
+        update(state.jobs.get_mut(&id).unwrap());
+    }
+}
@@ -0,0 +1,312 @@
+//! A local HTTP service that renders submitted scenes one after another.
+//!
+//! | Request                  | Response                                                      |
+//! |--------------------------|---------------------------------------------------------------|
+//! | `POST /jobs`             | Queues the scene file in the body, returns `{"id": <id>}`     |
+//! | `GET /jobs`              | The [`JobStatus`] of every job                                |
+//! | `GET /jobs/<id>`         | The [`JobStatus`] of a job                                    |
+//! | `GET /jobs/<id>/image`   | PNG of the samples rendered so far                            |
+//! | `GET /jobs/<id>/output`  | OpenEXR of the linear radiance, once the job is done          |
+//! | `DELETE /jobs/<id>`      | Removes a queued or finished job                              |
+//!
+//! Errors are returned as `{"error": "<message>"}`. Only the most recently finished jobs are
+//! kept, see [`JobQueue::new`].
+
+use std::io::{Cursor, Read};
+
+use image::{ImageFormat, ImageResult};
+use serde::Serialize;
+use tiny_http::{Header, Method, Response, Server};
+
+use crate::{cli::SettingsArgs, scene::Scene};
+
+pub use jobs::{JobQueue, JobState, JobStatus};
+
+mod jobs;
+
+/// Scene files larger than this are rejected.
+const MAX_SCENE_SIZE: u64 = 16 * 1024 * 1024;
+
+/// A response to a request.
+struct Reply {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Reply {
+    fn json(status: u16, value: &impl Serialize) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: serde_json::to_vec(value).expect("replies can always be serialized"),
+        }
+    }
+
+    fn no_content() -> Self {
+        Self {
+            status: 204,
+            content_type: "text/plain",
+            body: Vec::new(),
+        }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        #[derive(Serialize)]
+        struct Error {
+            error: String,
+        }
+
+        Self::json(
+            status,
+            &Error {
+                error: message.into(),
+            },
+        )
+    }
+
+    fn image(
+        content_type: &'static str,
+        encode: impl FnOnce(&mut Cursor<Vec<u8>>) -> ImageResult<()>,
+    ) -> Self {
+        let mut body = Cursor::new(Vec::new());
+        match encode(&mut body) {
+            Ok(()) => Self {
+                status: 200,
+                content_type,
+                body: body.into_inner(),
+            },
+            Err(err) => Self::error(500, format!("cannot encode image: {err}")),
+        }
+    }
+}
+
+/// Answers requests until the server is shut down.
+///
+/// Submitted scenes get the overrides of `settings` applied, like scenes rendered from the
+/// command line.
+pub fn serve(server: &Server, queue: &JobQueue, settings: &SettingsArgs) {
+    for mut request in server.incoming_requests() {
+        let mut body = Vec::new();
+        let reply = match request
+            .as_reader()
+            .take(MAX_SCENE_SIZE + 1)
+            .read_to_end(&mut body)
+        {
+            Ok(_) if body.len() as u64 > MAX_SCENE_SIZE => {
+                Reply::error(413, "the scene file is too large")
+            }
+            Ok(_) => route(queue, settings, request.method(), request.url(), &body),
+            Err(err) => Reply::error(400, format!("cannot read request body: {err}")),
+        };
+
+        let content_type = Header::from_bytes("Content-Type", reply.content_type).unwrap();
+        let response = Response::from_data(reply.body)
+            .with_status_code(reply.status)
+            .with_header(content_type);
+        if let Err(err) = request.respond(response) {
+            eprintln!("Failed to respond to request: {err}");
+        }
+    }
+}
+
+fn route(
+    queue: &JobQueue,
+    settings: &SettingsArgs,
+    method: &Method,
+    url: &str,
+    body: &[u8],
+) -> Reply {
+    let path = url.split('?').next().unwrap_or_default();
+    let segments: Vec<_> = path.trim_matches('/').split('/').collect();
+    let job_id = |id: &str| {
+        id.parse::<u64>()
+            .ok()
+            .filter(|&id| queue.status(id).is_some())
+    };
+
+    match (method, segments.as_slice()) {
+        (Method::Post, ["jobs"]) => submit(queue, settings, body),
+        (Method::Get, ["jobs"]) => Reply::json(200, &queue.statuses()),
+        (Method::Get, ["jobs", id]) => match job_id(id).and_then(|id| queue.status(id)) {
+            Some(status) => Reply::json(200, &status),
+            None => Reply::error(404, format!("no job with ID {id}")),
+        },
+        (Method::Delete, ["jobs", id]) => match job_id(id).and_then(|id| queue.remove(id)) {
+            Some(true) => Reply::no_content(),
+            Some(false) => Reply::error(409, "the job is being rendered"),
+            None => Reply::error(404, format!("no job with ID {id}")),
+        },
+        (Method::Get, ["jobs", id, "image"]) => match job_id(id).and_then(|id| queue.preview(id)) {
+            Some(Some(image)) => {
+                Reply::image("image/png", |body| image.write_to(body, ImageFormat::Png))
+            }
+            Some(None) => Reply::error(409, "no samples have been rendered yet"),
+            None => Reply::error(404, format!("no job with ID {id}")),
+        },
+        (Method::Get, ["jobs", id, "output"]) => match job_id(id).and_then(|id| queue.output(id)) {
+            Some(Some(radiance)) => Reply::image("image/x-exr", |body| {
+                radiance.write_to(body, ImageFormat::OpenExr)
+            }),
+            Some(None) => Reply::error(409, "the job is not done"),
+            None => Reply::error(404, format!("no job with ID {id}")),
+        },
+        (_, ["jobs"] | ["jobs", _] | ["jobs", _, "image" | "output"]) => {
+            Reply::error(405, format!("{method} is not supported for {path}"))
+        }
+        _ => Reply::error(404, format!("{path} does not exist")),
+    }
+}
+
+fn submit(queue: &JobQueue, settings: &SettingsArgs, body: &[u8]) -> Reply {
+    #[derive(Serialize)]
+    struct Submitted {
+        id: u64,
+    }
+
+    let scene = std::str::from_utf8(body)
+        .map_err(|err| format!("the scene file is not UTF-8: {err}"))
+        .and_then(|json| Scene::from_json(json).map_err(|err| format!("invalid scene: {err}")))
+        .and_then(|scene| {
+            settings
+                .apply_overrides(scene)
+                .map_err(|err| format!("{err:#}"))
+        });
+
+    match scene {
+        Ok(scene) => Reply::json(
+            201,
+            &Submitted {
+                id: queue.submit(scene),
+            },
+        ),
+        Err(message) => Reply::error(400, message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use tiny_http::Method;
+
+    use super::{route, JobQueue, JobState, Reply};
+    use crate::{
+        cli::CommonArgs,
+        renderer::{cpu::CpuRenderer, RendererConfig},
+        scene::{render_settings::Resolution, Scene},
+    };
+
+    fn scene_file() -> Vec<u8> {
+        let mut scene = Scene::default();
+        scene.render_settings.resolution = Resolution::new(16, 9);
+        scene.render_settings.samples = 2;
+        scene.to_json().unwrap().into_bytes()
+    }
+
+    fn request(queue: &JobQueue, method: Method, url: &str, body: &[u8]) -> Reply {
+        let settings = CommonArgs::parse_from(["raydar", "--cpu"]).settings;
+        route(queue, &settings, &method, url, body)
+    }
+
+    fn json(reply: &Reply) -> serde_json::Value {
+        serde_json::from_slice(&reply.body).unwrap()
+    }
+
+    #[test]
+    fn submitted_jobs_are_rendered() {
+        let queue = JobQueue::default();
+        let submitted = request(&queue, Method::Post, "/jobs", &scene_file());
+        assert_eq!(submitted.status, 201);
+        assert_eq!(json(&submitted)["id"], 1);
+
+        let status = request(&queue, Method::Get, "/jobs/1", &[]);
+        assert_eq!(json(&status)["state"], "queued");
+        assert_eq!(
+            request(&queue, Method::Get, "/jobs/1/image", &[]).status,
+            409
+        );
+        assert_eq!(
+            request(&queue, Method::Get, "/jobs/1/output", &[]).status,
+            409
+        );
+
+        queue.render_next(&mut CpuRenderer::new(RendererConfig::default()));
+
+        let status = queue.status(1).unwrap();
+        assert_eq!(status.state, JobState::Done);
+        assert_eq!((status.samples, status.total_samples), (2, 2));
+        assert!(status.rays > 0);
+
+        let image = request(&queue, Method::Get, "/jobs/1/image", &[]);
+        assert_eq!(image.content_type, "image/png");
+        let image = image::load_from_memory(&image.body).unwrap();
+        assert_eq!((image.width(), image.height()), (16, 9));
+
+        let output = request(&queue, Method::Get, "/jobs/1/output", &[]);
+        assert_eq!(output.status, 200);
+        let output = image::load_from_memory(&output.body).unwrap();
+        assert_eq!((output.width(), output.height()), (16, 9));
+    }
+
+    #[test]
+    fn invalid_requests_are_rejected() {
+        let queue = JobQueue::default();
+
+        let invalid_scene = request(&queue, Method::Post, "/jobs", b"{\"camera\": 5}");
+        assert_eq!(invalid_scene.status, 400);
+        assert!(json(&invalid_scene)["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("invalid scene"));
+
+        assert_eq!(request(&queue, Method::Get, "/jobs/1", &[]).status, 404);
+        assert_eq!(
+            request(&queue, Method::Get, "/jobs/0/image", &[]).status,
+            404
+        );
+        assert_eq!(request(&queue, Method::Get, "/jobs/abc", &[]).status, 404);
+        assert_eq!(request(&queue, Method::Delete, "/jobs", &[]).status, 405);
+        assert_eq!(request(&queue, Method::Delete, "/jobs/1", &[]).status, 404);
+        assert_eq!(request(&queue, Method::Get, "/scenes", &[]).status, 404);
+        assert!(queue.statuses().is_empty());
+    }
+
+    #[test]
+    fn deleted_jobs_are_not_rendered() {
+        let queue = JobQueue::default();
+        request(&queue, Method::Post, "/jobs", &scene_file());
+        request(&queue, Method::Post, "/jobs", &scene_file());
+
+        let deleted = request(&queue, Method::Delete, "/jobs/1", &[]);
+        assert_eq!(deleted.status, 204);
+        assert_eq!(request(&queue, Method::Get, "/jobs/1", &[]).status, 404);
+
+        // The next job keeps its ID and is the next to be rendered
+        queue.render_next(&mut CpuRenderer::new(RendererConfig::default()));
+        let statuses = queue.statuses();
+        assert_eq!(statuses.len(), 1);
+        assert_eq!((statuses[0].id, statuses[0].state), (2, JobState::Done));
+
+        let submitted = request(&queue, Method::Post, "/jobs", &scene_file());
+        assert_eq!(json(&submitted)["id"], 3);
+    }
+
+    #[test]
+    fn only_the_latest_finished_jobs_are_kept() {
+        let queue = JobQueue::new(1);
+        let mut renderer = CpuRenderer::new(RendererConfig::default());
+        for _ in 0..3 {
+            request(&queue, Method::Post, "/jobs", &scene_file());
+        }
+
+        queue.render_next(&mut renderer);
+        queue.render_next(&mut renderer);
+
+        let states: Vec<_> = queue
+            .statuses()
+            .into_iter()
+            .map(|status| (status.id, status.state))
+            .collect();
+        assert_eq!(states, [(2, JobState::Done), (3, JobState::Queued)]);
+    }
+}
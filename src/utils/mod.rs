@@ -1,7 +1,8 @@
 use std::ops::{Mul, Sub};
 
-use cgmath::{InnerSpace, Vector3};
-use rand::Rng;
+use cgmath::InnerSpace;
+
+pub mod sampling;
 
 pub trait Reflect {
     fn reflect(&self, normal: Self) -> Self;
@@ -44,21 +45,91 @@ impl Refract for cgmath::Vector3<f32> {
     }
 }
 
-pub fn random_in_unit_sphere() -> Vector3<f32> {
-    let mut rng = rand::thread_rng();
-    Vector3::new(
-        rng.gen_range(-1.0..=1.0),
-        rng.gen_range(-1.0..=1.0),
-        rng.gen_range(-1.0..=1.0),
-    )
-    .normalize()
-}
+#[cfg(test)]
+mod tests {
+    use cgmath::{InnerSpace, Vector2, Vector3};
+
+    use super::{Reflect, Refract};
+
+    const EPSILON: f32 = 1e-5;
+
+    fn assert_close(actual: Vector3<f32>, expected: Vector3<f32>) {
+        assert!(
+            (actual - expected).magnitude() < EPSILON,
+            "expected {expected:?}, got {actual:?}"
+        );
+    }
+
+    /// A unit direction hitting a surface with normal `+Y` at `angle` radians from the normal.
+    fn incident(angle: f32) -> Vector3<f32> {
+        Vector3::new(angle.sin(), -angle.cos(), 0.0)
+    }
+
+    #[test]
+    fn reflect_mirrors_around_the_normal() {
+        let reflected = Vector3::new(1.0, -1.0, 0.0).reflect(Vector3::unit_y());
+        assert_close(reflected, Vector3::new(1.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn reflect_reverses_head_on_rays() {
+        let reflected = Vector3::new(0.0, 0.0, -2.0).reflect(Vector3::unit_z());
+        assert_close(reflected, Vector3::new(0.0, 0.0, 2.0));
+    }
+
+    #[test]
+    fn reflect_preserves_length_and_tangential_component() {
+        let direction = Vector3::new(0.3, -0.8, 0.5);
+        let reflected = direction.reflect(Vector3::unit_y());
+
+        assert!((reflected.magnitude() - direction.magnitude()).abs() < EPSILON);
+        assert_close(reflected, Vector3::new(0.3, 0.8, 0.5));
+    }
+
+    #[test]
+    fn reflect_works_in_two_dimensions() {
+        let reflected = Vector2::new(1.0, -1.0).reflect(Vector2::unit_y());
+        assert!((reflected - Vector2::new(1.0, 1.0)).magnitude() < EPSILON);
+    }
+
+    #[test]
+    fn refract_with_equal_indices_keeps_direction() {
+        let direction = incident(0.7);
+        assert_close(direction.refract(Vector3::unit_y(), 1.0), direction);
+    }
+
+    #[test]
+    fn refract_keeps_head_on_rays_straight() {
+        let direction = incident(0.0);
+        assert_close(direction.refract(Vector3::unit_y(), 1.0 / 1.5), direction);
+    }
+
+    #[test]
+    fn refract_follows_snells_law() {
+        let ior_ratio = 1.0 / 1.5;
+        let angle: f32 = 0.6;
+        let refracted = incident(angle).refract(Vector3::unit_y(), ior_ratio);
+
+        let expected_angle = (ior_ratio * angle.sin()).asin();
+        assert!((refracted.magnitude() - 1.0).abs() < EPSILON);
+        assert_close(refracted, incident(expected_angle));
+    }
+
+    #[test]
+    fn can_refract_detects_total_internal_reflection() {
+        // From glass into air, the critical angle is asin(1 / 1.5) ≈ 41.8°
+        let ior_ratio = 1.5;
+        assert!(incident(0.0).can_refract(Vector3::unit_y(), ior_ratio));
+        assert!(incident(30f32.to_radians()).can_refract(Vector3::unit_y(), ior_ratio));
+        assert!(!incident(45f32.to_radians()).can_refract(Vector3::unit_y(), ior_ratio));
+        assert!(!incident(80f32.to_radians()).can_refract(Vector3::unit_y(), ior_ratio));
+    }
 
-pub fn _random_in_unit_hemisphere(normal: Vector3<f32>) -> Vector3<f32> {
-    let random = random_in_unit_sphere();
-    if random.dot(normal) > 0.0 {
-        random
-    } else {
-        -random
+    #[test]
+    fn can_refract_always_succeeds_into_denser_media() {
+        let ior_ratio = 1.0 / 1.5;
+        for degrees in [0.0f32, 30.0, 60.0, 89.0] {
+            assert!(incident(degrees.to_radians()).can_refract(Vector3::unit_y(), ior_ratio));
+        }
     }
 }
@@ -0,0 +1,685 @@
+//! Sampling routines for Monte Carlo integration.
+//!
+//! Every routine returns the sampled value together with the probability density it was
+//! sampled with, so that estimators can weight it correctly. Densities of directions are
+//! with respect to solid angle, densities of points with respect to area.
+
+// Not every routine has a caller in the integrators yet
+#![allow(dead_code)]
+
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+use cgmath::{InnerSpace, Point3, Vector2, Vector3};
+use rand::Rng;
+
+use crate::scene::render_settings::Filter;
+
+/// Standard deviation of the Gaussian reconstruction filter in pixels.
+pub const GAUSSIAN_FILTER_SIGMA: f32 = 0.5;
+
+/// A sampled value and the probability density of sampling it.
+#[derive(Debug, Clone, Copy)]
+pub struct Sample<T> {
+    pub value: T,
+    pub pdf: f32,
+}
+
+/// Builds an orthonormal basis around the unit vector `normal`.
+///
+/// Based on "Building an Orthonormal Basis, Revisited" by Duff et al.
+pub fn orthonormal_basis(normal: Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+    let sign = 1f32.copysign(normal.z);
+    let a = -1.0 / (sign + normal.z);
+    let b = normal.x * normal.y * a;
+    (
+        Vector3::new(
+            1.0 + sign * normal.x * normal.x * a,
+            sign * b,
+            -sign * normal.x,
+        ),
+        Vector3::new(b, sign + normal.y * normal.y * a, -normal.y),
+    )
+}
+
+/// Transforms a direction from the local frame where `normal` is the Z axis to world space.
+pub fn to_world(local: Vector3<f32>, normal: Vector3<f32>) -> Vector3<f32> {
+    let (tangent, bitangent) = orthonormal_basis(normal);
+    tangent * local.x + bitangent * local.y + normal * local.z
+}
+
+/// Transforms a world space direction to the local frame where `normal` is the Z axis.
+pub fn to_local(direction: Vector3<f32>, normal: Vector3<f32>) -> Vector3<f32> {
+    let (tangent, bitangent) = orthonormal_basis(normal);
+    Vector3::new(
+        direction.dot(tangent),
+        direction.dot(bitangent),
+        direction.dot(normal),
+    )
+}
+
+pub fn uniform_sphere(rng: &mut impl Rng) -> Sample<Vector3<f32>> {
+    let z = 1.0 - 2.0 * rng.gen::<f32>();
+    let radius = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * rng.gen::<f32>();
+
+    Sample {
+        value: Vector3::new(radius * phi.cos(), radius * phi.sin(), z),
+        pdf: uniform_sphere_pdf(),
+    }
+}
+
+pub fn uniform_sphere_pdf() -> f32 {
+    1.0 / (4.0 * PI)
+}
+
+/// Samples a direction uniformly from the hemisphere around `normal`.
+pub fn uniform_hemisphere(rng: &mut impl Rng, normal: Vector3<f32>) -> Sample<Vector3<f32>> {
+    let z = rng.gen::<f32>();
+    let radius = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * rng.gen::<f32>();
+
+    Sample {
+        value: to_world(
+            Vector3::new(radius * phi.cos(), radius * phi.sin(), z),
+            normal,
+        ),
+        pdf: uniform_hemisphere_pdf(),
+    }
+}
+
+pub fn uniform_hemisphere_pdf() -> f32 {
+    1.0 / (2.0 * PI)
+}
+
+/// Samples a direction from the hemisphere around `normal` proportionally to the cosine of
+/// its angle with the normal, which matches the distribution of light reflected by a
+/// Lambertian surface.
+pub fn cosine_hemisphere(rng: &mut impl Rng, normal: Vector3<f32>) -> Sample<Vector3<f32>> {
+    // Malley's method: project points uniformly distributed on the disk up to the hemisphere
+    let disk = concentric_disk(rng).value;
+    let z = (1.0 - disk.magnitude2()).max(0.0).sqrt();
+
+    Sample {
+        value: to_world(Vector3::new(disk.x, disk.y, z), normal),
+        pdf: cosine_hemisphere_pdf(z),
+    }
+}
+
+pub fn cosine_hemisphere_pdf(cos_theta: f32) -> f32 {
+    cos_theta.max(0.0) / PI
+}
+
+/// Samples a point uniformly from the unit disk.
+///
+/// Uses Shirley and Chiu's concentric mapping, which keeps neighbouring samples close together.
+pub fn concentric_disk(rng: &mut impl Rng) -> Sample<Vector2<f32>> {
+    let offset = Vector2::new(rng.gen::<f32>(), rng.gen::<f32>()) * 2.0 - Vector2::new(1.0, 1.0);
+
+    let value = if offset.x == 0.0 && offset.y == 0.0 {
+        offset
+    } else {
+        let (radius, theta) = if offset.x.abs() > offset.y.abs() {
+            (offset.x, FRAC_PI_4 * (offset.y / offset.x))
+        } else {
+            (offset.y, FRAC_PI_2 - FRAC_PI_4 * (offset.x / offset.y))
+        };
+        Vector2::new(theta.cos(), theta.sin()) * radius
+    };
+
+    Sample {
+        value,
+        pdf: concentric_disk_pdf(),
+    }
+}
+
+pub fn concentric_disk_pdf() -> f32 {
+    1.0 / PI
+}
+
+/// Samples a direction uniformly from the cone around `axis` whose half-angle has the
+/// cosine `cos_theta_max`.
+pub fn uniform_cone(
+    rng: &mut impl Rng,
+    axis: Vector3<f32>,
+    cos_theta_max: f32,
+) -> Sample<Vector3<f32>> {
+    let cos_theta = 1.0 - rng.gen::<f32>() * (1.0 - cos_theta_max);
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * rng.gen::<f32>();
+
+    Sample {
+        value: to_world(
+            Vector3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta),
+            axis,
+        ),
+        pdf: uniform_cone_pdf(cos_theta_max),
+    }
+}
+
+pub fn uniform_cone_pdf(cos_theta_max: f32) -> f32 {
+    1.0 / (2.0 * PI * (1.0 - cos_theta_max))
+}
+
+/// Samples a point uniformly from the surface of the triangle `a`, `b`, `c`.
+pub fn uniform_triangle(
+    rng: &mut impl Rng,
+    a: Point3<f32>,
+    b: Point3<f32>,
+    c: Point3<f32>,
+) -> Sample<Point3<f32>> {
+    let sqrt_u = rng.gen::<f32>().sqrt();
+    let v = rng.gen::<f32>();
+    let (b0, b1) = (1.0 - sqrt_u, v * sqrt_u);
+
+    Sample {
+        value: a + (b - a) * b1 + (c - a) * (1.0 - b0 - b1),
+        pdf: uniform_triangle_pdf(a, b, c),
+    }
+}
+
+pub fn uniform_triangle_pdf(a: Point3<f32>, b: Point3<f32>, c: Point3<f32>) -> f32 {
+    let area = (b - a).cross(c - a).magnitude() * 0.5;
+    1.0 / area
+}
+
+/// Samples the offset of a camera ray from the center of its pixel, in pixels, proportionally
+/// to the reconstruction `filter`.
+///
+/// The filters are normalized, so averaging the radiance of the samples applies the filter.
+pub fn pixel_filter(rng: &mut impl Rng, filter: Filter) -> Sample<Vector2<f32>> {
+    let value = match filter {
+        Filter::Box => Vector2::new(rng.gen::<f32>() - 0.5, rng.gen::<f32>() - 0.5),
+        Filter::Tent => Vector2::new(sample_tent(rng.gen()), sample_tent(rng.gen())),
+        Filter::Gaussian => {
+            // Box-Muller transform
+            let radius = GAUSSIAN_FILTER_SIGMA * (-2.0 * (1.0 - rng.gen::<f32>()).ln()).sqrt();
+            let phi = 2.0 * PI * rng.gen::<f32>();
+            Vector2::new(radius * phi.cos(), radius * phi.sin())
+        }
+    };
+
+    Sample {
+        value,
+        pdf: pixel_filter_pdf(value, filter),
+    }
+}
+
+pub fn pixel_filter_pdf(offset: Vector2<f32>, filter: Filter) -> f32 {
+    match filter {
+        Filter::Box => {
+            if offset.x.abs() <= 0.5 && offset.y.abs() <= 0.5 {
+                1.0
+            } else {
+                0.0
+            }
+        }
+        Filter::Tent => (1.0 - offset.x.abs()).max(0.0) * (1.0 - offset.y.abs()).max(0.0),
+        Filter::Gaussian => {
+            let variance = GAUSSIAN_FILTER_SIGMA * GAUSSIAN_FILTER_SIGMA;
+            (-offset.magnitude2() / (2.0 * variance)).exp() / (2.0 * PI * variance)
+        }
+    }
+}
+
+/// Maps a uniform random number to the tent distribution on [-1, 1] by inverting its CDF.
+fn sample_tent(u: f32) -> f32 {
+    if u < 0.5 {
+        (2.0 * u).sqrt() - 1.0
+    } else {
+        1.0 - (2.0 - 2.0 * u).sqrt()
+    }
+}
+
+/// The GGX (Trowbridge-Reitz) distribution of microfacet normals, `cos_theta` is the cosine
+/// of the angle between the microfacet normal and the surface normal.
+pub fn ggx_distribution(cos_theta: f32, alpha: f32) -> f32 {
+    if cos_theta <= 0.0 {
+        return 0.0;
+    }
+    let alpha2 = alpha * alpha;
+    let cos2_theta = cos_theta * cos_theta;
+    let denominator = cos2_theta * (alpha2 - 1.0) + 1.0;
+    alpha2 / (PI * denominator * denominator)
+}
+
+/// Smith's masking function for the GGX distribution, `cos_theta` is the cosine of the angle
+/// between the direction and the surface normal.
+pub fn ggx_smith_g1(cos_theta: f32, alpha: f32) -> f32 {
+    if cos_theta <= 0.0 {
+        return 0.0;
+    }
+    let cos2_theta = cos_theta * cos_theta;
+    let tan2_theta = (1.0 - cos2_theta).max(0.0) / cos2_theta;
+    2.0 / (1.0 + (1.0 + alpha * alpha * tan2_theta).sqrt())
+}
+
+/// Samples a microfacet normal of the GGX distribution around `normal` that is visible from
+/// the direction `view`, which points away from the surface.
+///
+/// Based on "Sampling the GGX Distribution of Visible Normals" by Heitz. `alpha` must be
+/// greater than zero.
+pub fn ggx_vndf(
+    rng: &mut impl Rng,
+    view: Vector3<f32>,
+    normal: Vector3<f32>,
+    alpha: f32,
+) -> Sample<Vector3<f32>> {
+    let view_local = to_local(view, normal);
+
+    // Transform the view direction to the hemisphere configuration
+    let view_hemisphere =
+        Vector3::new(alpha * view_local.x, alpha * view_local.y, view_local.z).normalize();
+
+    // Orthonormal basis around the view direction
+    let length2 = view_hemisphere.x * view_hemisphere.x + view_hemisphere.y * view_hemisphere.y;
+    let t1 = if length2 > 0.0 {
+        Vector3::new(-view_hemisphere.y, view_hemisphere.x, 0.0) / length2.sqrt()
+    } else {
+        Vector3::unit_x()
+    };
+    let t2 = view_hemisphere.cross(t1);
+
+    // Sample the projected area of the visible hemisphere
+    let radius = rng.gen::<f32>().sqrt();
+    let phi = 2.0 * PI * rng.gen::<f32>();
+    let p1 = radius * phi.cos();
+    let s = 0.5 * (1.0 + view_hemisphere.z);
+    let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * radius * phi.sin();
+    let normal_hemisphere =
+        t1 * p1 + t2 * p2 + view_hemisphere * (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+
+    // Transform the normal back to the ellipsoid configuration
+    let microfacet_normal = Vector3::new(
+        alpha * normal_hemisphere.x,
+        alpha * normal_hemisphere.y,
+        normal_hemisphere.z.max(0.0),
+    )
+    .normalize();
+
+    Sample {
+        value: to_world(microfacet_normal, normal),
+        pdf: ggx_vndf_pdf_local(view_local, microfacet_normal, alpha),
+    }
+}
+
+/// The density of sampling `microfacet_normal` with [`ggx_vndf`].
+pub fn ggx_vndf_pdf(
+    view: Vector3<f32>,
+    microfacet_normal: Vector3<f32>,
+    normal: Vector3<f32>,
+    alpha: f32,
+) -> f32 {
+    ggx_vndf_pdf_local(
+        to_local(view, normal),
+        to_local(microfacet_normal, normal),
+        alpha,
+    )
+}
+
+fn ggx_vndf_pdf_local(view: Vector3<f32>, microfacet_normal: Vector3<f32>, alpha: f32) -> f32 {
+    if view.z <= 0.0 {
+        return 0.0;
+    }
+    ggx_smith_g1(view.z, alpha)
+        * view.dot(microfacet_normal).max(0.0)
+        * ggx_distribution(microfacet_normal.z, alpha)
+        / view.z
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cmp::Ordering, f32::consts::PI};
+
+    use cgmath::{InnerSpace, Point3, Vector3};
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use super::*;
+
+    const SAMPLE_COUNT: usize = 200_000;
+    const THETA_BINS: usize = 16;
+    const PHI_BINS: usize = 32;
+    /// Number of points per bin and dimension used to integrate the expected density.
+    const INTEGRATION_STEPS: usize = 8;
+
+    fn rng() -> SmallRng {
+        SmallRng::seed_from_u64(0x5EED)
+    }
+
+    /// A normal that is not aligned with any axis, to also test the change of basis.
+    fn tilted_normal() -> Vector3<f32> {
+        Vector3::new(0.3, -0.5, 0.8).normalize()
+    }
+
+    /// Pearson's chi-square test of the observed bin counts against the expected ones.
+    ///
+    /// Bins with small expected counts are pooled together, as the test is not reliable for
+    /// them. The statistic is compared against a significance level of about 1e-5, using the
+    /// Wilson-Hilferty approximation of the chi-square distribution.
+    fn assert_chi_square(name: &str, observed: &[f64], expected: &[f64]) {
+        const MIN_EXPECTED: f64 = 5.0;
+
+        let mut statistic = 0.0;
+        let mut degrees_of_freedom = 0usize;
+        let (mut pooled_observed, mut pooled_expected) = (0.0, 0.0);
+        for (&observed, &expected) in observed.iter().zip(expected) {
+            if expected < MIN_EXPECTED {
+                pooled_observed += observed;
+                pooled_expected += expected;
+            } else {
+                statistic += (observed - expected).powi(2) / expected;
+                degrees_of_freedom += 1;
+            }
+        }
+        if pooled_expected > 0.0 {
+            statistic += (pooled_observed - pooled_expected).powi(2) / pooled_expected;
+            degrees_of_freedom += 1;
+        } else {
+            assert_eq!(
+                pooled_observed, 0.0,
+                "{name}: samples in bins with zero density"
+            );
+        }
+        let degrees_of_freedom = (degrees_of_freedom - 1) as f64;
+
+        let variance = 2.0 / (9.0 * degrees_of_freedom);
+        let z = ((statistic / degrees_of_freedom).cbrt() - (1.0 - variance)) / variance.sqrt();
+        assert!(
+            z < 4.265,
+            "{name}: chi-square statistic {statistic:.1} with {degrees_of_freedom} degrees of freedom"
+        );
+    }
+
+    /// Bins directions by the cosine of their angle with the Z axis and their azimuth.
+    fn direction_bin(direction: Vector3<f32>) -> usize {
+        let cos_theta = direction.z.clamp(-1.0, 1.0);
+        let phi = direction.y.atan2(direction.x).rem_euclid(2.0 * PI);
+        let theta_bin =
+            (((cos_theta + 1.0) * 0.5 * THETA_BINS as f32) as usize).min(THETA_BINS - 1);
+        let phi_bin = ((phi / (2.0 * PI) * PHI_BINS as f32) as usize).min(PHI_BINS - 1);
+        theta_bin * PHI_BINS + phi_bin
+    }
+
+    /// Integrates a density over solid angle in each direction bin, by the midpoint rule in
+    /// `(cos_theta, phi)` where the solid angle measure is uniform.
+    fn expected_direction_counts(pdf: impl Fn(Vector3<f32>) -> f32) -> Vec<f64> {
+        let cos_theta_step = 2.0 / (THETA_BINS * INTEGRATION_STEPS) as f32;
+        let phi_step = 2.0 * PI / (PHI_BINS * INTEGRATION_STEPS) as f32;
+
+        let mut expected = vec![0.0; THETA_BINS * PHI_BINS];
+        for i in 0..THETA_BINS * INTEGRATION_STEPS {
+            let cos_theta = -1.0 + (i as f32 + 0.5) * cos_theta_step;
+            let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+            for j in 0..PHI_BINS * INTEGRATION_STEPS {
+                let phi = (j as f32 + 0.5) * phi_step;
+                let direction =
+                    Vector3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+                let bin = (i / INTEGRATION_STEPS) * PHI_BINS + j / INTEGRATION_STEPS;
+                expected[bin] += (pdf(direction) * cos_theta_step * phi_step) as f64;
+            }
+        }
+        expected.iter().map(|p| p * SAMPLE_COUNT as f64).collect()
+    }
+
+    /// Checks that sampled directions follow `pdf` and that every sample reports its density.
+    ///
+    /// Both the samples and `pdf` are expressed in the local frame around `normal`.
+    fn test_directions(
+        name: &str,
+        normal: Vector3<f32>,
+        mut sample: impl FnMut(&mut SmallRng) -> Sample<Vector3<f32>>,
+        pdf: impl Fn(Vector3<f32>) -> f32,
+    ) {
+        let mut rng = rng();
+        let mut observed = vec![0.0; THETA_BINS * PHI_BINS];
+        for _ in 0..SAMPLE_COUNT {
+            let sample = sample(&mut rng);
+            assert!(
+                (sample.value.magnitude() - 1.0).abs() < 1e-4,
+                "{name}: {:?} is not a unit vector",
+                sample.value
+            );
+
+            let local = to_local(sample.value, normal);
+            let expected_pdf = pdf(local);
+            assert!(
+                (sample.pdf - expected_pdf).abs() <= 1e-3 * expected_pdf.max(1.0),
+                "{name}: reported density {} instead of {expected_pdf} for {local:?}",
+                sample.pdf
+            );
+
+            observed[direction_bin(local)] += 1.0;
+        }
+
+        assert_chi_square(name, &observed, &expected_direction_counts(pdf));
+    }
+
+    #[test]
+    fn orthonormal_basis_is_orthonormal() {
+        for normal in [
+            Vector3::unit_x(),
+            Vector3::unit_z(),
+            -Vector3::unit_z(),
+            tilted_normal(),
+            -tilted_normal(),
+        ] {
+            let (tangent, bitangent) = orthonormal_basis(normal);
+            for vector in [tangent, bitangent] {
+                assert!((vector.magnitude() - 1.0).abs() < 1e-5);
+                assert!(vector.dot(normal).abs() < 1e-5);
+            }
+            assert!(tangent.dot(bitangent).abs() < 1e-5);
+
+            let direction = Vector3::new(0.2, -0.7, 0.4);
+            assert!((to_world(to_local(direction, normal), normal) - direction).magnitude() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn uniform_sphere_is_uniform() {
+        test_directions("uniform sphere", Vector3::unit_z(), uniform_sphere, |_| {
+            uniform_sphere_pdf()
+        });
+    }
+
+    #[test]
+    fn uniform_hemisphere_is_uniform() {
+        let normal = tilted_normal();
+        test_directions(
+            "uniform hemisphere",
+            normal,
+            |rng| uniform_hemisphere(rng, normal),
+            |direction| {
+                if direction.z >= 0.0 {
+                    uniform_hemisphere_pdf()
+                } else {
+                    0.0
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn cosine_hemisphere_is_cosine_weighted() {
+        let normal = tilted_normal();
+        test_directions(
+            "cosine hemisphere",
+            normal,
+            |rng| cosine_hemisphere(rng, normal),
+            |direction| cosine_hemisphere_pdf(direction.z),
+        );
+    }
+
+    #[test]
+    fn uniform_cone_is_uniform() {
+        let normal = tilted_normal();
+        let cos_theta_max = 0.3;
+        test_directions(
+            "uniform cone",
+            normal,
+            |rng| uniform_cone(rng, normal, cos_theta_max),
+            |direction| {
+                if direction.z >= cos_theta_max {
+                    uniform_cone_pdf(cos_theta_max)
+                } else {
+                    0.0
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn ggx_vndf_follows_the_visible_normal_distribution() {
+        let normal = tilted_normal();
+        for (alpha, view_angle) in [(0.3f32, 0.0f32), (0.5, 0.6), (1.0, 1.3)] {
+            let view_local = Vector3::new(view_angle.sin(), 0.0, view_angle.cos());
+            let view = to_world(view_local, normal);
+            test_directions(
+                &format!("GGX VNDF with alpha {alpha} and view angle {view_angle}"),
+                normal,
+                |rng| ggx_vndf(rng, view, normal, alpha),
+                |microfacet_normal| ggx_vndf_pdf_local(view_local, microfacet_normal, alpha),
+            );
+        }
+    }
+
+    #[test]
+    fn ggx_distribution_is_normalized() {
+        // The projected area of the microfacets equals the area of the surface
+        for alpha in [0.3, 0.5, 1.0] {
+            let integral = expected_direction_counts(|microfacet_normal| {
+                ggx_distribution(microfacet_normal.z, alpha) * microfacet_normal.z
+            })
+            .iter()
+            .sum::<f64>()
+                / SAMPLE_COUNT as f64;
+            assert!((integral - 1.0).abs() < 1e-2, "alpha {alpha}: {integral}");
+        }
+    }
+
+    #[test]
+    fn concentric_disk_is_uniform() {
+        const RADIUS_BINS: usize = 16;
+        const ANGLE_BINS: usize = 32;
+
+        let mut rng = rng();
+        let mut observed = vec![0.0; RADIUS_BINS * ANGLE_BINS];
+        for _ in 0..SAMPLE_COUNT {
+            let sample = concentric_disk(&mut rng);
+            assert_eq!(sample.pdf, concentric_disk_pdf());
+
+            // Bins of equal area
+            let radius2 = sample.value.magnitude2();
+            assert!(
+                radius2 <= 1.0 + 1e-5,
+                "{:?} is outside of the disk",
+                sample.value
+            );
+            let angle = sample.value.y.atan2(sample.value.x).rem_euclid(2.0 * PI);
+            let radius_bin = ((radius2 * RADIUS_BINS as f32) as usize).min(RADIUS_BINS - 1);
+            let angle_bin = ((angle / (2.0 * PI) * ANGLE_BINS as f32) as usize).min(ANGLE_BINS - 1);
+            observed[radius_bin * ANGLE_BINS + angle_bin] += 1.0;
+        }
+
+        let expected = vec![SAMPLE_COUNT as f64 / observed.len() as f64; observed.len()];
+        assert_chi_square("concentric disk", &observed, &expected);
+    }
+
+    #[test]
+    fn pixel_filters_follow_their_densities() {
+        // The filters are separable, so it is enough to test the horizontal offsets
+        const BINS: usize = 32;
+        const EXTENT: f32 = 2.0;
+        let bin_width = 2.0 * EXTENT / BINS as f32;
+
+        let marginal_pdf = |filter: Filter, x: f32| match filter {
+            Filter::Box => pixel_filter_pdf(Vector2::new(x, 0.0), filter),
+            Filter::Tent => (1.0 - x.abs()).max(0.0),
+            Filter::Gaussian => {
+                pixel_filter_pdf(Vector2::new(x, 0.0), filter)
+                    * (2.0 * PI).sqrt()
+                    * GAUSSIAN_FILTER_SIGMA
+            }
+        };
+
+        for filter in [Filter::Box, Filter::Tent, Filter::Gaussian] {
+            let name = format!("{filter:?} filter");
+
+            let mut rng = rng();
+            // The last bin holds the samples outside of the extent
+            let mut observed = vec![0.0; BINS + 1];
+            for _ in 0..SAMPLE_COUNT {
+                let sample = pixel_filter(&mut rng, filter);
+                let expected_pdf = pixel_filter_pdf(sample.value, filter);
+                assert!(
+                    (sample.pdf - expected_pdf).abs() <= 1e-4 * expected_pdf.max(1.0),
+                    "{name}: reported density {} instead of {expected_pdf}",
+                    sample.pdf
+                );
+
+                let bin = ((sample.value.x + EXTENT) / bin_width).floor();
+                observed[if (0.0..BINS as f32).contains(&bin) {
+                    bin as usize
+                } else {
+                    BINS
+                }] += 1.0;
+            }
+
+            let step = bin_width / INTEGRATION_STEPS as f32;
+            let mut expected = vec![0.0; BINS + 1];
+            for i in 0..BINS * INTEGRATION_STEPS {
+                let x = -EXTENT + (i as f32 + 0.5) * step;
+                expected[i / INTEGRATION_STEPS] +=
+                    (marginal_pdf(filter, x) * step) as f64 * SAMPLE_COUNT as f64;
+            }
+            expected[BINS] = (SAMPLE_COUNT as f64 - expected.iter().sum::<f64>()).max(0.0);
+
+            assert_chi_square(&name, &observed, &expected);
+        }
+    }
+
+    #[test]
+    fn uniform_triangle_is_uniform() {
+        const BINS: usize = 16;
+
+        // The barycentric coordinates of the samples are their X and Y coordinates
+        let (a, b, c) = (
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+        );
+
+        let mut rng = rng();
+        let mut observed = vec![0.0; BINS * BINS];
+        for _ in 0..SAMPLE_COUNT {
+            let sample = uniform_triangle(&mut rng, a, b, c);
+            assert!((sample.pdf - 2.0).abs() < 1e-5);
+
+            let point = sample.value;
+            assert!(point.x >= 0.0 && point.y >= 0.0 && point.x + point.y <= 1.0 + 1e-5);
+            let x_bin = ((point.x * BINS as f32) as usize).min(BINS - 1);
+            let y_bin = ((point.y * BINS as f32) as usize).min(BINS - 1);
+            observed[y_bin * BINS + x_bin] += 1.0;
+        }
+
+        // Bins below the diagonal lie inside of the triangle, the ones on it are cut in half
+        let bin_count = SAMPLE_COUNT as f64 * 2.0 / (BINS * BINS) as f64;
+        let expected: Vec<_> = (0..BINS * BINS)
+            .map(|bin| match (bin / BINS + bin % BINS).cmp(&(BINS - 1)) {
+                Ordering::Less => bin_count,
+                Ordering::Equal => bin_count * 0.5,
+                Ordering::Greater => 0.0,
+            })
+            .collect();
+        assert_chi_square("uniform triangle", &observed, &expected);
+    }
+
+    #[test]
+    fn uniform_triangle_density_is_inverse_area() {
+        let pdf = uniform_triangle_pdf(
+            Point3::new(1.0, 2.0, 3.0),
+            Point3::new(4.0, 2.0, 3.0),
+            Point3::new(1.0, 2.0, 7.0),
+        );
+        assert!((pdf - 1.0 / 6.0).abs() < 1e-6);
+    }
+}
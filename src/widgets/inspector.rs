@@ -1,12 +1,13 @@
-use cgmath::{Deg, Vector2, Vector3};
+use cgmath::{Deg, Vector3};
 use egui::{Grid, Layout};
 
 use crate::{
-    renderer::Renderer,
+    renderer::{timing::Profiler, Renderer},
     scene::{
         camera::{Camera, Projection},
         material::Material,
         objects::{Cube, Geometry, Object, Sphere},
+        render_settings::{Filter, RenderSettings, Tonemapping},
         world::World,
         Scene,
     },
@@ -14,7 +15,6 @@ use crate::{
 
 pub struct Inspector<'a> {
     scene: &'a mut Scene,
-    original_resolution: &'a mut Vector2<u32>,
     renderer: &'a mut dyn Renderer,
     needs_rerender: &'a mut bool,
     should_constantly_rerender: &'a mut bool,
@@ -23,14 +23,12 @@ pub struct Inspector<'a> {
 impl<'a> Inspector<'a> {
     pub fn new(
         scene: &'a mut Scene,
-        original_resolution: &'a mut Vector2<u32>,
         renderer: &'a mut dyn Renderer,
         needs_rerender: &'a mut bool,
         should_constantly_rerender: &'a mut bool,
     ) -> Self {
         Self {
             scene,
-            original_resolution,
             renderer,
             needs_rerender,
             should_constantly_rerender,
@@ -76,21 +74,24 @@ impl<'a> Inspector<'a> {
                             ui.label(format!(
                                 "Sample {}/{} took {}ms",
                                 self.renderer.sample_count(),
-                                self.renderer.max_sample_count(),
+                                self.renderer.config().max_sample_count,
                                 last_sample_duration.as_millis()
                             ));
                         }
 
-                        WorldEditor::new(&mut self.scene.world, self.needs_rerender).show(ui);
+                        StatisticsView::new(self.renderer.profiler()).show(ui);
 
-                        CameraEditor::new(
-                            &mut self.scene.camera,
+                        RenderSettingsEditor::new(
+                            &mut self.scene.render_settings,
                             self.renderer,
-                            self.original_resolution,
                             self.needs_rerender,
                         )
                         .show(ui);
 
+                        WorldEditor::new(&mut self.scene.world, self.needs_rerender).show(ui);
+
+                        CameraEditor::new(&mut self.scene.camera, self.needs_rerender).show(ui);
+
                         ui.horizontal(|ui| {
                             let available_width = ui.available_width();
                             let button_width = (available_width - 8.0) / 2.0; // 8.0 for spacing
@@ -143,70 +144,354 @@ impl<'a> Inspector<'a> {
     }
 }
 
-pub struct CameraEditor<'a> {
-    camera: &'a mut Camera,
-    original_resolution: &'a mut Vector2<u32>,
+/// Shows the statistics the renderer gathered about the traced rays and paths.
+pub struct StatisticsView<'a> {
+    profiler: &'a Profiler,
+}
+
+impl<'a> StatisticsView<'a> {
+    pub fn new(profiler: &'a Profiler) -> Self {
+        Self { profiler }
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        let statistics = self.profiler.path_statistics();
+        let unavailable = || "n/a".to_string();
+
+        ui.collapsing("Statistics", |ui| {
+            Grid::new("statistics_grid")
+                .num_columns(2)
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("Samples per Second");
+                    ui.label(
+                        self.profiler
+                            .samples_per_second()
+                            .map_or_else(unavailable, |rate| format!("{rate:.1}")),
+                    );
+                    ui.end_row();
+
+                    ui.label("Primary Rays");
+                    ui.label(statistics.primary_rays().to_string());
+                    ui.end_row();
+
+                    ui.label("Secondary Rays");
+                    ui.label(statistics.secondary_rays().to_string());
+                    ui.end_row();
+
+                    ui.label("Shadow Rays");
+                    ui.label(statistics.shadow_rays().to_string());
+                    ui.end_row();
+
+                    ui.label("Intersection Tests");
+                    ui.label(
+                        statistics
+                            .intersection_tests()
+                            .map_or_else(unavailable, |count| count.to_string()),
+                    );
+                    ui.end_row();
+
+                    ui.label("BVH Nodes Visited");
+                    ui.label(
+                        statistics
+                            .bvh_nodes_visited()
+                            .map_or_else(unavailable, |count| count.to_string()),
+                    );
+                    ui.end_row();
+
+                    ui.label("Average Path Length");
+                    ui.label(
+                        statistics
+                            .average_length()
+                            .map_or_else(unavailable, |length| format!("{length:.2}")),
+                    );
+                    ui.end_row();
+                });
+
+            if statistics.path_count() > 0 {
+                ui.label("Path Lengths");
+                let histogram = statistics.length_histogram();
+                let last_bin = histogram.len() - 1;
+                for (length, &count) in histogram.iter().enumerate() {
+                    if count == 0 {
+                        continue;
+                    }
+                    let fraction = count as f32 / statistics.path_count() as f32;
+                    let length = if length == last_bin {
+                        format!("{length}+")
+                    } else {
+                        length.to_string()
+                    };
+                    ui.add(
+                        egui::ProgressBar::new(fraction)
+                            .text(format!("{length}: {:.1}%", fraction * 100.0)),
+                    );
+                }
+            }
+        });
+    }
+}
+
+pub struct RenderSettingsEditor<'a> {
+    settings: &'a mut RenderSettings,
     renderer: &'a mut dyn Renderer,
     needs_rerender: &'a mut bool,
 }
 
-impl<'a> CameraEditor<'a> {
+impl<'a> RenderSettingsEditor<'a> {
     pub fn new(
-        camera: &'a mut Camera,
+        settings: &'a mut RenderSettings,
         renderer: &'a mut dyn Renderer,
-        original_resolution: &'a mut Vector2<u32>,
         needs_rerender: &'a mut bool,
     ) -> Self {
         Self {
-            camera,
-            original_resolution,
+            settings,
             renderer,
             needs_rerender,
         }
     }
 
     pub fn show(&mut self, ui: &mut egui::Ui) {
-        ui.collapsing("Camera", |ui| {
-            Grid::new("camera_grid")
+        ui.collapsing("Render Settings", |ui| {
+            Grid::new("render_settings_grid")
                 .num_columns(2)
                 .striped(true)
                 .show(ui, |ui| {
-                    // FIXME: This doesn't belong in the camera editor
-                    // It should be a new renderer editor
+                    // The viewport is rendered at its own size, the resolution only applies to
+                    // renders of the saved scene
+                    ui.label("Resolution X");
+                    ui.with_layout(Layout::top_down_justified(egui::Align::Min), |ui| {
+                        ui.add(
+                            egui::DragValue::new(&mut self.settings.resolution.width)
+                                .speed(10.0)
+                                .range(1..=10000),
+                        );
+                    });
+                    ui.end_row();
+
+                    ui.label("Resolution Y");
+                    ui.with_layout(Layout::top_down_justified(egui::Align::Min), |ui| {
+                        ui.add(
+                            egui::DragValue::new(&mut self.settings.resolution.height)
+                                .speed(10.0)
+                                .range(1..=10000),
+                        );
+                    });
+                    ui.end_row();
+
                     ui.label("Max Sample Count");
                     ui.with_layout(Layout::top_down_justified(egui::Align::Min), |ui| {
-                        let mut max_samples = self.renderer.max_sample_count();
                         if ui
                             .add(
-                                egui::DragValue::new(&mut max_samples)
+                                egui::DragValue::new(&mut self.settings.samples)
                                     .speed(10.0)
                                     .range(1..=10000),
                             )
                             .changed()
                         {
-                            self.renderer.set_max_sample_count(max_samples);
+                            self.renderer.config_mut().max_sample_count = self.settings.samples;
                             *self.needs_rerender = true;
                         }
                     });
                     ui.end_row();
 
-                    ui.label("Max Bounces");
+                    ui.label("Seed");
                     ui.with_layout(Layout::top_down_justified(egui::Align::Min), |ui| {
-                        let mut max_bounces = self.renderer.max_bounces();
                         if ui
-                            .add(
-                                egui::DragValue::new(&mut max_bounces)
+                            .add(egui::DragValue::new(&mut self.settings.seed).speed(1.0))
+                            .changed()
+                        {
+                            self.renderer.config_mut().seed = self.settings.seed;
+                            *self.needs_rerender = true;
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Filter");
+                    ui.with_layout(Layout::top_down_justified(egui::Align::Min), |ui| {
+                        let mut changed = false;
+                        egui::ComboBox::from_id_salt("filter")
+                            .selected_text(format!("{:?}", self.settings.filter))
+                            .show_ui(ui, |ui| {
+                                for filter in [Filter::Box, Filter::Tent, Filter::Gaussian] {
+                                    changed |= ui
+                                        .selectable_value(
+                                            &mut self.settings.filter,
+                                            filter,
+                                            format!("{filter:?}"),
+                                        )
+                                        .clicked();
+                                }
+                            });
+                        if changed {
+                            self.renderer.config_mut().filter = self.settings.filter;
+                            *self.needs_rerender = true;
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Tonemapping");
+                    ui.with_layout(Layout::top_down_justified(egui::Align::Min), |ui| {
+                        let mut changed = false;
+                        egui::ComboBox::from_id_salt("tonemapping")
+                            .selected_text(format!("{:?}", self.settings.tonemapping))
+                            .show_ui(ui, |ui| {
+                                for tonemapping in
+                                    [Tonemapping::None, Tonemapping::Reinhard, Tonemapping::Aces]
+                                {
+                                    changed |= ui
+                                        .selectable_value(
+                                            &mut self.settings.tonemapping,
+                                            tonemapping,
+                                            format!("{tonemapping:?}"),
+                                        )
+                                        .clicked();
+                                }
+                            });
+                        if changed {
+                            self.renderer.config_mut().tonemapping = self.settings.tonemapping;
+                            *self.needs_rerender = true;
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Output");
+                    ui.with_layout(Layout::top_down_justified(egui::Align::Min), |ui| {
+                        let mut output = self.settings.output.to_string_lossy().into_owned();
+                        if ui.text_edit_singleline(&mut output).changed() {
+                            self.settings.output = output.into();
+                        }
+                    });
+                    ui.end_row();
+
+                    // Like the resolution, the time limit only applies to renders of the
+                    // saved scene
+                    ui.label("Time Limit");
+                    ui.horizontal(|ui| {
+                        let mut enabled = self.settings.time_limit.is_some();
+                        if ui.checkbox(&mut enabled, "").changed() {
+                            self.settings.time_limit = enabled.then_some(60.0);
+                        }
+                        if let Some(time_limit) = &mut self.settings.time_limit {
+                            ui.add(
+                                egui::DragValue::new(time_limit)
                                     .speed(1.0)
-                                    .range(1..=100),
-                            )
+                                    .range(1.0..=f32::MAX)
+                                    .suffix(" s"),
+                            );
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Russian Roulette");
+                    ui.with_layout(Layout::top_down_justified(egui::Align::Min), |ui| {
+                        if ui
+                            .checkbox(&mut self.settings.russian_roulette, "Enabled")
                             .changed()
                         {
-                            self.renderer.set_max_bounces(max_bounces);
+                            self.renderer.config_mut().russian_roulette =
+                                self.settings.russian_roulette;
                             *self.needs_rerender = true;
                         }
                     });
                     ui.end_row();
 
+                    ui.label("Roulette Min Depth");
+                    ui.add_enabled_ui(self.settings.russian_roulette, |ui| {
+                        ui.with_layout(Layout::top_down_justified(egui::Align::Min), |ui| {
+                            if ui
+                                .add(
+                                    egui::DragValue::new(
+                                        &mut self.settings.russian_roulette_min_depth,
+                                    )
+                                    .speed(1.0)
+                                    .range(0..=100),
+                                )
+                                .changed()
+                            {
+                                self.renderer.config_mut().russian_roulette_min_depth =
+                                    self.settings.russian_roulette_min_depth;
+                                *self.needs_rerender = true;
+                            }
+                        });
+                    });
+                    ui.end_row();
+
+                    let mut bounces_changed = false;
+                    for (label, limit) in [
+                        ("Max Bounces", &mut self.settings.bounces.total),
+                        ("Diffuse Bounces", &mut self.settings.bounces.diffuse),
+                        ("Glossy Bounces", &mut self.settings.bounces.glossy),
+                        (
+                            "Transmission Bounces",
+                            &mut self.settings.bounces.transmission,
+                        ),
+                    ] {
+                        ui.label(label);
+                        ui.with_layout(Layout::top_down_justified(egui::Align::Min), |ui| {
+                            bounces_changed |= ui
+                                .add(egui::DragValue::new(limit).speed(1.0).range(0..=100))
+                                .changed();
+                        });
+                        ui.end_row();
+                    }
+                    if bounces_changed {
+                        self.renderer.config_mut().bounces = self.settings.bounces;
+                        *self.needs_rerender = true;
+                    }
+
+                    let mut clamping_changed = false;
+                    for (label, limit) in [
+                        ("Clamp Direct", &mut self.settings.clamping.direct),
+                        ("Clamp Indirect", &mut self.settings.clamping.indirect),
+                    ] {
+                        ui.label(label);
+                        ui.horizontal(|ui| {
+                            let mut enabled = limit.is_some();
+                            if ui.checkbox(&mut enabled, "").changed() {
+                                *limit = enabled.then_some(10.0);
+                                clamping_changed = true;
+                            }
+                            if let Some(limit) = limit {
+                                clamping_changed |= ui
+                                    .add(
+                                        egui::DragValue::new(limit)
+                                            .speed(0.1)
+                                            .range(0.0..=f32::MAX),
+                                    )
+                                    .changed();
+                            }
+                        });
+                        ui.end_row();
+                    }
+                    if clamping_changed {
+                        self.renderer.config_mut().clamping = self.settings.clamping;
+                        *self.needs_rerender = true;
+                    }
+                });
+        });
+    }
+}
+
+pub struct CameraEditor<'a> {
+    camera: &'a mut Camera,
+    needs_rerender: &'a mut bool,
+}
+
+impl<'a> CameraEditor<'a> {
+    pub fn new(camera: &'a mut Camera, needs_rerender: &'a mut bool) -> Self {
+        Self {
+            camera,
+            needs_rerender,
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        ui.collapsing("Camera", |ui| {
+            Grid::new("camera_grid")
+                .num_columns(2)
+                .striped(true)
+                .show(ui, |ui| {
                     // Position
                     ui.label("Position X");
                     ui.with_layout(Layout::top_down_justified(egui::Align::Min), |ui| {
@@ -404,27 +689,6 @@ impl<'a> CameraEditor<'a> {
                             ui.end_row();
                         }
                     }
-
-                    // Resolution
-                    ui.label("Resolution X");
-                    ui.with_layout(Layout::top_down_justified(egui::Align::Min), |ui| {
-                        ui.add(
-                            egui::DragValue::new(&mut self.original_resolution.x)
-                                .speed(10.0)
-                                .range(1..=10000),
-                        );
-                    });
-                    ui.end_row();
-
-                    ui.label("Resolution Y");
-                    ui.with_layout(Layout::top_down_justified(egui::Align::Min), |ui| {
-                        ui.add(
-                            egui::DragValue::new(&mut self.original_resolution.y)
-                                .speed(10.0)
-                                .range(1..=10000),
-                        );
-                    });
-                    ui.end_row();
                 });
         });
     }
@@ -751,13 +1015,9 @@ impl<'a> WorldEditor<'a> {
                                     )
                                     .clicked();
 
-                                // changed |= ui
-                                //     .selectable_value(
-                                //         &mut self.scene.world,
-                                //         World::Transparent,
-                                //         "Transparent",
-                                //     )
-                                //     .clicked();
+                                changed |= ui
+                                    .selectable_value(self.world, World::Transparent, "Transparent")
+                                    .clicked();
                                 changed |= ui
                                     .selectable_value(
                                         self.world,
@@ -0,0 +1,102 @@
+//! Checks that the CPU and Vulkan renderers agree on the same scenes.
+//!
+//! Both backends are Monte Carlo estimators with different random sequences, so their images
+//! are compared statistically rather than pixel by pixel. The Vulkan side is skipped when no
+//! device is available; set `VK_ICD_FILENAMES` to a software implementation such as lavapipe
+//! to run it without a GPU.
+
+mod common;
+
+use raydar::{
+    renderer::{cpu::CpuRenderer, vulkan::VulkanRenderer, Renderer, RendererConfig},
+    scene::Scene,
+};
+
+use common::{compare, render_radiance};
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 36;
+const SAMPLE_COUNT: u32 = 256;
+const SEED: u32 = 1234;
+
+/// Block size used to average out per-pixel noise before comparing.
+const BLOCK_SIZE: u32 = 4;
+/// Tolerated mean absolute error per channel, in displayable units.
+const MAX_MEAN_ABSOLUTE_ERROR: f32 = 0.03;
+/// Tolerated systematic brightness difference per channel, in displayable units.
+const MAX_BIAS: f32 = 0.015;
+
+fn config(seed: u32) -> RendererConfig {
+    RendererConfig {
+        max_sample_count: SAMPLE_COUNT,
+        seed,
+        ..Default::default()
+    }
+}
+
+fn reference_scenes() -> Vec<(&'static str, Scene)> {
+    vec![
+        (
+            "diffuse sphere",
+            common::diffuse_sphere_scene(WIDTH, HEIGHT),
+        ),
+        (
+            "metal and glass",
+            common::metal_and_glass_scene(WIDTH, HEIGHT),
+        ),
+        ("emissive cube", common::emissive_cube_scene(WIDTH, HEIGHT)),
+    ]
+}
+
+fn assert_consistent(name: &str, reference: &str, test: &str, comparison: common::ImageComparison) {
+    assert!(
+        comparison.max_mean_absolute_error() <= MAX_MEAN_ABSOLUTE_ERROR
+            && comparison.max_bias() <= MAX_BIAS,
+        "{test} and {reference} disagree on the {name} scene: {comparison:?}",
+    );
+}
+
+#[test]
+fn cpu_renders_are_reproducible_with_the_same_seed() {
+    let scene = common::diffuse_sphere_scene(16, 9);
+
+    let first = render_radiance(&mut CpuRenderer::new(config(SEED)), &scene);
+    let second = render_radiance(&mut CpuRenderer::new(config(SEED)), &scene);
+
+    assert_eq!(first, second);
+}
+
+/// Renders with different seeds only differ by noise, this is the baseline for the
+/// cross-backend tolerances.
+#[test]
+fn cpu_renders_with_different_seeds_agree() {
+    for (name, scene) in reference_scenes() {
+        let reference = render_radiance(&mut CpuRenderer::new(config(SEED)), &scene);
+        let test = render_radiance(&mut CpuRenderer::new(config(SEED + 1)), &scene);
+
+        assert_consistent(name, "CPU", "CPU", compare(&reference, &test, BLOCK_SIZE));
+    }
+}
+
+#[test]
+fn vulkan_matches_cpu() {
+    let mut vulkan = match VulkanRenderer::new(config(SEED)) {
+        Ok(renderer) => renderer,
+        Err(err) => {
+            eprintln!("Skipping Vulkan consistency test: {err}");
+            return;
+        }
+    };
+
+    for (name, scene) in reference_scenes() {
+        let reference = render_radiance(&mut CpuRenderer::new(config(SEED)), &scene);
+        let test = render_radiance(&mut vulkan, &scene);
+
+        assert_consistent(
+            name,
+            "CPU",
+            vulkan.name(),
+            compare(&reference, &test, BLOCK_SIZE),
+        );
+    }
+}
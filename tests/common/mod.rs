@@ -0,0 +1,250 @@
+//! Scenes and image statistics shared by the integration tests.
+
+// Every test binary compiles its own copy of this module and uses only part of it
+#![allow(dead_code)]
+
+use cgmath::{Deg, Point3, Vector3};
+use image::Rgba32FImage;
+use raydar::{
+    renderer::{output, Renderer},
+    scene::{
+        camera::{Camera, Projection},
+        material::Material,
+        objects::{Cube, Geometry, Object, Sphere},
+        world::World,
+        Scene,
+    },
+};
+
+pub fn camera(position: Point3<f32>, target: Point3<f32>, width: u32, height: u32) -> Camera {
+    Camera::new(
+        position,
+        target,
+        Vector3::unit_y(),
+        width,
+        height,
+        0.01,
+        1000.0,
+        Projection::Perspective { fov: Deg(60.0) },
+    )
+}
+
+pub fn sphere(center: Point3<f32>, radius: f32, material: Material) -> Object {
+    Object {
+        geometry: Geometry::Sphere(Sphere { center, radius }),
+        material,
+    }
+}
+
+pub fn cube(center: Point3<f32>, side_length: f32, material: Material) -> Object {
+    Object {
+        geometry: Geometry::Cube(Cube {
+            center,
+            side_length,
+        }),
+        material,
+    }
+}
+
+/// A large cube whose top face acts as the ground plane at `y = 0`.
+pub fn ground(material: Material) -> Object {
+    cube(Point3::new(0.0, -100.0, 0.0), 200.0, material)
+}
+
+fn sky() -> World {
+    World::SkyColor {
+        top_color: Vector3::new(0.53, 0.8, 0.92),
+        bottom_color: Vector3::new(1.0, 1.0, 1.0),
+    }
+}
+
+/// A rough diffuse sphere on a diffuse ground under the sky.
+pub fn diffuse_sphere_scene(width: u32, height: u32) -> Scene {
+    Scene {
+        camera: camera(
+            Point3::new(0.0, 1.5, -4.0),
+            Point3::new(0.0, 0.8, 0.0),
+            width,
+            height,
+        ),
+        world: sky(),
+        objects: vec![
+            sphere(
+                Point3::new(0.0, 1.0, 0.0),
+                1.0,
+                Material {
+                    albedo: Vector3::new(0.8, 0.3, 0.2),
+                    roughness: 1.0,
+                    ..Default::default()
+                },
+            ),
+            ground(Material {
+                albedo: Vector3::new(0.5, 0.5, 0.5),
+                roughness: 1.0,
+                ..Default::default()
+            }),
+        ],
+        render_settings: Default::default(),
+        animation: Default::default(),
+    }
+}
+
+/// A polished metal sphere next to a glass sphere, which exercise reflection and refraction.
+pub fn metal_and_glass_scene(width: u32, height: u32) -> Scene {
+    Scene {
+        camera: camera(
+            Point3::new(0.0, 1.5, -5.0),
+            Point3::new(0.0, 0.8, 0.0),
+            width,
+            height,
+        ),
+        world: sky(),
+        objects: vec![
+            sphere(
+                Point3::new(-1.1, 1.0, 0.0),
+                1.0,
+                Material {
+                    albedo: Vector3::new(0.9, 0.9, 0.9),
+                    roughness: 0.2,
+                    metallic: 1.0,
+                    ..Default::default()
+                },
+            ),
+            sphere(
+                Point3::new(1.1, 1.0, 0.0),
+                1.0,
+                Material {
+                    albedo: Vector3::new(1.0, 1.0, 1.0),
+                    roughness: 0.0,
+                    ..Material::with_transmission(1.0, 1.5)
+                },
+            ),
+            ground(Material {
+                albedo: Vector3::new(0.4, 0.4, 0.5),
+                roughness: 1.0,
+                ..Default::default()
+            }),
+        ],
+        render_settings: Default::default(),
+        animation: Default::default(),
+    }
+}
+
+/// A diffuse sphere lit only by an emissive cube in a dark world.
+pub fn emissive_cube_scene(width: u32, height: u32) -> Scene {
+    Scene {
+        camera: camera(
+            Point3::new(0.0, 2.0, -5.0),
+            Point3::new(0.0, 0.8, 0.0),
+            width,
+            height,
+        ),
+        world: World::SolidColor(Vector3::new(0.02, 0.02, 0.02)),
+        objects: vec![
+            sphere(
+                Point3::new(-0.8, 0.8, 0.0),
+                0.8,
+                Material {
+                    albedo: Vector3::new(0.8, 0.8, 0.8),
+                    roughness: 1.0,
+                    ..Default::default()
+                },
+            ),
+            cube(
+                Point3::new(1.2, 0.6, 0.5),
+                1.2,
+                Material::with_emission(Vector3::new(1.0, 0.7, 0.4), 4.0),
+            ),
+            ground(Material {
+                albedo: Vector3::new(0.5, 0.5, 0.5),
+                roughness: 1.0,
+                ..Default::default()
+            }),
+        ],
+        render_settings: Default::default(),
+        animation: Default::default(),
+    }
+}
+
+/// Renders a full frame and returns the averaged radiance.
+pub fn render_radiance(renderer: &mut dyn Renderer, scene: &Scene) -> Rgba32FImage {
+    renderer.render_frame(scene);
+    let frame_buffer = renderer
+        .frame_buffer()
+        .expect("renderer did not produce a frame buffer");
+    output::resolve(frame_buffer, renderer.sample_count())
+}
+
+/// Statistics of the per-channel difference between two images.
+#[derive(Debug, Clone, Copy)]
+pub struct ImageComparison {
+    /// The mean absolute difference of each color channel.
+    pub mean_absolute_error: [f32; 3],
+    /// The mean signed difference of each color channel, `test - reference`.
+    pub bias: [f32; 3],
+}
+
+impl ImageComparison {
+    pub fn max_mean_absolute_error(&self) -> f32 {
+        self.mean_absolute_error.into_iter().fold(0.0, f32::max)
+    }
+
+    pub fn max_bias(&self) -> f32 {
+        self.bias.into_iter().map(f32::abs).fold(0.0, f32::max)
+    }
+}
+
+/// Compares two radiance images.
+///
+/// Both images are clamped to the displayable range and averaged over blocks of
+/// `block_size` x `block_size` pixels first, which suppresses the per-pixel Monte Carlo
+/// noise while keeping systematic differences.
+pub fn compare(reference: &Rgba32FImage, test: &Rgba32FImage, block_size: u32) -> ImageComparison {
+    assert_eq!(
+        reference.dimensions(),
+        test.dimensions(),
+        "compared images must have the same size"
+    );
+
+    let reference = downsample(reference, block_size);
+    let test = downsample(test, block_size);
+
+    let mut mean_absolute_error = [0.0; 3];
+    let mut bias = [0.0; 3];
+    for (reference, test) in reference.iter().zip(&test) {
+        for (channel, (reference, test)) in reference.iter().zip(test).enumerate() {
+            let difference = test - reference;
+            mean_absolute_error[channel] += difference.abs();
+            bias[channel] += difference;
+        }
+    }
+
+    let count = reference.len().max(1) as f32;
+    ImageComparison {
+        mean_absolute_error: mean_absolute_error.map(|sum| sum / count),
+        bias: bias.map(|sum| sum / count),
+    }
+}
+
+/// Averages the clamped color of each block of pixels.
+fn downsample(image: &Rgba32FImage, block_size: u32) -> Vec<[f32; 3]> {
+    let block_size = block_size.max(1);
+    let mut blocks = Vec::new();
+    for block_y in (0..image.height()).step_by(block_size as usize) {
+        for block_x in (0..image.width()).step_by(block_size as usize) {
+            let mut sum = [0.0; 3];
+            let mut count = 0;
+            for y in block_y..(block_y + block_size).min(image.height()) {
+                for x in block_x..(block_x + block_size).min(image.width()) {
+                    let pixel = image.get_pixel(x, y);
+                    for (sum, value) in sum.iter_mut().zip(pixel.0) {
+                        *sum += value.clamp(0.0, 1.0);
+                    }
+                    count += 1;
+                }
+            }
+            blocks.push(sum.map(|sum| sum / count as f32));
+        }
+    }
+    blocks
+}
@@ -0,0 +1,474 @@
+//! Golden-image regression tests.
+//!
+//! Renders small canonical scenes with the CPU renderer at a fixed seed and compares them
+//! against the reference images in `tests/golden/`.
+//!
+//! ```bash
+//! cargo test --release --test golden                     # compare against the references
+//! cargo test --release --test golden -- --bless          # update the references
+//! cargo test --release --test golden -- cornell --bless  # update only matching cases
+//! ```
+//!
+//! When a comparison fails, the rendered image is written next to the test build artifacts
+//! so it can be inspected.
+
+mod common;
+
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use cgmath::{Point3, Vector3};
+use image::{Rgb, RgbImage};
+use raydar::{
+    renderer::{cpu::CpuRenderer, Renderer, RendererConfig},
+    scene::{material::Material, world::World, Scene},
+};
+
+use common::{cube, ground, sphere};
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 48;
+const SAMPLE_COUNT: u32 = 64;
+const SEED: u32 = 7;
+
+/// The minimum peak signal-to-noise ratio, in decibels.
+const MIN_PSNR: f64 = 35.0;
+/// The maximum mean perceptual color difference (CIE76 ΔE after a 3x3 box filter).
+/// A difference of about 2.3 is just noticeable.
+const MAX_MEAN_DELTA_E: f64 = 1.0;
+
+struct GoldenCase {
+    name: &'static str,
+    scene: fn() -> Scene,
+}
+
+const CASES: &[GoldenCase] = &[
+    GoldenCase {
+        name: "cornell_box",
+        scene: cornell_box,
+    },
+    GoldenCase {
+        name: "glass_sphere",
+        scene: glass_sphere,
+    },
+    GoldenCase {
+        name: "metal_spheres",
+        scene: metal_spheres,
+    },
+    GoldenCase {
+        name: "emissive_cube",
+        scene: || common::emissive_cube_scene(WIDTH, HEIGHT),
+    },
+    GoldenCase {
+        name: "world_sky_color",
+        scene: || {
+            with_world(World::SkyColor {
+                top_color: Vector3::new(0.53, 0.8, 0.92),
+                bottom_color: Vector3::new(1.0, 1.0, 1.0),
+            })
+        },
+    },
+    GoldenCase {
+        name: "world_solid_color",
+        scene: || with_world(World::SolidColor(Vector3::new(0.9, 0.6, 0.3))),
+    },
+    GoldenCase {
+        name: "world_transparent",
+        // Lit by an emissive cube, since a transparent world emits no light
+        scene: || Scene {
+            world: World::Transparent,
+            ..common::emissive_cube_scene(WIDTH, HEIGHT)
+        },
+    },
+];
+
+fn cornell_box() -> Scene {
+    let diffuse = |albedo| Material {
+        albedo,
+        roughness: 1.0,
+        ..Default::default()
+    };
+    let white = diffuse(Vector3::new(0.73, 0.73, 0.73));
+    Scene {
+        camera: common::camera(
+            Point3::new(0.0, 1.0, -2.7),
+            Point3::new(0.0, 1.0, 0.0),
+            WIDTH,
+            HEIGHT,
+        ),
+        world: World::SolidColor(Vector3::new(0.0, 0.0, 0.0)),
+        objects: vec![
+            // Floor, ceiling and back wall
+            cube(Point3::new(0.0, -1.0, 0.0), 2.0, white.clone()),
+            cube(Point3::new(0.0, 3.0, 0.0), 2.0, white.clone()),
+            cube(Point3::new(0.0, 1.0, 2.0), 2.0, white.clone()),
+            // Red left and green right wall
+            cube(
+                Point3::new(-2.0, 1.0, 0.0),
+                2.0,
+                diffuse(Vector3::new(0.65, 0.05, 0.05)),
+            ),
+            cube(
+                Point3::new(2.0, 1.0, 0.0),
+                2.0,
+                diffuse(Vector3::new(0.12, 0.45, 0.15)),
+            ),
+            // Ceiling light
+            cube(
+                Point3::new(0.0, 2.0, 0.0),
+                0.8,
+                Material::with_emission(Vector3::new(1.0, 0.85, 0.6), 4.0),
+            ),
+            cube(Point3::new(-0.35, 0.3, 0.3), 0.6, white.clone()),
+            sphere(Point3::new(0.4, 0.3, -0.2), 0.3, white),
+        ],
+        render_settings: Default::default(),
+        animation: Default::default(),
+    }
+}
+
+fn glass_sphere() -> Scene {
+    Scene {
+        camera: common::camera(
+            Point3::new(0.0, 1.2, -3.5),
+            Point3::new(0.0, 0.8, 0.0),
+            WIDTH,
+            HEIGHT,
+        ),
+        world: World::SkyColor {
+            top_color: Vector3::new(0.53, 0.8, 0.92),
+            bottom_color: Vector3::new(1.0, 1.0, 1.0),
+        },
+        objects: vec![
+            sphere(
+                Point3::new(0.0, 1.0, 0.0),
+                1.0,
+                Material {
+                    albedo: Vector3::new(1.0, 1.0, 1.0),
+                    roughness: 0.0,
+                    ..Material::with_transmission(1.0, 1.5)
+                },
+            ),
+            // A colored cube behind the sphere shows the refraction
+            cube(
+                Point3::new(0.8, 0.5, 2.0),
+                1.0,
+                Material::with_albedo(Vector3::new(0.8, 0.2, 0.2)),
+            ),
+            ground(Material::with_albedo(Vector3::new(0.4, 0.4, 0.5))),
+        ],
+        render_settings: Default::default(),
+        animation: Default::default(),
+    }
+}
+
+fn metal_spheres() -> Scene {
+    let metal = |albedo, roughness| Material {
+        albedo,
+        roughness,
+        metallic: 1.0,
+        ..Default::default()
+    };
+
+    Scene {
+        camera: common::camera(
+            Point3::new(0.0, 1.5, -5.0),
+            Point3::new(0.0, 0.7, 0.0),
+            WIDTH,
+            HEIGHT,
+        ),
+        world: World::SkyColor {
+            top_color: Vector3::new(0.53, 0.8, 0.92),
+            bottom_color: Vector3::new(1.0, 1.0, 1.0),
+        },
+        objects: vec![
+            sphere(
+                Point3::new(-1.6, 0.7, 0.0),
+                0.7,
+                metal(Vector3::new(0.95, 0.95, 0.95), 0.0),
+            ),
+            sphere(
+                Point3::new(0.0, 0.7, 0.0),
+                0.7,
+                metal(Vector3::new(0.95, 0.64, 0.54), 0.4),
+            ),
+            sphere(
+                Point3::new(1.6, 0.7, 0.0),
+                0.7,
+                metal(Vector3::new(1.0, 0.78, 0.34), 0.8),
+            ),
+            ground(Material::with_albedo(Vector3::new(0.3, 0.3, 0.3))),
+        ],
+        render_settings: Default::default(),
+        animation: Default::default(),
+    }
+}
+
+/// The diffuse sphere scene lit by the given world.
+fn with_world(world: World) -> Scene {
+    Scene {
+        world,
+        ..common::diffuse_sphere_scene(WIDTH, HEIGHT)
+    }
+}
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+fn render(scene: &Scene) -> image::RgbaImage {
+    let mut renderer = CpuRenderer::new(RendererConfig {
+        max_sample_count: SAMPLE_COUNT,
+        seed: SEED,
+        ..Default::default()
+    });
+    renderer.render_frame(scene)
+}
+
+/// Runs a single case, returning a description of the failure if there is one.
+fn run(case: &GoldenCase, bless: bool) -> Result<String, String> {
+    let rendered = render(&(case.scene)());
+    let reference_path = golden_dir().join(format!("{}.png", case.name));
+
+    if bless {
+        rendered
+            .save(&reference_path)
+            .map_err(|err| format!("cannot write {}: {err}", reference_path.display()))?;
+        return Ok(String::from("blessed"));
+    }
+
+    let reference = image::open(&reference_path)
+        .map_err(|err| {
+            format!(
+                "cannot open {}: {err} (run with `--bless` to create it)",
+                reference_path.display()
+            )
+        })?
+        .into_rgba8();
+
+    let comparison = if reference.dimensions() == rendered.dimensions() {
+        // The alpha channel is taken into account by compositing over both black and white
+        let (reference_black, rendered_black) = (blend(&reference, 0.0), blend(&rendered, 0.0));
+        let (reference_white, rendered_white) = (blend(&reference, 1.0), blend(&rendered, 1.0));
+        let psnr =
+            psnr(&reference_black, &rendered_black).min(psnr(&reference_white, &rendered_white));
+        let delta_e = mean_delta_e(&reference_black, &rendered_black)
+            .max(mean_delta_e(&reference_white, &rendered_white));
+        Some((psnr, delta_e))
+    } else {
+        None
+    };
+
+    match comparison {
+        Some((psnr, delta_e)) if psnr >= MIN_PSNR && delta_e <= MAX_MEAN_DELTA_E => {
+            Ok(format!("PSNR {psnr:.1} dB, mean ΔE {delta_e:.3}"))
+        }
+        _ => {
+            let actual_path =
+                Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.actual.png", case.name));
+            let saved = match rendered.save(&actual_path) {
+                Ok(()) => format!("rendered image saved to {}", actual_path.display()),
+                Err(err) => format!("cannot save rendered image: {err}"),
+            };
+            let summary = match comparison {
+                Some((psnr, delta_e)) => format!(
+                    "PSNR {psnr:.1} dB (min {MIN_PSNR}), mean ΔE {delta_e:.3} (max {MAX_MEAN_DELTA_E})"
+                ),
+                None => format!(
+                    "size {:?} does not match the reference {:?}",
+                    rendered.dimensions(),
+                    reference.dimensions()
+                ),
+            };
+            Err(format!("{summary}; {saved}"))
+        }
+    }
+}
+
+/// Options of the default test harness that take a value, which `cargo test -- <options>`
+/// passes to every test binary.
+const HARNESS_OPTIONS_WITH_VALUES: [&str; 6] = [
+    "--test-threads",
+    "--skip",
+    "--format",
+    "--color",
+    "--logfile",
+    "-Z",
+];
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let bless = args.iter().any(|arg| arg == "--bless");
+
+    // Other options are meant for the default test harness and are ignored, except for the
+    // cases to skip
+    let mut filters = Vec::new();
+    let mut skipped = Vec::new();
+    let mut remaining = args.iter().map(String::as_str);
+    while let Some(arg) = remaining.next() {
+        if HARNESS_OPTIONS_WITH_VALUES.contains(&arg) {
+            if let Some(value) = remaining.next() {
+                if arg == "--skip" {
+                    skipped.push(value);
+                }
+            }
+        } else if let Some(value) = arg.strip_prefix("--skip=") {
+            skipped.push(value);
+        } else if !arg.starts_with('-') {
+            filters.push(arg);
+        }
+    }
+
+    let cases: Vec<_> = CASES
+        .iter()
+        .filter(|case| filters.is_empty() || filters.iter().any(|f| case.name.contains(f)))
+        .filter(|case| !skipped.iter().any(|skip| case.name.contains(skip)))
+        .collect();
+
+    // The filters usually select tests of another binary, but a typo would otherwise
+    // silently run nothing
+    if !filters.is_empty() && cases.is_empty() {
+        println!(
+            "\nwarning: no golden image test matches {}",
+            filters.join(", ")
+        );
+    }
+
+    println!("\nrunning {} golden image tests", cases.len());
+    let mut failures = Vec::new();
+    for case in &cases {
+        match run(case, bless) {
+            Ok(details) => println!("test {} ... ok ({details})", case.name),
+            Err(details) => {
+                println!("test {} ... FAILED", case.name);
+                failures.push((case.name, details));
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        println!("\nfailures:");
+        for (name, details) in &failures {
+            println!("    {name}: {details}");
+        }
+        println!("\nIf the change is intentional, update the references with:");
+        println!("    cargo test --release --test golden -- --bless");
+    }
+
+    println!(
+        "\ntest result: {}. {} passed; {} failed\n",
+        if failures.is_empty() { "ok" } else { "FAILED" },
+        cases.len() - failures.len(),
+        failures.len()
+    );
+
+    if failures.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// Composites an image over a uniform gray `background` between 0 and 1.
+fn blend(image: &image::RgbaImage, background: f64) -> RgbImage {
+    RgbImage::from_fn(image.width(), image.height(), |x, y| {
+        let [r, g, b, a] = image.get_pixel(x, y).0;
+        let alpha = a as f64 / 255.0;
+        let channel = |value: u8| (value as f64 * alpha + background * 255.0 * (1.0 - alpha)) as u8;
+        Rgb([channel(r), channel(g), channel(b)])
+    })
+}
+
+/// The peak signal-to-noise ratio between two 8-bit images, in decibels.
+fn psnr(reference: &RgbImage, test: &RgbImage) -> f64 {
+    let squared_error: f64 = reference
+        .as_raw()
+        .iter()
+        .zip(test.as_raw())
+        .map(|(&a, &b)| (a as f64 - b as f64).powi(2))
+        .sum();
+    let mean_squared_error = squared_error / reference.as_raw().len().max(1) as f64;
+    if mean_squared_error == 0.0 {
+        return f64::INFINITY;
+    }
+    10.0 * (255.0 * 255.0 / mean_squared_error).log10()
+}
+
+/// The mean CIE76 color difference between two images.
+///
+/// Like FLIP, the images are low-pass filtered first to approximate how the differences
+/// are perceived at a normal viewing distance, so isolated noisy pixels weigh less than
+/// structured changes.
+fn mean_delta_e(reference: &RgbImage, test: &RgbImage) -> f64 {
+    let reference = box_filter_lab(reference);
+    let test = box_filter_lab(test);
+    let total: f64 = reference
+        .iter()
+        .zip(&test)
+        .map(|(a, b)| {
+            a.iter()
+                .zip(b)
+                .map(|(a, b)| (a - b).powi(2))
+                .sum::<f64>()
+                .sqrt()
+        })
+        .sum();
+    total / reference.len().max(1) as f64
+}
+
+/// Converts an image to CIELAB and applies a 3x3 box filter.
+fn box_filter_lab(image: &RgbImage) -> Vec<[f64; 3]> {
+    let (width, height) = image.dimensions();
+    let lab: Vec<_> = image.pixels().map(|pixel| srgb_to_lab(pixel.0)).collect();
+
+    let mut filtered = Vec::with_capacity(lab.len());
+    for y in 0..height as i64 {
+        for x in 0..width as i64 {
+            let mut sum = [0.0; 3];
+            let mut count = 0.0;
+            for (dx, dy) in (-1..=1).flat_map(|dy| (-1..=1).map(move |dx| (dx, dy))) {
+                let (sx, sy) = (x + dx, y + dy);
+                if sx < 0 || sy < 0 || sx >= width as i64 || sy >= height as i64 {
+                    continue;
+                }
+                let value = lab[(sy * width as i64 + sx) as usize];
+                for (sum, value) in sum.iter_mut().zip(value) {
+                    *sum += value;
+                }
+                count += 1.0;
+            }
+            filtered.push(sum.map(|sum| sum / count));
+        }
+    }
+    filtered
+}
+
+fn srgb_to_lab([r, g, b]: [u8; 3]) -> [f64; 3] {
+    let linear = |value: u8| {
+        let value = value as f64 / 255.0;
+        if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let (r, g, b) = (linear(r), linear(g), linear(b));
+
+    // Linear sRGB to XYZ, normalized by the D65 white point
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+
+    let f = |t: f64| {
+        if t > 0.008856 {
+            t.cbrt()
+        } else {
+            7.787 * t + 16.0 / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
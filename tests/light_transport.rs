@@ -0,0 +1,100 @@
+//! Checks how the CPU renderer carries light along a path against closed-form results.
+
+mod common;
+
+use cgmath::{Point3, Vector3};
+use image::Rgba32FImage;
+use raydar::{
+    renderer::{cpu::CpuRenderer, RendererConfig},
+    scene::{material::Material, world::World},
+};
+
+use common::render_radiance;
+
+const SIZE: u32 = 16;
+const SAMPLE_COUNT: u32 = 64;
+const SEED: u32 = 1234;
+
+fn renderer(max_bounces: u32) -> CpuRenderer {
+    let mut config = RendererConfig {
+        max_sample_count: SAMPLE_COUNT,
+        seed: SEED,
+        ..Default::default()
+    };
+    config.bounces.total = max_bounces;
+    CpuRenderer::new(config)
+}
+
+/// The mean of each color channel over the whole image.
+fn mean_radiance(image: &Rgba32FImage) -> [f32; 3] {
+    let mut sum = [0.0; 3];
+    for pixel in image.pixels() {
+        for (sum, value) in sum.iter_mut().zip(pixel.0) {
+            *sum += value;
+        }
+    }
+    sum.map(|sum| sum / image.pixels().len() as f32)
+}
+
+/// The camera sits inside a closed, emissive diffuse sphere in a black world, so every path
+/// bounces off the sphere until it reaches the bounce limit. The light emitted at the `n`th
+/// hit is attenuated by the `n - 1` surfaces before it, which sums to a geometric series.
+#[test]
+fn emitted_light_is_attenuated_along_the_path() {
+    const ALBEDO: f32 = 0.5;
+    const EMISSION: f32 = 1.0;
+    const MAX_BOUNCES: u32 = 4;
+
+    let mut scene = common::diffuse_sphere_scene(SIZE, SIZE);
+    scene.camera = common::camera(
+        Point3::new(0.0, 0.0, 0.0),
+        Point3::new(0.0, 0.0, 1.0),
+        SIZE,
+        SIZE,
+    );
+    scene.world = World::SolidColor(Vector3::new(0.0, 0.0, 0.0));
+    scene.objects = vec![common::sphere(
+        Point3::new(0.0, 0.0, 0.0),
+        2.0,
+        Material {
+            albedo: Vector3::new(ALBEDO, ALBEDO, ALBEDO),
+            roughness: 1.0,
+            ..Material::with_emission(Vector3::new(1.0, 1.0, 1.0), EMISSION)
+        },
+    )];
+
+    let expected = EMISSION * (1.0 - ALBEDO.powi(MAX_BOUNCES as i32)) / (1.0 - ALBEDO);
+    let radiance = render_radiance(&mut renderer(MAX_BOUNCES), &scene);
+
+    for mean in mean_radiance(&radiance) {
+        assert!(
+            (mean - expected).abs() <= 0.02 * expected,
+            "mean radiance {mean} differs from {expected}"
+        );
+    }
+}
+
+/// Camera rays that miss every object in a transparent world leave the pixel black and fully
+/// transparent, while pixels showing an object stay opaque.
+#[test]
+fn transparent_world_leaves_the_background_transparent() {
+    let mut scene = common::diffuse_sphere_scene(SIZE, SIZE);
+    scene.camera = common::camera(
+        Point3::new(0.0, 0.0, -5.0),
+        Point3::new(0.0, 0.0, 0.0),
+        SIZE,
+        SIZE,
+    );
+    scene.world = World::Transparent;
+    scene.objects = vec![common::sphere(
+        Point3::new(0.0, 0.0, 0.0),
+        1.0,
+        Material::with_emission(Vector3::new(1.0, 1.0, 1.0), 1.0),
+    )];
+
+    let radiance = render_radiance(&mut renderer(4), &scene);
+
+    assert_eq!(radiance.get_pixel(0, 0).0, [0.0; 4]);
+    let alpha = radiance.get_pixel(SIZE / 2, SIZE / 2).0[3];
+    assert!((alpha - 1.0).abs() < 1e-5, "opaque pixel has alpha {alpha}");
+}
@@ -0,0 +1,184 @@
+//! White-furnace tests for the material model.
+//!
+//! A sphere is placed inside a uniform white `World::SolidColor`. A material that neither
+//! absorbs nor emits light must then be indistinguishable from the background, and no
+//! material may reflect more light than its albedo allows.
+
+mod common;
+
+use cgmath::{Point3, Vector3};
+use raydar::{
+    renderer::{cpu::CpuRenderer, RendererConfig},
+    scene::{material::Material, render_settings::BounceLimits, world::World, Scene},
+};
+
+const SIZE: u32 = 16;
+const SAMPLE_COUNT: u32 = 256;
+/// Paths that reach the bounce limit inside a glass sphere lose their energy.
+const MAX_BOUNCES: u32 = 32;
+
+/// Tolerated energy loss for non-absorbing materials, mostly from paths that are cut off
+/// by the bounce limit.
+const MAX_ENERGY_LOSS: f32 = 0.02;
+/// Tolerated energy gain, which only accounts for floating point error.
+const MAX_ENERGY_GAIN: f32 = 1e-4;
+/// Tolerated difference between renders with and without Russian roulette, which only
+/// accounts for the noise roulette adds.
+const MAX_ROULETTE_DIFFERENCE: f32 = 0.02;
+
+const ROUGHNESS: [f32; 3] = [0.0, 0.5, 1.0];
+const METALLIC: [f32; 2] = [0.0, 1.0];
+const TRANSMISSION: [f32; 2] = [0.0, 1.0];
+
+fn furnace_scene(material: Material) -> Scene {
+    Scene {
+        camera: common::camera(
+            Point3::new(0.0, 0.0, -3.0),
+            Point3::new(0.0, 0.0, 0.0),
+            SIZE,
+            SIZE,
+        ),
+        world: World::SolidColor(Vector3::new(1.0, 1.0, 1.0)),
+        objects: vec![common::sphere(Point3::new(0.0, 0.0, 0.0), 1.0, material)],
+        render_settings: Default::default(),
+        animation: Default::default(),
+    }
+}
+
+fn config() -> RendererConfig {
+    RendererConfig {
+        max_sample_count: SAMPLE_COUNT,
+        bounces: BounceLimits::uniform(MAX_BOUNCES),
+        seed: 42,
+        ..Default::default()
+    }
+}
+
+/// Renders the material and returns the mean radiance reflected by the center of the sphere.
+fn reflectance(material: Material) -> Vector3<f32> {
+    reflectance_with(material, config())
+}
+
+fn reflectance_with(material: Material, config: RendererConfig) -> Vector3<f32> {
+    let mut renderer = CpuRenderer::new(config);
+    let radiance = common::render_radiance(&mut renderer, &furnace_scene(material));
+
+    // The sphere covers a disk with a radius of about 0.6 of the image around its center,
+    // only the pixels well inside of it are measured
+    let (min, max) = (SIZE / 2 - 3, SIZE / 2 + 3);
+    let mut sum = Vector3::new(0.0, 0.0, 0.0);
+    for y in min..max {
+        for x in min..max {
+            let pixel = radiance.get_pixel(x, y);
+            sum += Vector3::new(pixel[0], pixel[1], pixel[2]);
+        }
+    }
+    sum / ((max - min) * (max - min)) as f32
+}
+
+fn materials(albedo: f32) -> impl Iterator<Item = Material> {
+    ROUGHNESS.into_iter().flat_map(move |roughness| {
+        METALLIC.into_iter().flat_map(move |metallic| {
+            TRANSMISSION.into_iter().map(move |transmission| Material {
+                albedo: Vector3::new(albedo, albedo, albedo),
+                roughness,
+                metallic,
+                transmission,
+                ..Default::default()
+            })
+        })
+    })
+}
+
+fn describe(material: &Material) -> String {
+    format!(
+        "albedo {:.2}, roughness {:.2}, metallic {:.2}, transmission {:.2}",
+        material.albedo.x, material.roughness, material.metallic, material.transmission
+    )
+}
+
+#[test]
+fn white_materials_conserve_energy() {
+    for material in materials(1.0) {
+        let description = describe(&material);
+        let reflectance = reflectance(material);
+
+        for channel in [reflectance.x, reflectance.y, reflectance.z] {
+            assert!(
+                channel <= 1.0 + MAX_ENERGY_GAIN,
+                "{description} gains energy: {reflectance:?}"
+            );
+            assert!(
+                channel >= 1.0 - MAX_ENERGY_LOSS,
+                "{description} loses energy: {reflectance:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn colored_materials_do_not_reflect_more_than_their_albedo() {
+    let albedo = 0.5;
+    for material in materials(albedo) {
+        let description = describe(&material);
+        let reflectance = reflectance(material);
+
+        for channel in [reflectance.x, reflectance.y, reflectance.z] {
+            assert!(
+                (0.0..=albedo + MAX_ENERGY_GAIN).contains(&channel),
+                "{description} reflects outside of [0, {albedo}]: {reflectance:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn opaque_materials_reflect_their_albedo_once() {
+    // On a convex object, every bounce off an opaque surface escapes to the world
+    let albedo = 0.5;
+    for material in materials(albedo).filter(|material| material.transmission == 0.0) {
+        let description = describe(&material);
+        let reflectance = reflectance(material);
+
+        for channel in [reflectance.x, reflectance.y, reflectance.z] {
+            assert!(
+                (channel - albedo).abs() <= MAX_ENERGY_LOSS,
+                "{description} reflects {reflectance:?} instead of {albedo}"
+            );
+        }
+    }
+}
+
+#[test]
+fn russian_roulette_does_not_change_the_mean_radiance() {
+    // Roulette starts after the first bounce, so that it also ends paths through opaque
+    // materials before they reach the world
+    let albedo = 0.5;
+    for material in materials(albedo) {
+        let description = describe(&material);
+        let without_roulette = reflectance_with(
+            material.clone(),
+            RendererConfig {
+                russian_roulette: false,
+                ..config()
+            },
+        );
+        let with_roulette = reflectance_with(
+            material,
+            RendererConfig {
+                russian_roulette: true,
+                russian_roulette_min_depth: 1,
+                ..config()
+            },
+        );
+
+        let difference = with_roulette - without_roulette;
+        for channel in [difference.x, difference.y, difference.z] {
+            assert!(
+                channel.abs() <= MAX_ROULETTE_DIFFERENCE,
+                "{description} reflects {with_roulette:?} with Russian roulette and \
+                 {without_roulette:?} without"
+            );
+        }
+    }
+}